@@ -1,26 +1,59 @@
 use crate::{
-    common::{app_state::AppState, db_conn::new_mongo_client},
+    common::{app_state::AppState, db_conn::new_mongo_client, secret::SecretBox},
+    config::AppConfig,
     domain::{
         auth::{
             repository::{MongoTokenStore, TokenStore},
             routes::routes as auth_routes,
             service::AuthService,
         },
-        email::service::EmailService,
-        ingestor::{routes::routes as ingestor_routes, service::IngestorService},
+        category::{
+            repository::{CategoryRuleRepo, CorrectionRepo},
+            routes::routes as category_routes,
+            service::CategoryService,
+        },
+        email::{
+            archive::{BlobStore, FsBlobStore, GridFsBlobStore, RawArchive},
+            cache::ExtractionCache,
+            extractor::{Extraction, Extractor},
+            gmail::GmailSource,
+            imap::ImapSource,
+            maildir::MaildirSource,
+            prompts::PromptLibrary,
+            repository::{
+                ArchiveRepo, DeadLetterRepo, ExtractionCacheRepo, ImapAccountRepo, LedgerRepo,
+            },
+            routes::routes as email_routes,
+            service::EmailService,
+            source::MailSource,
+            templates::ExtractionTemplates,
+        },
+        fx::{repository::FxRateRepo, routes::routes as fx_routes, service::FxService},
+        ingestor::{
+            routes::routes as ingestor_routes,
+            service::{IngestorService, PushConfig},
+        },
+        instrument::{
+            repository::InstrumentRepo, routes::routes as instrument_routes,
+            service::InstrumentService,
+        },
+        jobs::{repository::JobRepo, routes::routes as jobs_routes, service::JobService},
+        llm::client::{build_llm_chain, build_llm_client},
+        merchant::{
+            repository::MerchantRepo, routes::routes as merchant_routes, service::MerchantService,
+        },
         receipt::{routes::routes as receipt_routes, service::ReceiptService},
         user::{routes::routes as user_routes, service::UserService},
     },
 };
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use axum::{
     http::{self, HeaderValue},
     routing::get,
     Router,
 };
-use backend::config::AppConfig;
 use reqwest::Method;
-use std::{env, sync::Arc, time::Duration};
+use std::{sync::Arc, time::Duration};
 use tokio::time::interval;
 use tower_http::{
     cors::{AllowHeaders, CorsLayer},
@@ -35,21 +68,152 @@ pub async fn build_app(config: AppConfig) -> Result<AppState> {
     let user_repo = crate::domain::user::repository::UserRepo::new(&mongo_client, &config.database);
     let receipt_repo =
         crate::domain::receipt::repository::ReceiptRepo::new(&mongo_client, &config.database);
+    let migrated = receipt_repo.migrate_amounts().await?;
+    if migrated > 0 {
+        println!("Migrated amounts of {} receipts to minor units", migrated);
+    }
     let email_repo =
         crate::domain::email::repository::EmailRepo::new(&mongo_client, &config.database);
-    let auth_svc = Arc::new(AuthService::new(token_store, config.frontend_app_url.clone()).await?);
-    let user_svc = Arc::new(UserService::new(user_repo));
-    let receipt_svc = Arc::new(ReceiptService::new(receipt_repo));
-    let email_svc = Arc::new(EmailService::new(
-        env::var("OLLAMA_MODEL").expect("Unspecified Ollama Model"),
-        email_repo,
-        auth_svc.clone(),
+    let auth_svc = Arc::new(
+        AuthService::new(token_store, config.frontend_app_url.clone())
+            .await?
+            .with_admins(config.admin_emails.clone()),
+    );
+    let user_svc = Arc::new(UserService::new(user_repo.clone()));
+    let instrument_repo = InstrumentRepo::new(&mongo_client, &config.database);
+    instrument_repo.ensure_indexes().await?;
+    let instrument_svc = Arc::new(InstrumentService::new(instrument_repo.clone()));
+    let merchant_repo = MerchantRepo::new(&mongo_client, &config.database);
+    let merchant_svc = Arc::new(MerchantService::new(
+        merchant_repo.clone(),
+        receipt_repo.clone(),
+    ));
+    let llm = build_llm_chain(&config.llm, &config.llm_fallbacks)?;
+    let mut category_svc = CategoryService::new(
+        CategoryRuleRepo::new(&mongo_client, &config.database),
+        CorrectionRepo::new(&mongo_client, &config.database),
+        receipt_repo.clone(),
+        merchant_repo.clone(),
+    );
+    if config.categorize_with_llm {
+        category_svc = category_svc.with_llm(llm.clone());
+    }
+    let category_svc = Arc::new(category_svc);
+    let fx_svc = Arc::new(FxService::new(
+        FxRateRepo::new(&mongo_client, &config.database),
+        user_repo.clone(),
+        receipt_repo.clone(),
+        config.fx_base_currency.clone(),
+    ));
+    if let Some(path) = &config.fx_rates_file {
+        fx_svc.import_file(path).await?;
+    }
+    let backfilled = fx_svc.backfill().await?;
+    if backfilled.checked > 0 {
+        println!(
+            "Backfilled conversions of {} receipts, {} without a rate",
+            backfilled.checked, backfilled.missing
+        );
+    }
+    let receipt_svc = Arc::new(ReceiptService::new(
+        receipt_repo,
+        instrument_repo,
+        merchant_repo,
+        category_svc.clone(),
+        fx_svc.clone(),
     ));
+    let imap_secrets = config
+        .imap_secret_key
+        .as_deref()
+        .map(SecretBox::from_base64)
+        .transpose()
+        .context("IMAP_SECRET_KEY must be 32 base64-encoded bytes")?;
+    let imap_accounts = ImapAccountRepo::new(&mongo_client, &config.database, imap_secrets);
+    let sealed = imap_accounts.seal_passwords().await?;
+    if sealed > 0 {
+        println!("Encrypted {} stored IMAP app passwords", sealed);
+    }
+    let mut mail_sources: Vec<Arc<dyn MailSource>> = vec![
+        Arc::new(GmailSource::new(auth_svc.clone())),
+        Arc::new(ImapSource::new(imap_accounts.clone(), auth_svc.clone())),
+    ];
+    if let Some(path) = &config.maildir_path {
+        mail_sources.push(Arc::new(MaildirSource::new(path.clone())));
+    }
+    if !mail_sources.iter().any(|s| s.kind() == config.mail_source) {
+        bail!("Unknown MAIL_SOURCE: {}", config.mail_source);
+    }
+    let templates = match &config.extraction_templates {
+        Some(path) => ExtractionTemplates::load(path)?,
+        None => ExtractionTemplates::default(),
+    };
+    let prompts = match &config.prompts_dir {
+        Some(dir) => PromptLibrary::load(dir)?,
+        None => PromptLibrary::builtin()?,
+    };
+    let vision_llm = config
+        .vision_model
+        .as_ref()
+        .map(|model| build_llm_client(&config.llm.with_model(model)))
+        .transpose()?;
+    let archive_store: Option<Box<dyn BlobStore>> = match config.archive_backend.as_deref() {
+        None => None,
+        Some("fs") => Some(Box::new(FsBlobStore::new(
+            config.archive_path.clone().unwrap_or_default(),
+        ))),
+        Some("gridfs") => Some(Box::new(GridFsBlobStore::new(
+            &mongo_client,
+            &config.database,
+        ))),
+        Some(other) => bail!("Unknown ARCHIVE_BACKEND: {}", other),
+    };
+    let mut extractor = Extractor::new(Extraction {
+        llm_config: config.llm.clone(),
+        llm,
+        vision_llm,
+        templates,
+        prompts,
+    });
+    if config.extraction_cache_size > 0 {
+        extractor = extractor.with_cache(Arc::new(ExtractionCache::new(
+            ExtractionCacheRepo::new(&mongo_client, &config.database),
+            config.extraction_cache_size,
+        )));
+    }
+    let mut email_svc = EmailService::new(
+        extractor,
+        email_repo,
+        LedgerRepo::new(&mongo_client, &config.database),
+        DeadLetterRepo::new(&mongo_client, &config.database),
+        imap_accounts,
+        mail_sources,
+        config.mail_source.clone(),
+    );
+    if let Some(store) = archive_store {
+        email_svc = email_svc.with_archive(Arc::new(RawArchive::new(
+            store,
+            ArchiveRepo::new(&mongo_client, &config.database),
+            config.archive_retention_days,
+        )));
+    }
+    let email_svc = Arc::new(email_svc);
     let ingestor = Arc::new(IngestorService::new(
         email_svc.clone(),
         receipt_svc.clone(),
         user_svc.clone(),
         config.issuer_emails.clone(),
+        PushConfig {
+            secret: config.gmail_push_secret.clone(),
+            topic: config.gmail_pubsub_topic.clone(),
+        },
+    ));
+
+    let job_svc = Arc::new(JobService::new(
+        JobRepo::new(&mongo_client, &config.database),
+        email_svc.clone(),
+        receipt_svc.clone(),
+        user_svc.clone(),
+        config.issuer_emails.clone(),
     ));
 
     Ok(AppState::new(
@@ -58,6 +222,11 @@ pub async fn build_app(config: AppConfig) -> Result<AppState> {
         receipt_svc,
         email_svc,
         ingestor,
+        job_svc,
+        instrument_svc,
+        merchant_svc,
+        category_svc,
+        fx_svc,
     ))
 }
 
@@ -66,9 +235,21 @@ pub fn mount_routes(state: Arc<AppState>) -> Router {
     let auth_state = state.clone();
     let receipt_state = state.clone();
     let ingestor_state = state.clone();
+    let email_state = state.clone();
+    let jobs_state = state.clone();
+    let instrument_state = state.clone();
+    let merchant_state = state.clone();
+    let category_state = state.clone();
+    let fx_state = state.clone();
     let user_state = state;
     let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::OPTIONS])
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
         .allow_origin(
             std::env::var("FRONTEND_APP_URL")
                 .expect("FRONTEND_APP_URL needs to be set!")
@@ -90,12 +271,18 @@ pub fn mount_routes(state: Arc<AppState>) -> Router {
         .merge(receipt_routes(receipt_state))
         .merge(ingestor_routes(ingestor_state))
         .merge(user_routes(user_state))
+        .merge(email_routes(email_state))
+        .merge(jobs_routes(jobs_state))
+        .merge(instrument_routes(instrument_state))
+        .merge(merchant_routes(merchant_state))
+        .merge(category_routes(category_state))
+        .merge(fx_routes(fx_state))
         .layer(cors)
         .layer(TraceLayer::new_for_http())
 }
 
 pub fn start_sync_job(duration: u64, state: Arc<AppState>) {
-    let _ = tokio::spawn(async move {
+    tokio::spawn(async move {
         let mut ticker = interval(Duration::from_secs(duration));
         loop {
             println!("starting countdown");
@@ -109,3 +296,51 @@ pub fn start_sync_job(duration: u64, state: Arc<AppState>) {
         }
     });
 }
+
+/// Picks up jobs that were queued or running when the server last stopped.
+pub fn start_job_runner(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        if let Err(e) = state.job_service.resume_unfinished().await {
+            error!(error = %e, "failed to resume jobs");
+        }
+    });
+}
+
+/// Runs the queue of targeted syncs fed by push notifications.
+pub fn start_sync_queue(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        state.ingestor_service.run_sync_queue().await;
+    });
+}
+
+/// Periodically renews Gmail watch registrations (they expire after 7 days).
+pub fn start_watch_renewal(duration: u64, state: Arc<AppState>) {
+    if !state.ingestor_service.push_enabled() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(duration));
+        loop {
+            ticker.tick().await;
+            if let Err(e) = state.ingestor_service.renew_watches().await {
+                error!(error = %e, "watch renewal failed");
+            }
+        }
+    });
+}
+
+pub fn start_archive_retention(duration: u64, state: Arc<AppState>) {
+    if !state.email_service.archive_retention_enabled() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(duration));
+        loop {
+            ticker.tick().await;
+            match state.email_service.apply_archive_retention().await {
+                Ok(deleted) => println!("Archive retention removed {} messages", deleted),
+                Err(e) => error!(error = %e, "archive retention failed"),
+            }
+        }
+    });
+}
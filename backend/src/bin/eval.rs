@@ -0,0 +1,412 @@
+//! Offline extraction evaluation over a golden corpus.
+//!
+//! Every `<name>.eml` in the corpus folder is paired with `<name>.json`, the
+//! receipts it should yield (`{"transactions": [...]}`, as the LLM returns
+//! them). Each message goes through the same parse → template/LLM pipeline as
+//! an import, once per model, and the results are scored per field. The
+//! report is JSON with a stable layout so two runs can be diffed.
+//!
+//! ```text
+//! cargo run --release --bin eval -- <corpus> [--models a,b] [--out report.json]
+//! ```
+//!
+//! LLM settings, `EXTRACTION_TEMPLATES` and `PROMPTS_DIR` come from the same
+//! environment as the server; no database is needed.
+use anyhow::{bail, Context, Result};
+use backend::common::money::Money;
+use backend::config::llm_from_env;
+use backend::domain::email::extractor::{Extraction, Extractor};
+use backend::domain::email::prompts::PromptLibrary;
+use backend::domain::email::service::EmailService;
+use backend::domain::email::templates::ExtractionTemplates;
+use backend::domain::llm::client::build_llm_client;
+use backend::domain::receipt::models::{Receipt, ReceiptList};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Fields scored on every matched receipt pair.
+const FIELDS: [&str; 5] = ["merchant", "amount", "currency", "kind", "last4"];
+
+struct Args {
+    corpus: PathBuf,
+    models: Vec<String>,
+    out: Option<PathBuf>,
+}
+
+struct Fixture {
+    name: String,
+    eml: Vec<u8>,
+    expected: Vec<Receipt>,
+}
+
+#[derive(Serialize)]
+struct Report {
+    corpus: String,
+    fixtures: usize,
+    models: Vec<ModelReport>,
+}
+
+#[derive(Serialize)]
+struct ModelReport {
+    model: String,
+    /// Messages whose extraction returned an error.
+    failed: usize,
+    fields: BTreeMap<&'static str, FieldScore>,
+    amount_error: AmountError,
+    latency_ms: Latency,
+    messages: Vec<MessageResult>,
+}
+
+#[derive(Serialize, Default)]
+struct FieldScore {
+    expected: usize,
+    predicted: usize,
+    correct: usize,
+    precision: f64,
+    recall: f64,
+}
+
+#[derive(Serialize, Default)]
+struct AmountError {
+    /// Receipt pairs where both sides carry an amount.
+    pairs: usize,
+    mean_abs: f64,
+    max_abs: f64,
+}
+
+#[derive(Serialize, Default)]
+struct Latency {
+    mean: u64,
+    p50: u64,
+    p95: u64,
+    max: u64,
+}
+
+#[derive(Serialize)]
+struct MessageResult {
+    fixture: String,
+    ms: u64,
+    expected: usize,
+    extracted: usize,
+    /// Fields that did not match, as `<receipt index>.<field>`.
+    misses: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+    let args = parse_args()?;
+    let fixtures = load_corpus(&args.corpus)?;
+    if fixtures.is_empty() {
+        bail!(
+            "No .eml fixtures with expected .json in {}",
+            args.corpus.display()
+        );
+    }
+
+    let llm_config = llm_from_env()?;
+    let models = if args.models.is_empty() {
+        vec![llm_config.model.clone()]
+    } else {
+        args.models.clone()
+    };
+    let templates = match env::var("EXTRACTION_TEMPLATES") {
+        Ok(path) => ExtractionTemplates::load(&path)?,
+        Err(_) => ExtractionTemplates::default(),
+    };
+    let prompts = match env::var("PROMPTS_DIR") {
+        Ok(dir) => PromptLibrary::load(&dir)?,
+        Err(_) => PromptLibrary::builtin()?,
+    };
+    let vision_llm = env::var("LLM_VISION_MODEL")
+        .ok()
+        .map(|model| build_llm_client(&llm_config.with_model(&model)))
+        .transpose()?;
+    // no cache: every run has to hit the model for the timings to mean anything
+    let extractor = Extractor::new(Extraction {
+        llm_config: llm_config.clone(),
+        llm: build_llm_client(&llm_config)?,
+        vision_llm,
+        templates,
+        prompts,
+    });
+
+    let mut report = Report {
+        corpus: args.corpus.display().to_string(),
+        fixtures: fixtures.len(),
+        models: Vec::new(),
+    };
+    for model in &models {
+        let llm = extractor.llm_for(Some(model))?;
+        eprintln!("Evaluating {} on {} fixtures", llm.name(), fixtures.len());
+        let mut scores: BTreeMap<&'static str, FieldScore> =
+            FIELDS.iter().map(|f| (*f, FieldScore::default())).collect();
+        let mut amount_errors = Vec::new();
+        let mut timings = Vec::new();
+        let mut messages = Vec::new();
+        let mut failed = 0;
+
+        for fixture in &fixtures {
+            let parsed = EmailService::parse_message(&fixture.eml)
+                .with_context(|| format!("Parsing {}.eml", fixture.name))?;
+            let content = EmailService::extract_email_content(&parsed);
+            let issuer = content
+                .from_name
+                .as_deref()
+                .or(content.from_addr.as_deref())
+                .unwrap_or_default()
+                .to_string();
+
+            let started = Instant::now();
+            let result = extractor.extract(&content, &issuer, llm.as_ref()).await;
+            let ms = started.elapsed().as_millis() as u64;
+            timings.push(ms);
+
+            let (extracted, error) = match result {
+                Ok(receipts) => (receipts, None),
+                Err(e) => {
+                    failed += 1;
+                    (Vec::new(), Some(format!("{:#}", e)))
+                }
+            };
+            let misses = score(
+                &fixture.expected,
+                &extracted,
+                &mut scores,
+                &mut amount_errors,
+            );
+            messages.push(MessageResult {
+                fixture: fixture.name.clone(),
+                ms,
+                expected: fixture.expected.len(),
+                extracted: extracted.len(),
+                misses,
+                error,
+            });
+        }
+
+        for s in scores.values_mut() {
+            s.precision = ratio(s.correct, s.predicted);
+            s.recall = ratio(s.correct, s.expected);
+        }
+        report.models.push(ModelReport {
+            model: llm.name(),
+            failed,
+            fields: scores,
+            amount_error: amount_error(&amount_errors),
+            latency_ms: latency(timings),
+            messages,
+        });
+    }
+
+    print_summary(&report);
+    let json = serde_json::to_string_pretty(&report)?;
+    match &args.out {
+        Some(path) => std::fs::write(path, json + "\n")
+            .with_context(|| format!("Writing {}", path.display()))?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+fn parse_args() -> Result<Args> {
+    let mut corpus = None;
+    let mut models = Vec::new();
+    let mut out = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--models" => {
+                let list = args
+                    .next()
+                    .context("--models needs a comma-separated list")?;
+                models = list
+                    .split(',')
+                    .map(|m| m.trim().to_string())
+                    .filter(|m| !m.is_empty())
+                    .collect();
+            }
+            "--out" => out = Some(PathBuf::from(args.next().context("--out needs a path")?)),
+            other if other.starts_with("--") => bail!("Unknown option: {}", other),
+            other => corpus = Some(PathBuf::from(other)),
+        }
+    }
+    Ok(Args {
+        corpus: corpus.context("Usage: eval <corpus folder> [--models a,b] [--out report.json]")?,
+        models,
+        out,
+    })
+}
+
+/// `.eml` files that have an expected `.json` next to them, sorted by name.
+fn load_corpus(dir: &Path) -> Result<Vec<Fixture>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Reading {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "eml"))
+        .collect();
+    paths.sort();
+
+    let mut fixtures = Vec::new();
+    for path in paths {
+        let expected_path = path.with_extension("json");
+        if !expected_path.exists() {
+            eprintln!("Skipping {}: no expected .json", path.display());
+            continue;
+        }
+        let expected: ReceiptList = serde_json::from_slice(&std::fs::read(&expected_path)?)
+            .with_context(|| format!("Parsing {}", expected_path.display()))?;
+        fixtures.push(Fixture {
+            name: path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            eml: std::fs::read(&path)?,
+            expected: expected.transactions,
+        });
+    }
+    Ok(fixtures)
+}
+
+/// Pairs expected and extracted receipts by closest amount, then tallies
+/// every field. Returns the fields that missed.
+fn score(
+    expected: &[Receipt],
+    extracted: &[Receipt],
+    scores: &mut BTreeMap<&'static str, FieldScore>,
+    amount_errors: &mut Vec<f64>,
+) -> Vec<String> {
+    for field in FIELDS {
+        let s = scores.get_mut(field).expect("every field is scored");
+        s.expected += expected
+            .iter()
+            .filter(|r| field_value(r, field).is_some())
+            .count();
+        s.predicted += extracted
+            .iter()
+            .filter(|r| field_value(r, field).is_some())
+            .count();
+    }
+
+    let mut misses = Vec::new();
+    let mut unused: Vec<&Receipt> = extracted.iter().collect();
+    for (i, want) in expected.iter().enumerate() {
+        let best = unused
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| amount_gap(want, a).total_cmp(&amount_gap(want, b)))
+            .map(|(idx, _)| idx);
+        let Some(got) = best.map(|idx| unused.remove(idx)) else {
+            misses.push(format!("{}.missing", i));
+            continue;
+        };
+        if let (Some(a), Some(b)) = (want.amount, got.amount) {
+            amount_errors.push((a.to_f64() - b.to_f64()).abs());
+        }
+        for field in FIELDS {
+            let (Some(a), Some(b)) = (field_value(want, field), field_value(got, field)) else {
+                if field_value(want, field).is_some() {
+                    misses.push(format!("{}.{}", i, field));
+                }
+                continue;
+            };
+            if field_matches(field, &a, &b) {
+                scores
+                    .get_mut(field)
+                    .expect("every field is scored")
+                    .correct += 1;
+            } else {
+                misses.push(format!("{}.{}", i, field));
+            }
+        }
+    }
+    misses
+}
+
+fn amount_gap(a: &Receipt, b: &Receipt) -> f64 {
+    match (a.amount, b.amount) {
+        (Some(a), Some(b)) => (a.to_f64() - b.to_f64()).abs(),
+        _ => f64::MAX,
+    }
+}
+
+/// The field as a comparable string; `kind` is only scored where the
+/// expected file states it.
+fn field_value(receipt: &Receipt, field: &str) -> Option<String> {
+    match field {
+        "merchant" => receipt.merchant.as_ref().map(|m| m.trim().to_lowercase()),
+        "amount" => receipt.amount.map(|a| a.to_string()),
+        "currency" => receipt.currency.as_ref().map(|c| c.trim().to_uppercase()),
+        "kind" => receipt
+            .kind
+            .and_then(|k| serde_json::to_value(k).ok())
+            .and_then(|v| v.as_str().map(str::to_string)),
+        "last4" => receipt.payment.as_ref().and_then(|p| p.last4.clone()),
+        _ => None,
+    }
+}
+
+fn field_matches(field: &str, expected: &str, got: &str) -> bool {
+    match field {
+        // `12.5` and `12.50` are the same amount
+        "amount" => Money::parse(expected).is_some_and(|a| Money::parse(got) == Some(a)),
+        _ => expected == got,
+    }
+}
+
+fn ratio(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        return 0.0;
+    }
+    // three decimals keep the report diffable across runs
+    (part as f64 / whole as f64 * 1000.0).round() / 1000.0
+}
+
+fn amount_error(errors: &[f64]) -> AmountError {
+    if errors.is_empty() {
+        return AmountError::default();
+    }
+    let round = |v: f64| (v * 100.0).round() / 100.0;
+    AmountError {
+        pairs: errors.len(),
+        mean_abs: round(errors.iter().sum::<f64>() / errors.len() as f64),
+        max_abs: round(errors.iter().cloned().fold(0.0, f64::max)),
+    }
+}
+
+fn latency(mut timings: Vec<u64>) -> Latency {
+    if timings.is_empty() {
+        return Latency::default();
+    }
+    timings.sort_unstable();
+    let at = |q: f64| timings[((timings.len() - 1) as f64 * q).round() as usize];
+    Latency {
+        mean: timings.iter().sum::<u64>() / timings.len() as u64,
+        p50: at(0.5),
+        p95: at(0.95),
+        max: *timings.last().expect("timings is not empty"),
+    }
+}
+
+fn print_summary(report: &Report) {
+    for model in &report.models {
+        eprintln!(
+            "\n{} ({} fixtures, {} failed, p50 {} ms, p95 {} ms)",
+            model.model, report.fixtures, model.failed, model.latency_ms.p50, model.latency_ms.p95
+        );
+        eprintln!("  {:<10} {:>9} {:>9}", "field", "precision", "recall");
+        for (field, s) in &model.fields {
+            eprintln!("  {:<10} {:>9.3} {:>9.3}", field, s.precision, s.recall);
+        }
+        eprintln!(
+            "  amount error: mean {:.2}, max {:.2} over {} pairs",
+            model.amount_error.mean_abs, model.amount_error.max_abs, model.amount_error.pairs
+        );
+    }
+}
@@ -1,6 +1,9 @@
 use crate::domain::{
-    auth::service::AuthService, email::service::EmailService, ingestor::service::IngestorService,
-    receipt::service::ReceiptService, user::service::UserService,
+    auth::service::AuthService, category::service::CategoryService, email::service::EmailService,
+    fx::service::FxService, ingestor::service::IngestorService,
+    instrument::service::InstrumentService, jobs::service::JobService,
+    merchant::service::MerchantService, receipt::service::ReceiptService,
+    user::service::UserService,
 };
 use std::sync::Arc;
 
@@ -11,22 +14,38 @@ pub struct AppState {
     pub receipt_service: Arc<ReceiptService>,
     pub email_service: Arc<EmailService>,
     pub ingestor_service: Arc<IngestorService>,
+    pub job_service: Arc<JobService>,
+    pub instrument_service: Arc<InstrumentService>,
+    pub merchant_service: Arc<MerchantService>,
+    pub category_service: Arc<CategoryService>,
+    pub fx_service: Arc<FxService>,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         auth_service: Arc<AuthService>,
         user_service: Arc<UserService>,
         receipt_service: Arc<ReceiptService>,
         email_service: Arc<EmailService>,
         ingestor: Arc<IngestorService>,
+        job_service: Arc<JobService>,
+        instrument_service: Arc<InstrumentService>,
+        merchant_service: Arc<MerchantService>,
+        category_service: Arc<CategoryService>,
+        fx_service: Arc<FxService>,
     ) -> Self {
         Self {
-            auth_service: auth_service,
-            user_service: user_service,
-            receipt_service: receipt_service,
-            email_service: email_service,
+            auth_service,
+            user_service,
+            receipt_service,
+            email_service,
             ingestor_service: ingestor,
+            job_service,
+            instrument_service,
+            merchant_service,
+            category_service,
+            fx_service,
         }
     }
 }
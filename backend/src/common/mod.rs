@@ -1,3 +1,5 @@
 pub mod api_response;
 pub mod app_state;
 pub mod db_conn;
+pub mod money;
+pub mod secret;
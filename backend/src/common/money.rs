@@ -0,0 +1,519 @@
+use serde::{
+    de::{self, MapAccess, Visitor},
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{cmp::Ordering, fmt};
+
+/// Most decimals kept; longer fractions are rounded to this many.
+pub const MAX_EXPONENT: u32 = 9;
+/// Exponent for currencies not in `CURRENCY_EXPONENTS`, and for no currency.
+const DEFAULT_EXPONENT: u32 = 2;
+
+/// ISO 4217 currencies whose minor unit is not a hundredth.
+const CURRENCY_EXPONENTS: &[(&str, u32)] = &[
+    ("BIF", 0),
+    ("CLP", 0),
+    ("DJF", 0),
+    ("GNF", 0),
+    ("ISK", 0),
+    ("JPY", 0),
+    ("KMF", 0),
+    ("KRW", 0),
+    ("PYG", 0),
+    ("RWF", 0),
+    ("UGX", 0),
+    ("UYI", 0),
+    ("VND", 0),
+    ("VUV", 0),
+    ("XAF", 0),
+    ("XOF", 0),
+    ("XPF", 0),
+    ("BHD", 3),
+    ("IQD", 3),
+    ("JOD", 3),
+    ("KWD", 3),
+    ("LYD", 3),
+    ("OMR", 3),
+    ("TND", 3),
+    ("CLF", 4),
+    ("UYW", 4),
+];
+
+/// Decimals of the currency's minor unit, e.g. 0 for JPY and 3 for BHD.
+pub fn currency_exponent(code: Option<&str>) -> u32 {
+    let Some(code) = code else {
+        return DEFAULT_EXPONENT;
+    };
+    CURRENCY_EXPONENTS
+        .iter()
+        .find(|(c, _)| c.eq_ignore_ascii_case(code.trim()))
+        .map_or(DEFAULT_EXPONENT, |(_, exponent)| *exponent)
+}
+
+/// An exact decimal amount: `minor × 10^-exponent`, so `12.30` is
+/// `{minor: 1230, exponent: 2}`. Values compare equal regardless of
+/// exponent.
+///
+/// Stored in Mongo as `{minor, exponent}` and sent over the API as a decimal
+/// string such as `"12.30"`. Reads also take plain numbers, which is how
+/// LLM output and receipts stored before this type arrive.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Money {
+    minor: i64,
+    exponent: u32,
+}
+
+impl Money {
+    pub fn new(minor: i64, exponent: u32) -> Self {
+        Money { minor, exponent }
+    }
+
+    pub fn minor(self) -> i64 {
+        self.minor
+    }
+
+    pub fn exponent(self) -> u32 {
+        self.exponent
+    }
+
+    /// Parses a plain decimal such as `-1234.5`, keeping the written decimals.
+    pub fn parse(raw: &str) -> Option<Money> {
+        let raw = raw.trim();
+        let (negative, digits) = match raw.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, raw.strip_prefix('+').unwrap_or(raw)),
+        };
+        let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+        if (int.is_empty() && frac.is_empty())
+            || !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit())
+            || frac.len() > 2 * MAX_EXPONENT as usize
+        {
+            return None;
+        }
+        let mut minor: i128 = 0;
+        for c in int.chars().chain(frac.chars()) {
+            minor = minor
+                .checked_mul(10)?
+                .checked_add(c.to_digit(10)? as i128)?;
+        }
+        let exponent = frac.len() as u32;
+        let minor = if negative { -minor } else { minor };
+        let target = exponent.min(MAX_EXPONENT);
+        let minor = shift(minor, exponent, target, Rounding::HalfAwayFromZero)?;
+        Some(Money {
+            minor: minor.try_into().ok()?,
+            exponent: target,
+        })
+    }
+
+    /// The float's shortest decimal form, so `12.3` becomes exactly `12.3`.
+    pub fn from_f64(value: f64) -> Option<Money> {
+        if !value.is_finite() {
+            return None;
+        }
+        Money::parse(&value.to_string())
+            .or_else(|| Money::parse(&format!("{:.*}", MAX_EXPONENT as usize, value)))
+    }
+
+    /// For display and statistics only; never feed the result back into sums.
+    pub fn to_f64(self) -> f64 {
+        self.minor as f64 / 10f64.powi(self.exponent as i32)
+    }
+
+    /// The same amount with `exponent` decimals, rounding half away from zero.
+    /// `None` when it does not fit.
+    pub fn rescale(self, exponent: u32) -> Option<Money> {
+        self.rescale_with(exponent, Rounding::HalfAwayFromZero)
+    }
+
+    /// Smallest amount with `exponent` decimals that is at least `self`.
+    pub fn ceil_to(self, exponent: u32) -> Option<Money> {
+        self.rescale_with(exponent, Rounding::Ceil)
+    }
+
+    /// Largest amount with `exponent` decimals that is at most `self`.
+    pub fn floor_to(self, exponent: u32) -> Option<Money> {
+        self.rescale_with(exponent, Rounding::Floor)
+    }
+
+    fn rescale_with(self, exponent: u32, rounding: Rounding) -> Option<Money> {
+        let minor = shift(self.minor as i128, self.exponent, exponent, rounding)?;
+        Some(Money {
+            minor: minor.try_into().ok()?,
+            exponent,
+        })
+    }
+
+    pub fn abs(self) -> Money {
+        Money {
+            minor: self.minor.saturating_abs(),
+            exponent: self.exponent,
+        }
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.minor < 0
+    }
+
+    pub fn checked_add(self, other: Money) -> Option<Money> {
+        let exponent = self.exponent.max(other.exponent);
+        let a = self.rescale(exponent)?.minor;
+        let b = other.rescale(exponent)?.minor;
+        Some(Money {
+            minor: a.checked_add(b)?,
+            exponent,
+        })
+    }
+
+    pub fn checked_sub(self, other: Money) -> Option<Money> {
+        self.checked_add(Money {
+            minor: other.minor.checked_neg()?,
+            exponent: other.exponent,
+        })
+    }
+
+    /// Exact product, rounded to `MAX_EXPONENT` decimals when longer.
+    pub fn checked_mul(self, other: Money) -> Option<Money> {
+        let minor = (self.minor as i128).checked_mul(other.minor as i128)?;
+        let exponent = self.exponent + other.exponent;
+        let target = exponent.min(MAX_EXPONENT);
+        let minor = shift(minor, exponent, target, Rounding::HalfAwayFromZero)?;
+        Some(Money {
+            minor: minor.try_into().ok()?,
+            exponent: target,
+        })
+    }
+
+    /// Quotient with up to `MAX_EXPONENT` decimals, rounded half away from
+    /// zero and without trailing zeros. `None` when dividing by zero.
+    pub fn checked_div(self, other: Money) -> Option<Money> {
+        if other.minor == 0 {
+            return None;
+        }
+        // (a / 10^ea) / (b / 10^eb) = a * 10^(eb + T - ea) / b at T decimals
+        let scale = (other.exponent + MAX_EXPONENT).checked_sub(self.exponent)?;
+        let numerator = (self.minor as i128).checked_mul(10i128.checked_pow(scale)?)?;
+        let denominator = other.minor as i128;
+        let (quotient, remainder) = (numerator / denominator, numerator % denominator);
+        let quotient = if remainder.abs() * 2 >= denominator.abs() {
+            quotient + (numerator.signum() * denominator.signum())
+        } else {
+            quotient
+        };
+        let mut result = Money {
+            minor: quotient.try_into().ok()?,
+            exponent: MAX_EXPONENT,
+        };
+        while result.exponent > 0 && result.minor % 10 == 0 {
+            result.minor /= 10;
+            result.exponent -= 1;
+        }
+        Some(result)
+    }
+
+    /// The value in units of `10^-MAX_EXPONENT`, for comparisons.
+    fn scaled(self) -> i128 {
+        self.minor as i128 * 10i128.pow(MAX_EXPONENT - self.exponent.min(MAX_EXPONENT))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Rounding {
+    HalfAwayFromZero,
+    Ceil,
+    Floor,
+}
+
+/// Moves `minor` from `from` to `to` decimals.
+fn shift(minor: i128, from: u32, to: u32, rounding: Rounding) -> Option<i128> {
+    if to >= from {
+        return minor.checked_mul(10i128.checked_pow(to - from)?);
+    }
+    let divisor = 10i128.checked_pow(from - to)?;
+    let (quotient, remainder) = (minor / divisor, minor % divisor);
+    let adjust = match rounding {
+        Rounding::HalfAwayFromZero if remainder.abs() * 2 >= divisor => remainder.signum(),
+        Rounding::Ceil if remainder > 0 => 1,
+        Rounding::Floor if remainder < 0 => -1,
+        _ => 0,
+    };
+    Some(quotient + adjust)
+}
+
+impl PartialEq for Money {
+    fn eq(&self, other: &Money) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Money {}
+
+impl PartialOrd for Money {
+    fn partial_cmp(&self, other: &Money) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Money {
+    fn cmp(&self, other: &Money) -> Ordering {
+        self.scaled().cmp(&other.scaled())
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.minor < 0 { "-" } else { "" };
+        let digits = self.minor.unsigned_abs().to_string();
+        let exponent = self.exponent as usize;
+        if exponent == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = exponent + 1);
+        let (int, frac) = digits.split_at(digits.len() - exponent);
+        write!(f, "{}{}.{}", sign, int, frac)
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return serializer.collect_str(self);
+        }
+        let mut state = serializer.serialize_struct("Money", 2)?;
+        state.serialize_field("minor", &self.minor)?;
+        state.serialize_field("exponent", &(self.exponent as i32))?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Money, D::Error> {
+        deserializer.deserialize_any(MoneyVisitor)
+    }
+}
+
+struct MoneyVisitor;
+
+impl<'de> Visitor<'de> for MoneyVisitor {
+    type Value = Money;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a decimal string, a number or {minor, exponent}")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Money, E> {
+        Money::parse(value).ok_or_else(|| E::custom(format!("invalid amount `{}`", value)))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Money, E> {
+        Ok(Money::new(value, 0))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Money, E> {
+        let minor = value
+            .try_into()
+            .map_err(|_| E::custom(format!("amount {} is too large", value)))?;
+        Ok(Money::new(minor, 0))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Money, E> {
+        Money::from_f64(value).ok_or_else(|| E::custom(format!("invalid amount {}", value)))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Money, A::Error> {
+        let (mut minor, mut exponent) = (None, None);
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "minor" => minor = Some(map.next_value::<i64>()?),
+                "exponent" => exponent = Some(map.next_value::<i64>()?),
+                _ => {
+                    map.next_value::<de::IgnoredAny>()?;
+                }
+            }
+        }
+        let minor = minor.ok_or_else(|| de::Error::missing_field("minor"))?;
+        let exponent = exponent.ok_or_else(|| de::Error::missing_field("exponent"))?;
+        match u32::try_from(exponent) {
+            Ok(exponent) if exponent <= MAX_EXPONENT => Ok(Money::new(minor, exponent)),
+            _ => Err(de::Error::custom(format!("invalid exponent {}", exponent))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::{self, doc, Document};
+
+    fn money(raw: &str) -> Money {
+        Money::parse(raw).unwrap()
+    }
+
+    #[test]
+    fn parse_and_display_round_trip() {
+        for raw in ["0", "12", "12.30", "-0.05", "1234.567", "0.000000001"] {
+            assert_eq!(money(raw).to_string(), raw);
+        }
+        assert_eq!(money("+7.5").to_string(), "7.5");
+        assert_eq!(money(".5").to_string(), "0.5");
+        assert_eq!(money("5.").to_string(), "5");
+        assert_eq!(money("12.30"), money("12.3"));
+        assert_eq!(money("12.30").exponent(), 2);
+    }
+
+    #[test]
+    fn parse_rejects_non_decimals() {
+        for raw in ["", "-", ".", "1,000", "1e3", "12.3.4", "abc", "--1"] {
+            assert_eq!(Money::parse(raw), None, "{:?}", raw);
+        }
+        assert_eq!(Money::parse(&format!("0.{}", "1".repeat(19))), None);
+        assert_eq!(Money::parse("99999999999999999999"), None);
+    }
+
+    #[test]
+    fn parse_caps_the_exponent() {
+        let m = money("1.0000000004");
+        assert_eq!((m.minor(), m.exponent()), (1_000_000_000, MAX_EXPONENT));
+        let m = money("-1.0000000005");
+        assert_eq!((m.minor(), m.exponent()), (-1_000_000_001, MAX_EXPONENT));
+        assert_eq!(money("0.123456789123456789").exponent(), MAX_EXPONENT);
+    }
+
+    #[test]
+    fn from_f64_keeps_the_shortest_form() {
+        assert_eq!(Money::from_f64(12.3).unwrap().to_string(), "12.3");
+        assert_eq!(Money::from_f64(0.1 + 0.2).unwrap().exponent(), 9);
+        assert_eq!(Money::from_f64(f64::NAN), None);
+        assert_eq!(Money::from_f64(f64::INFINITY), None);
+    }
+
+    #[test]
+    fn rescale_rounds_half_away_from_zero() {
+        assert_eq!(money("2.345").rescale(2).unwrap().to_string(), "2.35");
+        assert_eq!(money("-2.345").rescale(2).unwrap().to_string(), "-2.35");
+        assert_eq!(money("-2.344").rescale(2).unwrap().to_string(), "-2.34");
+        assert_eq!(money("-1.5").rescale(0).unwrap().to_string(), "-2");
+        assert_eq!(money("-1.2").rescale(3).unwrap().to_string(), "-1.200");
+        assert_eq!(Money::new(i64::MAX, 0).rescale(1), None);
+    }
+
+    #[test]
+    fn ceil_and_floor_on_negatives() {
+        assert_eq!(money("-2.341").ceil_to(2).unwrap().to_string(), "-2.34");
+        assert_eq!(money("-2.341").floor_to(2).unwrap().to_string(), "-2.35");
+        assert_eq!(money("2.341").ceil_to(2).unwrap().to_string(), "2.35");
+        assert_eq!(money("2.341").floor_to(2).unwrap().to_string(), "2.34");
+        assert_eq!(money("-2.34").ceil_to(2).unwrap().to_string(), "-2.34");
+        assert_eq!(money("-0.5").ceil_to(0).unwrap().to_string(), "0");
+        assert_eq!(money("-0.5").floor_to(0).unwrap().to_string(), "-1");
+    }
+
+    #[test]
+    fn add_and_sub_align_exponents() {
+        assert_eq!(
+            money("1.5").checked_add(money("0.25")).unwrap().to_string(),
+            "1.75"
+        );
+        assert_eq!(
+            money("1").checked_sub(money("1.001")).unwrap().to_string(),
+            "-0.001"
+        );
+        assert_eq!(Money::new(i64::MAX, 0).checked_add(money("1")), None);
+        assert_eq!(Money::new(0, 0).checked_sub(Money::new(i64::MIN, 0)), None);
+    }
+
+    #[test]
+    fn checked_mul() {
+        assert_eq!(
+            money("19.99").checked_mul(money("3")).unwrap().to_string(),
+            "59.97"
+        );
+        assert_eq!(
+            money("-1.05")
+                .checked_mul(money("1.5"))
+                .unwrap()
+                .to_string(),
+            "-1.575"
+        );
+        // 0.00001 × 0.00001 has 10 decimals and is rounded to 9
+        assert_eq!(
+            money("0.00005").checked_mul(money("0.00001")).unwrap(),
+            money("0.000000001")
+        );
+        assert_eq!(Money::new(i64::MAX, 0).checked_mul(money("2")), None);
+        assert_eq!(Money::new(i64::MAX, 9).checked_mul(money("2")), None);
+    }
+
+    #[test]
+    fn checked_div() {
+        assert_eq!(
+            money("1.4567")
+                .checked_div(money("1.0919"))
+                .unwrap()
+                .to_string(),
+            "1.334096529"
+        );
+        assert_eq!(
+            money("10").checked_div(money("4")).unwrap().to_string(),
+            "2.5"
+        );
+        assert_eq!(
+            money("-2").checked_div(money("3")).unwrap().to_string(),
+            "-0.666666667"
+        );
+        assert_eq!(money("1").checked_div(money("0.00")), None);
+        assert_eq!(Money::new(i64::MAX, 0).checked_div(money("0.5")), None);
+    }
+
+    #[test]
+    fn compares_by_value() {
+        assert!(money("-1") < money("0.5"));
+        assert!(money("2.10") > money("2.09"));
+        assert_eq!(money("0").abs(), money("-0.00"));
+        assert!(money("-0.01").is_negative());
+        assert_eq!(money("-3.5").abs().to_string(), "3.5");
+    }
+
+    #[test]
+    fn human_readable_serde_uses_decimal_strings() {
+        let json = serde_json::to_string(&money("12.30")).unwrap();
+        assert_eq!(json, r#""12.30""#);
+        let back: Money = serde_json::from_str(&json).unwrap();
+        assert_eq!((back.minor(), back.exponent()), (1230, 2));
+
+        let from_number: Money = serde_json::from_str("12.3").unwrap();
+        assert_eq!(from_number.to_string(), "12.3");
+        let from_int: Money = serde_json::from_str("-7").unwrap();
+        assert_eq!(from_int.to_string(), "-7");
+        assert!(serde_json::from_str::<Money>(r#""12,30""#).is_err());
+    }
+
+    #[test]
+    fn raw_serde_uses_minor_and_exponent() {
+        #[derive(Serialize, Deserialize)]
+        struct Row {
+            amount: Money,
+        }
+
+        let raw = bson::to_raw_document_buf(&Row {
+            amount: money("-12.30"),
+        })
+        .unwrap();
+        let stored: Document = raw.to_document().unwrap();
+        assert_eq!(
+            stored,
+            doc! {"amount": {"minor": -1230_i64, "exponent": 2_i32}}
+        );
+
+        let back: Row = bson::from_slice(raw.as_bytes()).unwrap();
+        assert_eq!((back.amount.minor(), back.amount.exponent()), (-1230, 2));
+
+        // documents written before amounts were exact hold plain doubles
+        let legacy: Row = bson::from_document(doc! {"amount": 12.3}).unwrap();
+        assert_eq!(legacy.amount.to_string(), "12.3");
+
+        let bad = doc! {"amount": {"minor": 1_i64, "exponent": 10_i32}};
+        assert!(bson::from_document::<Row>(bad).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::{anyhow, bail, Context, Result};
+use base64::Engine;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use std::{fmt, sync::Arc};
+
+/// Marks a sealed value, so values stored before encryption can be told apart.
+const SEALED_PREFIX: &str = "enc:v1:";
+
+/// Encrypts credentials kept in Mongo (AES-256-GCM with a random nonce).
+/// Sealed values are `enc:v1:` followed by base64 of the nonce and ciphertext.
+#[derive(Clone)]
+pub struct SecretBox {
+    key: Arc<LessSafeKey>,
+    rng: SystemRandom,
+}
+
+impl fmt::Debug for SecretBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretBox(..)")
+    }
+}
+
+impl SecretBox {
+    /// Key from 32 base64-encoded bytes, e.g. `openssl rand -base64 32`.
+    pub fn from_base64(raw: &str) -> Result<Self> {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(raw.trim())
+            .context("key is not base64")?;
+        let key = UnboundKey::new(&AES_256_GCM, &bytes)
+            .map_err(|_| anyhow!("key must be 32 bytes, got {}", bytes.len()))?;
+        Ok(SecretBox {
+            key: Arc::new(LessSafeKey::new(key)),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    pub fn is_sealed(value: &str) -> bool {
+        value.starts_with(SEALED_PREFIX)
+    }
+
+    pub fn seal(&self, plaintext: &str) -> Result<String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("no randomness for a nonce"))?;
+        let mut sealed = plaintext.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut sealed,
+            )
+            .map_err(|_| anyhow!("failed to encrypt secret"))?;
+        let mut out = nonce.to_vec();
+        out.extend(sealed);
+        Ok(format!(
+            "{}{}",
+            SEALED_PREFIX,
+            base64::engine::general_purpose::STANDARD.encode(out)
+        ))
+    }
+
+    pub fn open(&self, sealed: &str) -> Result<String> {
+        let Some(encoded) = sealed.strip_prefix(SEALED_PREFIX) else {
+            bail!("secret is not sealed");
+        };
+        let mut bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .context("sealed secret is not base64")?;
+        if bytes.len() < NONCE_LEN {
+            bail!("sealed secret is too short");
+        }
+        let mut ciphertext = bytes.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&bytes)
+            .map_err(|_| anyhow!("sealed secret has a bad nonce"))?;
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut ciphertext)
+            .map_err(|_| anyhow!("failed to decrypt secret; was the key changed?"))?;
+        Ok(String::from_utf8(plaintext.to_vec())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+    const OTHER_KEY: &str = "//////////////////////////////////////////8=";
+
+    #[test]
+    fn sealed_values_open_with_the_same_key_only() {
+        let secrets = SecretBox::from_base64(KEY).unwrap();
+        let sealed = secrets.seal("app password").unwrap();
+        assert!(SecretBox::is_sealed(&sealed));
+        assert!(!sealed.contains("app password"));
+        assert_ne!(sealed, secrets.seal("app password").unwrap());
+        assert_eq!(secrets.open(&sealed).unwrap(), "app password");
+
+        let other = SecretBox::from_base64(OTHER_KEY).unwrap();
+        assert!(other.open(&sealed).is_err());
+    }
+
+    #[test]
+    fn bad_keys_and_values_are_rejected() {
+        let cases = ["", "not base64!", "AAECAwQ="];
+        for key in cases {
+            assert!(SecretBox::from_base64(key).is_err(), "{:?}", key);
+        }
+        let secrets = SecretBox::from_base64(KEY).unwrap();
+        // flip one ciphertext character
+        let sealed = secrets.seal("pw").unwrap();
+        let mid = SEALED_PREFIX.len() + 20;
+        let flipped = if &sealed[mid..=mid] == "A" { "B" } else { "A" };
+        let tampered = format!("{}{}{}", &sealed[..mid], flipped, &sealed[mid + 1..]);
+        let cases = ["pw", "enc:v1:", "enc:v1:AAAA", tampered.as_str()];
+        for value in cases {
+            assert!(secrets.open(value).is_err(), "{:?}", value);
+        }
+    }
+}
@@ -2,13 +2,40 @@ use std::env;
 
 use anyhow::{Context, Result};
 
+use crate::domain::{fx::models::is_currency_code, llm::client::LlmConfig};
+
 #[derive(Clone, Debug)]
 pub struct AppConfig {
     pub mongo_uri: String,
     pub database: String,
-    pub ollama_model: String,
+    pub llm: LlmConfig,
+    pub llm_fallbacks: Vec<LlmConfig>,
+    pub vision_model: Option<String>,
     pub frontend_app_url: String,
     pub issuer_emails: Vec<String>,
+    /// Users given the `admin` role, which shared data such as the merchant
+    /// registry requires for writes.
+    pub admin_emails: Vec<String>,
+    /// Base64 AES-256 key that IMAP app passwords are encrypted with.
+    pub imap_secret_key: Option<String>,
+    pub extraction_templates: Option<String>,
+    /// Folder of extra prompt files, added to the built-in ones.
+    pub prompts_dir: Option<String>,
+    pub mail_source: String,
+    pub maildir_path: Option<String>,
+    pub gmail_push_secret: Option<String>,
+    pub gmail_pubsub_topic: Option<String>,
+    pub archive_backend: Option<String>,
+    pub archive_path: Option<String>,
+    pub archive_retention_days: Option<u32>,
+    /// Extraction results kept in memory; `0` turns the cache off.
+    pub extraction_cache_size: usize,
+    /// Let the LLM pick a category when no rule or merchant default applies.
+    pub categorize_with_llm: bool,
+    /// CSV or ECB XML file loaded into the FX rate table on startup.
+    pub fx_rates_file: Option<String>,
+    /// Base currency of users who have not picked one.
+    pub fx_base_currency: String,
 }
 
 impl AppConfig {
@@ -17,8 +44,15 @@ impl AppConfig {
             env::var("MONGO_URI").context("MONGO_URI must be set (Mongo connection string)")?;
         let database =
             env::var("DATABASE").context("DATABASE must be set (Mongo database name)")?;
-        let ollama_model =
-            env::var("OLLAMA_MODEL").context("OLLAMA_MODEL must be set (Model name)")?;
+        let llm = llm_from_env()?;
+        let llm_fallbacks: Vec<LlmConfig> = match env::var("LLM_FALLBACKS") {
+            Ok(raw) => serde_json::from_str(&raw)
+                .context("LLM_FALLBACKS must be a JSON array of LLM configs")?,
+            Err(_) => vec![],
+        };
+        let vision_model = env::var("LLM_VISION_MODEL")
+            .or_else(|_| env::var("OLLAMA_VISION_MODEL"))
+            .ok();
         let frontend_app_url =
             env::var("FRONTEND_APP_URL").context("FRONTEND_APP_URL must be set.")?;
         let raw_issuers = env::var("ISSUER_EMAILS")
@@ -29,13 +63,105 @@ impl AppConfig {
             !issuer_emails.is_empty(),
             "ISSUER_EMAILS must contain at least one entry"
         );
+        let admin_emails: Vec<String> = match env::var("ADMIN_EMAILS") {
+            Ok(raw) if !raw.is_empty() => serde_json::from_str(&raw)
+                .context("ADMIN_EMAILS must be a JSON array of strings")?,
+            _ => vec![],
+        };
+
+        let imap_secret_key = env::var("IMAP_SECRET_KEY").ok().filter(|k| !k.is_empty());
+
+        let extraction_templates = env::var("EXTRACTION_TEMPLATES").ok();
+        let prompts_dir = env::var("PROMPTS_DIR").ok();
+        let mail_source = env::var("MAIL_SOURCE").unwrap_or_else(|_| "gmail".to_string());
+        let maildir_path = env::var("MAILDIR_PATH").ok();
+        anyhow::ensure!(
+            mail_source != "maildir" || maildir_path.is_some(),
+            "MAILDIR_PATH must be set when MAIL_SOURCE=maildir"
+        );
+        let gmail_push_secret = env::var("GMAIL_PUSH_SECRET").ok();
+        let gmail_pubsub_topic = env::var("GMAIL_PUBSUB_TOPIC").ok();
+        let archive_backend = env::var("ARCHIVE_BACKEND").ok();
+        let archive_path = env::var("ARCHIVE_PATH").ok();
+        anyhow::ensure!(
+            archive_backend.as_deref() != Some("fs") || archive_path.is_some(),
+            "ARCHIVE_PATH must be set when ARCHIVE_BACKEND=fs"
+        );
+        let archive_retention_days = env::var("ARCHIVE_RETENTION_DAYS")
+            .ok()
+            .map(|raw| raw.parse())
+            .transpose()
+            .context("ARCHIVE_RETENTION_DAYS must be a number of days")?;
+        let extraction_cache_size = env::var("EXTRACTION_CACHE_SIZE")
+            .ok()
+            .map(|raw| raw.parse())
+            .transpose()
+            .context("EXTRACTION_CACHE_SIZE must be a number of entries")?
+            .unwrap_or(1000);
+        let categorize_with_llm = env::var("CATEGORIZE_WITH_LLM")
+            .ok()
+            .map(|raw| raw.parse())
+            .transpose()
+            .context("CATEGORIZE_WITH_LLM must be true or false")?
+            .unwrap_or(true);
+        let fx_rates_file = env::var("FX_RATES_FILE").ok().filter(|p| !p.is_empty());
+        let fx_base_currency = env::var("FX_BASE_CURRENCY")
+            .ok()
+            .map(|raw| raw.trim().to_uppercase())
+            .filter(|code| !code.is_empty())
+            .unwrap_or_else(|| "USD".to_string());
+        anyhow::ensure!(
+            is_currency_code(&fx_base_currency),
+            "FX_BASE_CURRENCY must be an ISO 4217 code such as USD"
+        );
 
         Ok(Self {
             mongo_uri,
             database,
-            ollama_model,
+            llm,
+            llm_fallbacks,
+            vision_model,
             frontend_app_url,
             issuer_emails,
+            admin_emails,
+            imap_secret_key,
+            extraction_templates,
+            prompts_dir,
+            mail_source,
+            maildir_path,
+            gmail_push_secret,
+            gmail_pubsub_topic,
+            archive_backend,
+            archive_path,
+            archive_retention_days,
+            extraction_cache_size,
+            categorize_with_llm,
+            fx_rates_file,
+            fx_base_currency,
         })
     }
 }
+
+/// The primary LLM backend from `LLM_*` variables; shared with the eval binary.
+pub fn llm_from_env() -> Result<LlmConfig> {
+    Ok(LlmConfig {
+        provider: env::var("LLM_PROVIDER").unwrap_or_else(|_| "ollama".to_string()),
+        model: env::var("LLM_MODEL")
+            .or_else(|_| env::var("OLLAMA_MODEL"))
+            .context("LLM_MODEL (or OLLAMA_MODEL) must be set (Model name)")?,
+        host: env::var("LLM_HOST").ok(),
+        timeout_secs: match env::var("LLM_TIMEOUT_SECS") {
+            Ok(raw) => raw
+                .parse()
+                .context("LLM_TIMEOUT_SECS must be a number of seconds")?,
+            Err(_) => 120,
+        },
+        temperature: env::var("LLM_TEMPERATURE")
+            .ok()
+            .map(|raw| raw.parse())
+            .transpose()
+            .context("LLM_TEMPERATURE must be a number")?,
+        api_key: env::var("LLM_API_KEY").ok(),
+        mock_responses: vec![],
+    })
+}
@@ -16,7 +16,7 @@ use time::{Duration, OffsetDateTime};
 
 use crate::{
     common::{api_response::ApiResponse, app_state::AppState},
-    domain::auth::models::TokenRecord,
+    domain::auth::models::{Claims, TokenRecord},
 };
 
 #[derive(Deserialize)]
@@ -127,7 +127,7 @@ pub async fn google_oauth_callback(
             scope: scope.unwrap_or_else(|| "https://www.googleapis.com/auth/gmail.readonly".into()),
             access_token: access,
             refresh_token: refresh,
-            expires_at: expires_at,
+            expires_at,
             updated_at: OffsetDateTime::now_utc(),
         };
 
@@ -192,6 +192,24 @@ pub async fn authorization_middleware(
     Ok(next.run(req).await)
 }
 
+/// Lets through only callers with the `admin` role. Goes inside
+/// `authorization_middleware`, which provides the claims.
+pub async fn admin_middleware(
+    State(app): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Result<Response<Body>, (StatusCode, String)> {
+    let claims = req
+        .extensions()
+        .get::<Claims>()
+        .cloned()
+        .ok_or((StatusCode::UNAUTHORIZED, "Not signed in".to_string()))?;
+    app.auth_service
+        .validate_roles(claims, "admin")
+        .map_err(|e| (StatusCode::FORBIDDEN, e.to_string()))?;
+    Ok(next.run(req).await)
+}
+
 fn extract_bearer_token(req: &Request) -> Option<String> {
     req.headers()
         .get(http::header::AUTHORIZATION)
@@ -216,7 +234,7 @@ fn extract_session_cookie(req: &Request) -> Option<String> {
                 let trimmed = pair.trim();
                 let mut parts = trimmed.splitn(2, '=');
                 match (parts.next(), parts.next()) {
-                    (Some(name), Some(val)) if name == "session" => Some(val.to_string()),
+                    (Some("session"), Some(val)) => Some(val.to_string()),
                     _ => None,
                 }
             })
@@ -33,6 +33,7 @@ pub struct AuthService {
     jwt_decoding: DecodingKey,
     jwt_validation: Validation,
     pub frontend_url: String,
+    admins: Vec<String>,
 }
 
 impl AuthService {
@@ -69,9 +70,16 @@ impl AuthService {
             jwt_decoding,
             jwt_validation,
             frontend_url,
+            admins: vec![],
         })
     }
 
+    /// Users whose sessions get the `admin` role.
+    pub fn with_admins(mut self, admins: Vec<String>) -> Self {
+        self.admins = admins;
+        self
+    }
+
     pub fn auth_redirect(&self) -> (Url, CsrfToken, String) {
         let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
         let (auth_url, csrf_token) = self
@@ -179,7 +187,7 @@ impl AuthService {
             exp: (OffsetDateTime::now_utc() + TimeDuration::hours(1)).unix_timestamp(),
             iss: "finOS".to_string(),
             aud: "finOS".to_string(),
-            roles: vec!["user".to_string()],
+            roles: self.roles_of(&token_record.user_id),
         };
 
         let jwt = encode(&Header::default(), &claims, &self.jwt_encoding)
@@ -201,10 +209,22 @@ impl AuthService {
         Ok(claims)
     }
 
+    fn roles_of(&self, user_id: &str) -> Vec<String> {
+        let mut roles = vec!["user".to_string()];
+        if self
+            .admins
+            .iter()
+            .any(|admin| admin.eq_ignore_ascii_case(user_id))
+        {
+            roles.push("admin".to_string());
+        }
+        roles
+    }
+
     pub fn validate_roles(&self, claims: Claims, required_role: &str) -> Result<Claims> {
         let user_roles = &claims.roles;
         ensure!(
-            user_roles.iter().all(|role| role == required_role),
+            user_roles.iter().any(|role| role == required_role),
             "missing role: {required_role}"
         );
         Ok(claims)
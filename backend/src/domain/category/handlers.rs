@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    common::{api_response::ApiResponse, app_state::AppState},
+    domain::{auth::models::Claims, category::models::CategoryRuleSpec},
+};
+
+#[derive(Deserialize)]
+pub struct AcceptSuggestion {
+    pub key: String,
+}
+
+/// Categories the caller uses on receipts and in rules.
+pub async fn list_categories(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state.category_service.categories(&claims.sub).await {
+        Ok(categories) => Ok(Json(ApiResponse::success(categories))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!(
+                "Failed to list categories: {}",
+                e
+            ))),
+        )),
+    }
+}
+
+pub async fn list_rules(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state.category_service.rules(&claims.sub).await {
+        Ok(rules) => Ok(Json(ApiResponse::success(rules))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!("Failed to list rules: {}", e))),
+        )),
+    }
+}
+
+pub async fn create_rule(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CategoryRuleSpec>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    if let Err(reason) = request.validate() {
+        return Err((StatusCode::BAD_REQUEST, Json(ApiResponse::error(reason))));
+    }
+    match state
+        .category_service
+        .create_rule(&claims.sub, request)
+        .await
+    {
+        Ok(rule) => Ok((StatusCode::CREATED, Json(ApiResponse::success(rule)))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!("Failed to create rule: {}", e))),
+        )),
+    }
+}
+
+pub async fn update_rule(
+    Extension(claims): Extension<Claims>,
+    Path(rule_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CategoryRuleSpec>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    if let Err(reason) = request.validate() {
+        return Err((StatusCode::BAD_REQUEST, Json(ApiResponse::error(reason))));
+    }
+    match state
+        .category_service
+        .update_rule(&claims.sub, &rule_id, request)
+        .await
+    {
+        Ok(Some(rule)) => Ok(Json(ApiResponse::success(rule))),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error("Rule not found".to_string())),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!("Failed to update rule: {}", e))),
+        )),
+    }
+}
+
+pub async fn delete_rule(
+    Extension(claims): Extension<Claims>,
+    Path(rule_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state
+        .category_service
+        .delete_rule(&claims.sub, &rule_id)
+        .await
+    {
+        Ok(true) => Ok(Json(ApiResponse::success(()))),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error("Rule not found".to_string())),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!("Failed to delete rule: {}", e))),
+        )),
+    }
+}
+
+/// Re-runs the caller's rules over their stored receipts.
+pub async fn apply_rules(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state.category_service.apply_rules(&claims.sub).await {
+        Ok(report) => Ok(Json(ApiResponse::success(report))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!("Failed to apply rules: {}", e))),
+        )),
+    }
+}
+
+/// Rules proposed from the caller's repeated category corrections.
+pub async fn list_suggestions(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state.category_service.suggestions(&claims.sub).await {
+        Ok(suggestions) => Ok(Json(ApiResponse::success(suggestions))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!(
+                "Failed to list suggestions: {}",
+                e
+            ))),
+        )),
+    }
+}
+
+/// Creates the rule proposed for a correction key.
+pub async fn accept_suggestion(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<AcceptSuggestion>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state
+        .category_service
+        .accept_suggestion(&claims.sub, &request.key)
+        .await
+    {
+        Ok(Some(rule)) => Ok((StatusCode::CREATED, Json(ApiResponse::success(rule)))),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error("Suggestion not found".to_string())),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!(
+                "Failed to accept suggestion: {}",
+                e
+            ))),
+        )),
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{
+    common::money::Money,
+    domain::{merchant::models::compile_pattern, receipt::models::CategorySource},
+};
+use serde::{Deserialize, Serialize};
+
+/// A user's rule assigning categories to receipts that meet all of its
+/// conditions. Rules run by ascending `priority`; the first match wins.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryRule {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub user_email: String,
+    #[serde(default)]
+    pub priority: i32,
+    pub conditions: RuleConditions,
+    pub categories: Vec<String>,
+    pub created_at: i64,
+}
+
+/// Conditions a receipt must all meet; a rule without any matches every receipt.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleConditions {
+    /// Case-insensitive regex over the canonical or the extracted merchant name.
+    pub merchant: Option<String>,
+    /// Registry merchant the receipt is linked to.
+    pub merchant_id: Option<String>,
+    /// Case-insensitive regex over the sender.
+    pub issuer: Option<String>,
+    /// Bounds on the amount's magnitude, as decimal strings like `"50.00"`.
+    pub min_amount: Option<Money>,
+    pub max_amount: Option<Money>,
+    pub currency: Option<String>,
+}
+
+/// Fields of a rule as sent by the client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryRuleSpec {
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub conditions: RuleConditions,
+    pub categories: Vec<String>,
+}
+
+impl CategoryRuleSpec {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.categories.iter().all(|c| c.trim().is_empty()) {
+            return Err("A rule needs at least one category".to_string());
+        }
+        for pattern in [&self.conditions.merchant, &self.conditions.issuer]
+            .into_iter()
+            .flatten()
+        {
+            compile_pattern(pattern)
+                .map_err(|e| format!("Invalid pattern `{}`: {}", pattern, e))?;
+        }
+        if let (Some(min), Some(max)) = (self.conditions.min_amount, self.conditions.max_amount) {
+            if min > max {
+                return Err("`min_amount` is larger than `max_amount`".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of re-applying the rules to stored receipts.
+#[derive(Debug, Default, Serialize)]
+pub struct ApplyReport {
+    /// Receipts looked at; manually categorized ones are skipped.
+    pub checked: usize,
+    pub updated: usize,
+}
+
+/// A user changing a receipt's categories, kept as a signal for later receipts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryCorrection {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub user_email: String,
+    pub msg_id: Option<String>,
+    /// What later receipts are matched by: `merchant:<merchant id>`,
+    /// `name:<normalized merchant>` or, without a merchant, `issuer:<normalized sender>`.
+    pub key: String,
+    pub merchant: Option<String>,
+    pub merchant_id: Option<String>,
+    pub issuer: Option<String>,
+    /// Categories before the correction and what had set them.
+    pub previous: Option<Vec<String>>,
+    pub previous_source: Option<CategorySource>,
+    pub categories: Vec<String>,
+    pub created_at: i64,
+}
+
+/// A rule proposed from repeated, identical corrections.
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleSuggestion {
+    /// Correction key the suggestion is for; sent back to accept it.
+    pub key: String,
+    pub categories: Vec<String>,
+    /// Corrections that agree with `categories`.
+    pub corrections: usize,
+    pub rule: CategoryRuleSpec,
+}
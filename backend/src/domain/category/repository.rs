@@ -0,0 +1,104 @@
+use crate::domain::category::models::{CategoryCorrection, CategoryRule};
+use anyhow::{Context, Result};
+use futures::TryStreamExt;
+use mongodb::{bson::doc, Client, Collection};
+
+#[derive(Clone)]
+pub struct CategoryRuleRepo {
+    collection: Collection<CategoryRule>,
+}
+
+impl CategoryRuleRepo {
+    pub fn new(client: &Client, database: &str) -> Self {
+        CategoryRuleRepo {
+            collection: client.database(database).collection("category_rules"),
+        }
+    }
+
+    /// The user's rules in the order they are tried.
+    pub async fn by_user(&self, user_email: &str) -> Result<Vec<CategoryRule>> {
+        let mut result = Vec::new();
+        let mut cursor = self
+            .collection
+            .find(doc! { "user_email": user_email })
+            .sort(doc! { "priority": 1, "created_at": 1 })
+            .await
+            .with_context(|| format!("Failed to list category rules for {}", user_email))?;
+        while let Some(rule) = cursor.try_next().await? {
+            result.push(rule);
+        }
+        Ok(result)
+    }
+
+    pub async fn insert(&self, rule: &CategoryRule) -> Result<()> {
+        self.collection
+            .insert_one(rule)
+            .await
+            .with_context(|| format!("Failed to insert category rule for {}", rule.user_email))?;
+        Ok(())
+    }
+
+    /// Returns whether the user owns a rule with that id.
+    pub async fn replace(&self, rule: &CategoryRule) -> Result<bool> {
+        let result = self
+            .collection
+            .replace_one(
+                doc! { "_id": &rule.id, "user_email": &rule.user_email },
+                rule,
+            )
+            .await
+            .with_context(|| format!("Failed to update category rule {}", rule.id))?;
+        Ok(result.matched_count > 0)
+    }
+
+    /// Returns whether the user owned a rule with that id.
+    pub async fn delete(&self, user_email: &str, id: &str) -> Result<bool> {
+        let result = self
+            .collection
+            .delete_one(doc! { "_id": id, "user_email": user_email })
+            .await
+            .with_context(|| format!("Failed to delete category rule {}", id))?;
+        Ok(result.deleted_count > 0)
+    }
+}
+
+#[derive(Clone)]
+pub struct CorrectionRepo {
+    collection: Collection<CategoryCorrection>,
+}
+
+impl CorrectionRepo {
+    pub fn new(client: &Client, database: &str) -> Self {
+        CorrectionRepo {
+            collection: client.database(database).collection("category_corrections"),
+        }
+    }
+
+    pub async fn insert(&self, correction: &CategoryCorrection) -> Result<()> {
+        self.collection
+            .insert_one(correction)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to record category correction for {}",
+                    correction.user_email
+                )
+            })?;
+        Ok(())
+    }
+
+    /// The user's corrections, newest first.
+    pub async fn by_user(&self, user_email: &str) -> Result<Vec<CategoryCorrection>> {
+        let mut result = Vec::new();
+        let mut cursor = self
+            .collection
+            .find(doc! { "user_email": user_email })
+            .sort(doc! { "created_at": -1 })
+            .await
+            .with_context(|| format!("Failed to list category corrections for {}", user_email))?;
+        while let Some(correction) = cursor.try_next().await? {
+            result.push(correction);
+        }
+        Ok(result)
+    }
+}
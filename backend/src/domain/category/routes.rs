@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use axum::{
+    middleware,
+    routing::{get, post, put},
+    Router,
+};
+
+use crate::{
+    common::app_state::AppState,
+    domain::{
+        auth::handlers::authorization_middleware,
+        category::handlers::{
+            accept_suggestion, apply_rules, create_rule, delete_rule, list_categories, list_rules,
+            list_suggestions, update_rule,
+        },
+    },
+};
+
+pub fn routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/categories", get(list_categories))
+        .route("/categories/rules", get(list_rules).post(create_rule))
+        .route("/categories/rules/apply", post(apply_rules))
+        .route("/categories/suggestions", get(list_suggestions))
+        .route("/categories/suggestions/accept", post(accept_suggestion))
+        .route(
+            "/categories/rules/{rule_id}",
+            put(update_rule).delete(delete_rule),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            authorization_middleware,
+        ))
+        .with_state(state)
+}
@@ -0,0 +1,845 @@
+use crate::{
+    common::money::Money,
+    domain::{
+        category::{
+            models::{
+                ApplyReport, CategoryCorrection, CategoryRule, CategoryRuleSpec, RuleConditions,
+                RuleSuggestion,
+            },
+            repository::{CategoryRuleRepo, CorrectionRepo},
+        },
+        llm::client::{LlmClient, LlmRequest},
+        merchant::{
+            models::{compile_pattern, merchant_key},
+            repository::MerchantRepo,
+        },
+        receipt::{
+            models::{CategorySource, Receipt},
+            repository::ReceiptRepo,
+        },
+    },
+};
+use anyhow::{Context, Result};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use regex::Regex;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::Arc,
+};
+
+const CATEGORY_PROMPT: &str = "Pick the category that fits this transaction best.\n\
+     Categories: {{categories}}\n\
+     Merchant: {{merchant}}\n\
+     Sender: {{issuer}}\n\
+     Amount: {{amount}}\n\
+     Items: {{items}}\n\
+     Answer with JSON: {\"category\": \"<one of the categories, spelled exactly as listed>\"}, \
+     or {\"category\": null} when none of them fits.";
+
+/// Identical corrections for one merchant before a rule is suggested.
+const SUGGEST_AFTER: usize = 3;
+
+#[derive(Clone)]
+pub struct CategoryService {
+    db_client: CategoryRuleRepo,
+    corrections: CorrectionRepo,
+    receipts: ReceiptRepo,
+    merchants: MerchantRepo,
+    llm: Option<Arc<dyn LlmClient>>,
+}
+
+/// CategoryService manages the users' category rules and categorizes receipts.
+impl CategoryService {
+    pub fn new(
+        db_client: CategoryRuleRepo,
+        corrections: CorrectionRepo,
+        receipts: ReceiptRepo,
+        merchants: MerchantRepo,
+    ) -> Self {
+        CategoryService {
+            db_client,
+            corrections,
+            receipts,
+            merchants,
+            llm: None,
+        }
+    }
+
+    /// Lets the LLM pick one of the user's categories when no rule or
+    /// merchant default applies.
+    pub fn with_llm(mut self, llm: Arc<dyn LlmClient>) -> Self {
+        self.llm = Some(llm);
+        self
+    }
+
+    pub async fn rules(&self, user_email: &str) -> Result<Vec<CategoryRule>> {
+        self.db_client.by_user(user_email).await
+    }
+
+    pub async fn create_rule(
+        &self,
+        user_email: &str,
+        spec: CategoryRuleSpec,
+    ) -> Result<CategoryRule> {
+        let rule = from_spec(ObjectId::new().to_hex(), user_email, spec);
+        println!("Creating category rule for {}", user_email);
+        self.db_client.insert(&rule).await?;
+        Ok(rule)
+    }
+
+    /// `None` when the user has no rule with that id.
+    pub async fn update_rule(
+        &self,
+        user_email: &str,
+        id: &str,
+        spec: CategoryRuleSpec,
+    ) -> Result<Option<CategoryRule>> {
+        let rules = self.db_client.by_user(user_email).await?;
+        let Some(existing) = rules.iter().find(|r| r.id == id) else {
+            return Ok(None);
+        };
+        let rule = CategoryRule {
+            created_at: existing.created_at,
+            ..from_spec(id.to_string(), user_email, spec)
+        };
+        Ok(self.db_client.replace(&rule).await?.then_some(rule))
+    }
+
+    pub async fn delete_rule(&self, user_email: &str, id: &str) -> Result<bool> {
+        println!("Deleting category rule {} for {}", id, user_email);
+        self.db_client.delete(user_email, id).await
+    }
+
+    /// Categories the user works with: those on their receipts and in their rules.
+    pub async fn categories(&self, user_email: &str) -> Result<Vec<String>> {
+        let mut categories: BTreeSet<String> = self
+            .receipts
+            .categories_of(user_email)
+            .await?
+            .into_iter()
+            .collect();
+        for rule in self.db_client.by_user(user_email).await? {
+            categories.extend(rule.categories);
+        }
+        Ok(categories
+            .into_iter()
+            .filter(|c| !c.trim().is_empty())
+            .collect())
+    }
+
+    /// Fills in categories for receipts that have none: the owner's rules
+    /// first, then what the owner's corrections taught for the merchant, then
+    /// the merchant's default category, then an LLM guess among the owner's
+    /// categories. A failed guess leaves the receipt as it is.
+    pub async fn categorize(&self, receipts: &mut [Receipt]) -> Result<()> {
+        let owners: BTreeSet<String> = receipts
+            .iter()
+            .filter(|r| !has_categories(r))
+            .filter_map(|r| r.owner.clone())
+            .collect();
+        if owners.is_empty() {
+            return Ok(());
+        }
+        let defaults = self.merchant_defaults().await?;
+        for owner in owners {
+            let knowledge = self.knowledge(&owner).await?;
+            let mut known: Option<Vec<String>> = None;
+            for receipt in receipts
+                .iter_mut()
+                .filter(|r| r.owner.as_deref() == Some(owner.as_str()) && !has_categories(r))
+            {
+                if let Some(assignment) = assign(receipt, &knowledge, &defaults) {
+                    assignment.apply(receipt);
+                    continue;
+                }
+                let Some(llm) = &self.llm else {
+                    continue;
+                };
+                if known.is_none() {
+                    known = Some(self.categories(&owner).await?);
+                }
+                let categories = known.as_deref().unwrap_or_default();
+                match guess(llm.as_ref(), receipt, categories).await {
+                    Ok(Some(category)) => Assignment {
+                        categories: Some(vec![category]),
+                        source: Some(CategorySource::Llm),
+                        rule: None,
+                    }
+                    .apply(receipt),
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!(owner = %owner, "category guess failed: {:#}", e),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the user's current rules, learned categories and merchant defaults
+    /// over their stored receipts. Manual categories are kept; categories from
+    /// one of those that no longer applies are cleared. LLM guesses are only
+    /// replaced.
+    pub async fn apply_rules(&self, user_email: &str) -> Result<ApplyReport> {
+        let knowledge = self.knowledge(user_email).await?;
+        let defaults = self.merchant_defaults().await?;
+        let mut report = ApplyReport::default();
+        for (id, receipt) in self.receipts.by_email_with_ids(user_email).await? {
+            if is_manual(&receipt) {
+                continue;
+            }
+            report.checked += 1;
+            let assignment = match assign(&receipt, &knowledge, &defaults) {
+                Some(assignment) => assignment,
+                None if matches!(
+                    receipt.category_source,
+                    Some(CategorySource::Rule | CategorySource::Learned | CategorySource::Merchant)
+                ) =>
+                {
+                    Assignment::default()
+                }
+                None => continue,
+            };
+            if assignment.categories == receipt.categories
+                && assignment.source == receipt.category_source
+                && assignment.rule == receipt.category_rule
+            {
+                continue;
+            }
+            self.receipts
+                .set_categories(
+                    id,
+                    assignment.categories.as_deref(),
+                    assignment.source,
+                    assignment.rule.as_deref(),
+                )
+                .await?;
+            report.updated += 1;
+        }
+        println!(
+            "Re-applied category rules for {}: {} of {} receipts updated",
+            user_email, report.updated, report.checked
+        );
+        Ok(report)
+    }
+
+    /// Records the user setting `categories` on `receipt` (as it was before),
+    /// so later receipts of the same merchant get them too.
+    pub async fn record_correction(
+        &self,
+        user_email: &str,
+        receipt: &Receipt,
+        categories: &[String],
+    ) -> Result<()> {
+        let Some(key) = correction_keys(receipt).into_iter().next() else {
+            return Ok(());
+        };
+        if categories.is_empty() || receipt.categories.as_deref() == Some(categories) {
+            return Ok(());
+        }
+        self.corrections
+            .insert(&CategoryCorrection {
+                id: ObjectId::new().to_hex(),
+                user_email: user_email.to_string(),
+                msg_id: receipt.msg_id.clone(),
+                key,
+                merchant: receipt.merchant.clone(),
+                merchant_id: receipt.merchant_id.clone(),
+                issuer: receipt.issuer.clone(),
+                previous: receipt.categories.clone(),
+                previous_source: receipt.category_source,
+                categories: categories.to_vec(),
+                created_at: DateTime::now().timestamp_millis(),
+            })
+            .await
+    }
+
+    /// Rules proposed for merchants the user corrected to the same categories
+    /// at least `SUGGEST_AFTER` times, unless a rule already does that.
+    pub async fn suggestions(&self, user_email: &str) -> Result<Vec<RuleSuggestion>> {
+        let rules = compile(self.db_client.by_user(user_email).await?);
+        let mut by_key: BTreeMap<String, Vec<CategoryCorrection>> = BTreeMap::new();
+        for correction in self.corrections.by_user(user_email).await? {
+            by_key
+                .entry(correction.key.clone())
+                .or_default()
+                .push(correction);
+        }
+
+        let mut suggestions = Vec::new();
+        for (key, corrections) in by_key {
+            // newest first, so the first correction is what the user wants now
+            let latest = &corrections[0];
+            let agreeing = corrections
+                .iter()
+                .filter(|c| c.categories == latest.categories)
+                .count();
+            if agreeing < SUGGEST_AFTER {
+                continue;
+            }
+            let sample = Receipt {
+                merchant: latest.merchant.clone(),
+                merchant_id: latest.merchant_id.clone(),
+                issuer: latest.issuer.clone(),
+                ..Default::default()
+            };
+            let covered = rules
+                .iter()
+                .find(|r| r.matches(&sample))
+                .is_some_and(|r| r.rule.categories == latest.categories);
+            if covered {
+                continue;
+            }
+            let conditions = match (&latest.merchant_id, &latest.merchant, &latest.issuer) {
+                (Some(id), _, _) => RuleConditions {
+                    merchant_id: Some(id.clone()),
+                    ..Default::default()
+                },
+                (None, Some(merchant), _) => RuleConditions {
+                    merchant: Some(format!("^{}$", regex::escape(merchant))),
+                    ..Default::default()
+                },
+                (None, None, Some(issuer)) => RuleConditions {
+                    issuer: Some(format!("^{}$", regex::escape(issuer))),
+                    ..Default::default()
+                },
+                (None, None, None) => continue,
+            };
+            suggestions.push(RuleSuggestion {
+                key,
+                categories: latest.categories.clone(),
+                corrections: agreeing,
+                rule: CategoryRuleSpec {
+                    priority: 0,
+                    conditions,
+                    categories: latest.categories.clone(),
+                },
+            });
+        }
+        Ok(suggestions)
+    }
+
+    /// Turns the suggestion for `key` into a rule; `None` when there is none.
+    pub async fn accept_suggestion(
+        &self,
+        user_email: &str,
+        key: &str,
+    ) -> Result<Option<CategoryRule>> {
+        let Some(suggestion) = self
+            .suggestions(user_email)
+            .await?
+            .into_iter()
+            .find(|s| s.key == key)
+        else {
+            return Ok(None);
+        };
+        Ok(Some(self.create_rule(user_email, suggestion.rule).await?))
+    }
+
+    /// The user's rules and the categories their latest correction per key set.
+    async fn knowledge(&self, user_email: &str) -> Result<Knowledge> {
+        let mut learned = HashMap::new();
+        for correction in self.corrections.by_user(user_email).await? {
+            learned
+                .entry(correction.key)
+                .or_insert(correction.categories);
+        }
+        Ok(Knowledge {
+            rules: compile(self.db_client.by_user(user_email).await?),
+            learned,
+        })
+    }
+
+    /// Default category per registry merchant id.
+    async fn merchant_defaults(&self) -> Result<HashMap<String, String>> {
+        Ok(self
+            .merchants
+            .all()
+            .await?
+            .into_iter()
+            .filter_map(|m| Some((m.id, m.default_category?)))
+            .collect())
+    }
+}
+
+fn from_spec(id: String, user_email: &str, spec: CategoryRuleSpec) -> CategoryRule {
+    CategoryRule {
+        id,
+        user_email: user_email.to_string(),
+        priority: spec.priority,
+        conditions: spec.conditions,
+        categories: spec
+            .categories
+            .into_iter()
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .collect(),
+        created_at: DateTime::now().timestamp_millis(),
+    }
+}
+
+fn has_categories(receipt: &Receipt) -> bool {
+    receipt.categories.as_ref().is_some_and(|c| !c.is_empty())
+}
+
+/// Set by the user, or set before categories had a source.
+pub fn is_manual(receipt: &Receipt) -> bool {
+    match receipt.category_source {
+        Some(source) => source == CategorySource::Manual,
+        None => has_categories(receipt),
+    }
+}
+
+/// Keys a receipt's corrections are stored and looked up under, most
+/// specific first. The sender only counts for receipts without a merchant,
+/// since one sender (e.g. a payment provider) can cover many merchants.
+fn correction_keys(receipt: &Receipt) -> Vec<String> {
+    let mut keys = Vec::new();
+    if let Some(id) = &receipt.merchant_id {
+        keys.push(format!("merchant:{}", id));
+    }
+    let name = merchant_key(
+        receipt
+            .merchant_raw
+            .as_deref()
+            .or(receipt.merchant.as_deref())
+            .unwrap_or_default(),
+    );
+    if !name.is_empty() {
+        keys.push(format!("name:{}", name));
+    } else if keys.is_empty() {
+        let issuer = merchant_key(receipt.issuer.as_deref().unwrap_or_default());
+        if !issuer.is_empty() {
+            keys.push(format!("issuer:{}", issuer));
+        }
+    }
+    keys
+}
+
+/// What a user's rules and corrections say about categories.
+struct Knowledge {
+    rules: Vec<CompiledRule>,
+    /// Categories of the latest correction per correction key.
+    learned: HashMap<String, Vec<String>>,
+}
+
+#[derive(Default)]
+struct Assignment {
+    categories: Option<Vec<String>>,
+    source: Option<CategorySource>,
+    rule: Option<String>,
+}
+
+impl Assignment {
+    fn apply(self, receipt: &mut Receipt) {
+        receipt.categories = self.categories;
+        receipt.category_source = self.source;
+        receipt.category_rule = self.rule;
+    }
+}
+
+struct CompiledRule {
+    rule: CategoryRule,
+    merchant: Option<Regex>,
+    issuer: Option<Regex>,
+}
+
+impl CompiledRule {
+    fn matches(&self, receipt: &Receipt) -> bool {
+        let c = &self.rule.conditions;
+        let amount = receipt.amount.map(Money::abs);
+        self.merchant.as_ref().is_none_or(|re| {
+            [&receipt.merchant, &receipt.merchant_raw]
+                .into_iter()
+                .flatten()
+                .any(|name| re.is_match(name))
+        }) && c
+            .merchant_id
+            .as_ref()
+            .is_none_or(|id| receipt.merchant_id.as_ref() == Some(id))
+            && self
+                .issuer
+                .as_ref()
+                .is_none_or(|re| receipt.issuer.as_deref().is_some_and(|i| re.is_match(i)))
+            && c.min_amount
+                .is_none_or(|min| amount.is_some_and(|a| a >= min))
+            && c.max_amount
+                .is_none_or(|max| amount.is_some_and(|a| a <= max))
+            && c.currency.as_ref().is_none_or(|currency| {
+                receipt
+                    .currency
+                    .as_deref()
+                    .is_some_and(|rc| rc.eq_ignore_ascii_case(currency))
+            })
+    }
+}
+
+/// Rules whose patterns no longer compile are skipped.
+fn compile(rules: Vec<CategoryRule>) -> Vec<CompiledRule> {
+    rules
+        .into_iter()
+        .filter_map(|rule| {
+            let pattern = |p: &Option<String>| p.as_deref().map(compile_pattern).transpose();
+            match (
+                pattern(&rule.conditions.merchant),
+                pattern(&rule.conditions.issuer),
+            ) {
+                (Ok(merchant), Ok(issuer)) => Some(CompiledRule {
+                    rule,
+                    merchant,
+                    issuer,
+                }),
+                (Err(e), _) | (_, Err(e)) => {
+                    tracing::warn!(rule = %rule.id, "skipping category rule: {}", e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// The first matching rule, else the categories learned for the merchant,
+/// else the default category of the receipt's merchant.
+fn assign(
+    receipt: &Receipt,
+    knowledge: &Knowledge,
+    defaults: &HashMap<String, String>,
+) -> Option<Assignment> {
+    if let Some(compiled) = knowledge.rules.iter().find(|r| r.matches(receipt)) {
+        return Some(Assignment {
+            categories: Some(compiled.rule.categories.clone()),
+            source: Some(CategorySource::Rule),
+            rule: Some(compiled.rule.id.clone()),
+        });
+    }
+    let learned = correction_keys(receipt)
+        .iter()
+        .find_map(|key| knowledge.learned.get(key));
+    if let Some(categories) = learned {
+        return Some(Assignment {
+            categories: Some(categories.clone()),
+            source: Some(CategorySource::Learned),
+            rule: None,
+        });
+    }
+    let category = defaults.get(receipt.merchant_id.as_ref()?)?;
+    Some(Assignment {
+        categories: Some(vec![category.clone()]),
+        source: Some(CategorySource::Merchant),
+        rule: None,
+    })
+}
+
+/// Asks the model for one of `categories`; anything else counts as no answer.
+async fn guess(
+    llm: &dyn LlmClient,
+    receipt: &Receipt,
+    categories: &[String],
+) -> Result<Option<String>> {
+    if categories.is_empty() {
+        return Ok(None);
+    }
+    let items = receipt
+        .line_items
+        .iter()
+        .flatten()
+        .filter_map(|item| item.description.as_deref())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let amount = match (receipt.amount, &receipt.currency) {
+        (Some(amount), Some(currency)) => format!("{} {}", amount, currency),
+        (Some(amount), None) => amount.to_string(),
+        _ => String::new(),
+    };
+    let prompt = CATEGORY_PROMPT
+        .replace("{{categories}}", &categories.join(", "))
+        .replace(
+            "{{merchant}}",
+            receipt.merchant.as_deref().unwrap_or_default(),
+        )
+        .replace("{{issuer}}", receipt.issuer.as_deref().unwrap_or_default())
+        .replace("{{amount}}", &amount)
+        .replace("{{items}}", &items);
+    let response = llm.generate(&LlmRequest::json(prompt)).await?;
+    let value: Value =
+        serde_json::from_str(response.text.trim()).context("Category answer is not JSON")?;
+    let answer = value
+        .get("category")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .trim();
+    Ok(categories
+        .iter()
+        .find(|c| c.eq_ignore_ascii_case(answer))
+        .cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: &str, conditions: RuleConditions, categories: &[&str]) -> CategoryRule {
+        CategoryRule {
+            id: id.to_string(),
+            user_email: "me@example.com".to_string(),
+            priority: 0,
+            conditions,
+            categories: categories.iter().map(|c| c.to_string()).collect(),
+            created_at: 0,
+        }
+    }
+
+    fn receipt() -> Receipt {
+        Receipt {
+            merchant: Some("Grab".to_string()),
+            merchant_raw: Some("GRAB*FOOD 1234".to_string()),
+            merchant_id: Some("grab".to_string()),
+            issuer: Some("receipts@grab.com".to_string()),
+            amount: Money::parse("-42.50"),
+            currency: Some("SGD".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn money(raw: &str) -> Option<Money> {
+        Money::parse(raw)
+    }
+
+    #[test]
+    fn rules_match_when_all_conditions_hold() {
+        let cases = [
+            ("no conditions", RuleConditions::default(), true),
+            (
+                "canonical name",
+                RuleConditions {
+                    merchant: Some("^grab$".to_string()),
+                    ..Default::default()
+                },
+                true,
+            ),
+            (
+                "extracted name, ignoring case",
+                RuleConditions {
+                    merchant: Some("grab\\*food".to_string()),
+                    ..Default::default()
+                },
+                true,
+            ),
+            (
+                "other merchant",
+                RuleConditions {
+                    merchant: Some("gojek".to_string()),
+                    ..Default::default()
+                },
+                false,
+            ),
+            (
+                "merchant id",
+                RuleConditions {
+                    merchant_id: Some("grab".to_string()),
+                    ..Default::default()
+                },
+                true,
+            ),
+            (
+                "other merchant id",
+                RuleConditions {
+                    merchant_id: Some("gojek".to_string()),
+                    ..Default::default()
+                },
+                false,
+            ),
+            (
+                "issuer",
+                RuleConditions {
+                    issuer: Some("@grab\\.com$".to_string()),
+                    ..Default::default()
+                },
+                true,
+            ),
+            (
+                "amount bounds are on the magnitude",
+                RuleConditions {
+                    min_amount: money("42.50"),
+                    max_amount: money("50"),
+                    ..Default::default()
+                },
+                true,
+            ),
+            (
+                "below the minimum",
+                RuleConditions {
+                    min_amount: money("42.51"),
+                    ..Default::default()
+                },
+                false,
+            ),
+            (
+                "above the maximum",
+                RuleConditions {
+                    max_amount: money("42.49"),
+                    ..Default::default()
+                },
+                false,
+            ),
+            (
+                "currency, ignoring case",
+                RuleConditions {
+                    currency: Some("sgd".to_string()),
+                    ..Default::default()
+                },
+                true,
+            ),
+            (
+                "one failing condition is enough",
+                RuleConditions {
+                    merchant: Some("grab".to_string()),
+                    currency: Some("USD".to_string()),
+                    ..Default::default()
+                },
+                false,
+            ),
+        ];
+        for (name, conditions, expected) in cases {
+            let compiled = compile(vec![rule("r", conditions, &["Food"])]);
+            assert_eq!(compiled[0].matches(&receipt()), expected, "{}", name);
+        }
+
+        let unpriced = Receipt {
+            amount: None,
+            ..receipt()
+        };
+        let bounded = compile(vec![rule(
+            "r",
+            RuleConditions {
+                max_amount: money("100"),
+                ..Default::default()
+            },
+            &["Food"],
+        )]);
+        assert!(!bounded[0].matches(&unpriced));
+    }
+
+    #[test]
+    fn rules_with_bad_patterns_are_skipped() {
+        let bad = RuleConditions {
+            issuer: Some("(unclosed".to_string()),
+            ..Default::default()
+        };
+        let compiled = compile(vec![
+            rule("bad", bad, &["Broken"]),
+            rule("good", RuleConditions::default(), &["Food"]),
+        ]);
+        let ids: Vec<&str> = compiled.iter().map(|c| c.rule.id.as_str()).collect();
+        assert_eq!(ids, ["good"]);
+    }
+
+    #[test]
+    fn rules_beat_corrections_beat_merchant_defaults() {
+        let food_rule = rule(
+            "food",
+            RuleConditions {
+                merchant: Some("grab".to_string()),
+                ..Default::default()
+            },
+            &["Food"],
+        );
+        let transport_rule = rule(
+            "transport",
+            RuleConditions {
+                currency: Some("SGD".to_string()),
+                ..Default::default()
+            },
+            &["Transport"],
+        );
+        let learned = HashMap::from([(
+            "name:grabfood1234".to_string(),
+            vec!["Takeaway".to_string()],
+        )]);
+        let defaults = HashMap::from([("grab".to_string(), "Rides".to_string())]);
+        let knowledge =
+            |rules: Vec<CategoryRule>, learned: &HashMap<String, Vec<String>>| Knowledge {
+                rules: compile(rules),
+                learned: learned.clone(),
+            };
+
+        let cases = [
+            (
+                "first matching rule in order",
+                knowledge(vec![food_rule.clone(), transport_rule.clone()], &learned),
+                receipt(),
+                Some((vec!["Food"], CategorySource::Rule, Some("food"))),
+            ),
+            (
+                "rule order decides",
+                knowledge(vec![transport_rule.clone(), food_rule.clone()], &learned),
+                receipt(),
+                Some((vec!["Transport"], CategorySource::Rule, Some("transport"))),
+            ),
+            (
+                "correction when no rule matches",
+                knowledge(vec![], &learned),
+                receipt(),
+                Some((vec!["Takeaway"], CategorySource::Learned, None)),
+            ),
+            (
+                "merchant default last",
+                knowledge(vec![], &HashMap::new()),
+                receipt(),
+                Some((vec!["Rides"], CategorySource::Merchant, None)),
+            ),
+            (
+                "nothing known",
+                knowledge(vec![], &HashMap::new()),
+                Receipt {
+                    merchant_id: None,
+                    ..receipt()
+                },
+                None,
+            ),
+        ];
+        for (name, knowledge, receipt, expected) in cases {
+            let assigned = assign(&receipt, &knowledge, &defaults)
+                .map(|a| (a.categories.unwrap_or_default(), a.source.unwrap(), a.rule));
+            let expected = expected.map(|(categories, source, rule)| {
+                (
+                    categories.into_iter().map(str::to_string).collect(),
+                    source,
+                    rule.map(str::to_string),
+                )
+            });
+            assert_eq!(assigned, expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn corrections_are_keyed_most_specific_first() {
+        let cases = [
+            (receipt(), vec!["merchant:grab", "name:grabfood1234"]),
+            (
+                Receipt {
+                    merchant_id: None,
+                    merchant_raw: None,
+                    ..receipt()
+                },
+                vec!["name:grab"],
+            ),
+            (
+                Receipt {
+                    merchant_id: None,
+                    merchant_raw: None,
+                    merchant: None,
+                    ..receipt()
+                },
+                vec!["issuer:receiptsgrabcom"],
+            ),
+        ];
+        for (receipt, expected) in cases {
+            assert_eq!(
+                correction_keys(&receipt),
+                expected,
+                "{:?}",
+                receipt.merchant
+            );
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use crate::domain::email::{models::ArchiveRef, repository::ArchiveRepo};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::{AsyncReadExt, AsyncWriteExt, TryStreamExt};
+use mongodb::{
+    bson::{doc, DateTime},
+    gridfs::GridFsBucket,
+    options::GridFsBucketOptions,
+    Client,
+};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+/// Storage for raw RFC822 bytes, addressed by their SHA-256.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    async fn put(&self, sha256: &str, bytes: &[u8]) -> Result<()>;
+    async fn get(&self, sha256: &str) -> Result<Option<Vec<u8>>>;
+    async fn delete(&self, sha256: &str) -> Result<()>;
+}
+
+/// Blobs under `<root>/<first two hex chars>/<sha256>.eml`.
+pub struct FsBlobStore {
+    root: PathBuf,
+}
+
+impl FsBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FsBlobStore { root: root.into() }
+    }
+
+    fn path(&self, sha256: &str) -> PathBuf {
+        self.root.join(&sha256[..2]).join(format!("{}.eml", sha256))
+    }
+}
+
+#[async_trait]
+impl BlobStore for FsBlobStore {
+    async fn put(&self, sha256: &str, bytes: &[u8]) -> Result<()> {
+        let path = self.path(sha256);
+        if tokio::fs::try_exists(&path).await? {
+            return Ok(());
+        }
+        let dir = path.parent().expect("blob path has a parent");
+        tokio::fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("Creating archive folder {}", dir.display()))?;
+        // write then rename so a crash never leaves a truncated blob under its hash
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, bytes)
+            .await
+            .with_context(|| format!("Writing {}", tmp.display()))?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    async fn get(&self, sha256: &str) -> Result<Option<Vec<u8>>> {
+        match tokio::fs::read(self.path(sha256)).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Reading archived message {}", sha256)),
+        }
+    }
+
+    async fn delete(&self, sha256: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.path(sha256)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Deleting archived message {}", sha256))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Blobs in the `raw_emails` GridFS bucket, one file per hash.
+pub struct GridFsBlobStore {
+    bucket: GridFsBucket,
+}
+
+impl GridFsBlobStore {
+    pub fn new(client: &Client, database: &str) -> Self {
+        GridFsBlobStore {
+            bucket: client.database(database).gridfs_bucket(
+                GridFsBucketOptions::builder()
+                    .bucket_name("raw_emails".to_string())
+                    .build(),
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl BlobStore for GridFsBlobStore {
+    async fn put(&self, sha256: &str, bytes: &[u8]) -> Result<()> {
+        if self
+            .bucket
+            .find_one(doc! { "filename": sha256 })
+            .await?
+            .is_some()
+        {
+            return Ok(());
+        }
+        let mut upload = self.bucket.open_upload_stream(sha256).await?;
+        upload.write_all(bytes).await?;
+        upload
+            .close()
+            .await
+            .with_context(|| format!("Uploading archived message {}", sha256))?;
+        Ok(())
+    }
+
+    async fn get(&self, sha256: &str) -> Result<Option<Vec<u8>>> {
+        if self
+            .bucket
+            .find_one(doc! { "filename": sha256 })
+            .await?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        let mut download = self.bucket.open_download_stream_by_name(sha256).await?;
+        let mut bytes = Vec::new();
+        download
+            .read_to_end(&mut bytes)
+            .await
+            .with_context(|| format!("Downloading archived message {}", sha256))?;
+        Ok(Some(bytes))
+    }
+
+    async fn delete(&self, sha256: &str) -> Result<()> {
+        let mut files = self.bucket.find(doc! { "filename": sha256 }).await?;
+        while let Some(file) = files.try_next().await? {
+            self.bucket.delete(file.id).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Content-addressed archive of raw messages. Identical bytes are stored
+/// once; per-user refs decide when a blob can be dropped.
+pub struct RawArchive {
+    store: Box<dyn BlobStore>,
+    refs: ArchiveRepo,
+    retention_days: Option<u32>,
+}
+
+impl RawArchive {
+    pub fn new(store: Box<dyn BlobStore>, refs: ArchiveRepo, retention_days: Option<u32>) -> Self {
+        RawArchive {
+            store,
+            refs,
+            retention_days,
+        }
+    }
+
+    pub fn sha256(bytes: &[u8]) -> String {
+        Sha256::digest(bytes)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Stores the bytes and links them to `ref_id`, returning their hash.
+    pub async fn store(
+        &self,
+        ref_id: &str,
+        user_email: &str,
+        source: &str,
+        msg_id: &str,
+        bytes: &[u8],
+    ) -> Result<String> {
+        let sha256 = RawArchive::sha256(bytes);
+        self.store.put(&sha256, bytes).await?;
+        self.refs
+            .upsert(ArchiveRef {
+                id: ref_id.to_string(),
+                user_email: user_email.to_string(),
+                source: source.to_string(),
+                msg_id: msg_id.to_string(),
+                sha256: sha256.clone(),
+                size: bytes.len() as u64,
+                archived_at: DateTime::now().timestamp_millis(),
+            })
+            .await?;
+        Ok(sha256)
+    }
+
+    /// Archived bytes for `ref_id`, with their hash, if present.
+    pub async fn load(&self, ref_id: &str) -> Result<Option<(String, Vec<u8>)>> {
+        let Some(archive_ref) = self.refs.get(ref_id).await? else {
+            return Ok(None);
+        };
+        Ok(self
+            .store
+            .get(&archive_ref.sha256)
+            .await?
+            .map(|bytes| (archive_ref.sha256, bytes)))
+    }
+
+    /// Archived messages of the user, as `(source kind, message id)`.
+    pub async fn list_user(&self, user_email: &str) -> Result<Vec<(String, String)>> {
+        Ok(self
+            .refs
+            .list_for_user(user_email)
+            .await?
+            .into_iter()
+            .map(|r| (r.source, r.msg_id))
+            .collect())
+    }
+
+    /// Drops every archived message of the user. Returns how many blobs were deleted.
+    pub async fn purge_user(&self, user_email: &str) -> Result<usize> {
+        let hashes = self.refs.delete_for_user(user_email).await?;
+        self.delete_orphans(hashes).await
+    }
+
+    /// Drops refs older than the retention period, if one is configured.
+    pub async fn apply_retention(&self) -> Result<usize> {
+        let Some(days) = self.retention_days else {
+            return Ok(0);
+        };
+        let cutoff = DateTime::now().timestamp_millis() - i64::from(days) * 24 * 60 * 60 * 1000;
+        let hashes = self.refs.delete_older_than(cutoff).await?;
+        self.delete_orphans(hashes).await
+    }
+
+    pub fn retention_enabled(&self) -> bool {
+        self.retention_days.is_some()
+    }
+
+    /// Deletes blobs no other ref (e.g. another user's copy) still points to.
+    async fn delete_orphans(&self, hashes: Vec<String>) -> Result<usize> {
+        let mut deleted = 0;
+        for sha256 in hashes {
+            if !self.refs.is_referenced(&sha256).await? {
+                self.store.delete(&sha256).await?;
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+}
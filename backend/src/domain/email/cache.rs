@@ -0,0 +1,112 @@
+use crate::domain::email::{
+    archive::RawArchive,
+    models::{CacheStats, CachedExtraction},
+    repository::ExtractionCacheRepo,
+};
+use crate::domain::receipt::models::ReceiptList;
+use anyhow::Result;
+use lru_cache::LruCache;
+use mongodb::bson::DateTime;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+/// Extraction results by input, model chain and prompt version: an in-memory
+/// LRU in front of the `extraction_cache` collection. Keys name the
+/// configured chain (`LlmClient::name`) rather than the model that answered,
+/// which is only known after the call.
+pub struct ExtractionCache {
+    memory: Mutex<LruCache<String, ReceiptList>>,
+    store: ExtractionCacheRepo,
+    memory_hits: AtomicU64,
+    store_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ExtractionCache {
+    pub fn new(store: ExtractionCacheRepo, capacity: usize) -> Self {
+        ExtractionCache {
+            memory: Mutex::new(LruCache::new(capacity)),
+            store,
+            memory_hits: AtomicU64::new(0),
+            store_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Cache key for text input, over the fully rendered prompt so the
+    /// issuer, locale and template hints count as well as the email text.
+    /// Whitespace is collapsed so re-rendered copies of the same email hash
+    /// alike.
+    pub fn text_key(prompt: &str, chain: &str, prompt_version: &str) -> String {
+        let normalized = prompt.split_whitespace().collect::<Vec<_>>().join(" ");
+        ExtractionCache::key(normalized.as_bytes(), chain, prompt_version)
+    }
+
+    /// Cache key for an image sent with the rendered prompt.
+    pub fn image_key(prompt: &str, image: &[u8], chain: &str, prompt_version: &str) -> String {
+        let mut input = format!("{}\n", prompt).into_bytes();
+        input.extend_from_slice(image);
+        ExtractionCache::key(&input, chain, prompt_version)
+    }
+
+    pub fn key(input: &[u8], chain: &str, prompt_version: &str) -> String {
+        let mut bytes = format!("{}\n{}\n", chain, prompt_version).into_bytes();
+        bytes.extend_from_slice(input);
+        RawArchive::sha256(&bytes)
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<ReceiptList>> {
+        if let Some(hit) = self.memory.lock().unwrap().get_mut(key) {
+            self.memory_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(Some(hit.clone()));
+        }
+        let Some(entry) = self.store.get(key).await? else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return Ok(None);
+        };
+        self.store_hits.fetch_add(1, Ordering::Relaxed);
+        let receipts = ReceiptList {
+            transactions: entry.receipts,
+        };
+        self.memory
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), receipts.clone());
+        Ok(Some(receipts))
+    }
+
+    pub async fn put(
+        &self,
+        key: &str,
+        chain: &str,
+        model: &str,
+        prompt_version: &str,
+        receipts: &ReceiptList,
+    ) -> Result<()> {
+        self.memory
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), receipts.clone());
+        self.store
+            .upsert(&CachedExtraction {
+                id: key.to_string(),
+                chain: chain.to_string(),
+                model: model.to_string(),
+                prompt_version: prompt_version.to_string(),
+                receipts: receipts.transactions.clone(),
+                created_at: DateTime::now().timestamp_millis(),
+            })
+            .await
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            memory_hits: self.memory_hits.load(Ordering::Relaxed),
+            store_hits: self.store_hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            memory_entries: self.memory.lock().unwrap().len(),
+        }
+    }
+}
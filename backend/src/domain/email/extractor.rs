@@ -0,0 +1,344 @@
+use crate::domain::email::cache::ExtractionCache;
+use crate::domain::email::models::{CacheStats, EmailAttachment, ParsedEmailContent};
+use crate::domain::email::prompts::{PromptContext, PromptInput, PromptLibrary};
+use crate::domain::email::sanitize;
+use crate::domain::email::service::EmailService;
+use crate::domain::email::templates::ExtractionTemplates;
+use crate::domain::email::validation;
+use crate::domain::llm::client::{build_llm_client, LlmClient, LlmConfig, LlmRequest};
+use crate::domain::receipt::models::{Receipt, ReceiptList, ValidationStatus};
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+/// Re-prompts allowed when the model's answer does not match the schema.
+const MAX_REPAIR_ATTEMPTS: usize = 2;
+
+const RECEIPT_SCHEMA_HINT: &str =
+    "{ 'transactions': [ {'merchant': '...', 'amount': 0.0, 'currency': '...', \
+     'kind': 'purchase|refund|reversal|credit|transfer', \
+     'payment': {'type': 'card|account|wallet', 'last4': '1234', 'issuer': '...', 'nickname': '...'}, \
+     'line_items': [ {'description': '...', 'quantity': 1, 'unit_price': 0.0, 'total': 0.0} ], \
+     'subtotal': 0.0, 'tax': 0.0, 'tip': 0.0, 'shipping': 0.0, 'discount': 0.0} ] } \
+     (amounts are positive, `kind` says which way the money went; \
+     leave out line items and amounts the text does not show)";
+
+/// Everything used to turn message content into receipts.
+pub struct Extraction {
+    /// Settings behind `llm`, reused when a reprocess overrides the model.
+    pub llm_config: LlmConfig,
+    pub llm: Arc<dyn LlmClient>,
+    /// Multimodal model for image attachments; images are skipped without one.
+    pub vision_llm: Option<Arc<dyn LlmClient>>,
+    pub templates: ExtractionTemplates,
+    pub prompts: PromptLibrary,
+}
+
+/// Turns message content into receipts: templates first, then the LLM with
+/// prompt selection, sanitization, schema validation and caching. Needs no
+/// database, so it also runs outside the server (see `src/bin/eval.rs`).
+pub struct Extractor {
+    llm_config: LlmConfig,
+    llm: Arc<dyn LlmClient>,
+    vision_llm: Option<Arc<dyn LlmClient>>,
+    templates: ExtractionTemplates,
+    prompts: PromptLibrary,
+    cache: Option<Arc<ExtractionCache>>,
+}
+
+impl Extractor {
+    pub fn new(extraction: Extraction) -> Self {
+        Extractor {
+            llm_config: extraction.llm_config,
+            llm: extraction.llm,
+            vision_llm: extraction.vision_llm,
+            templates: extraction.templates,
+            prompts: extraction.prompts,
+            cache: None,
+        }
+    }
+
+    /// Reuses extraction results for identical input, model and prompt.
+    pub fn with_cache(mut self, cache: Arc<ExtractionCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|cache| cache.stats())
+    }
+
+    pub fn llm(&self) -> &dyn LlmClient {
+        self.llm.as_ref()
+    }
+
+    /// The configured LLM, or another model on the same backend.
+    pub fn llm_for(&self, model: Option<&str>) -> Result<Arc<dyn LlmClient>> {
+        match model {
+            Some(model) => build_llm_client(&self.llm_config.with_model(model)),
+            None => Ok(self.llm.clone()),
+        }
+    }
+
+    /// Extracts the receipts of one message: a matching template, otherwise
+    /// the LLM over the body and the attachments. `issuer` is the sender's
+    /// display name or address.
+    pub async fn extract(
+        &self,
+        parsed_email_content: &ParsedEmailContent,
+        issuer: &str,
+        llm: &dyn LlmClient,
+    ) -> Result<Vec<Receipt>> {
+        // exported mail is not always multipart, so fall back to the plain text body
+        let body = parsed_email_content
+            .html
+            .as_deref()
+            .or(parsed_email_content.text.as_deref());
+        anyhow::ensure!(
+            body.is_some() || !parsed_email_content.attachments.is_empty(),
+            "Email has no body or attachments"
+        );
+        let prompt_ctx = PromptContext {
+            sender: parsed_email_content
+                .from_addr
+                .as_deref()
+                .unwrap_or_default(),
+            issuer,
+            locale: parsed_email_content.locale.as_deref(),
+            schema: RECEIPT_SCHEMA_HINT,
+        };
+
+        // fixed-format issuers are handled by templates, without the LLM
+        let text = body.map(EmailService::html_to_text).unwrap_or_default();
+        let receipts = match self.templates.apply(parsed_email_content, &text) {
+            Some(receipt) => vec![receipt],
+            None => {
+                let mut body_receipts = match body {
+                    Some(body) => {
+                        self.parse_with_ollmao(body, llm, &prompt_ctx)
+                            .await?
+                            .transactions
+                    }
+                    None => vec![],
+                };
+                let attachment_receipts = self
+                    .extract_from_attachments(&parsed_email_content.attachments, llm, &prompt_ctx)
+                    .await;
+                // invoices usually repeat the body's total; keep the attachment's version
+                body_receipts.retain(|b| {
+                    !attachment_receipts
+                        .iter()
+                        .any(|a| a.amount == b.amount && a.currency == b.currency)
+                });
+                body_receipts.extend(attachment_receipts);
+                body_receipts
+            }
+        };
+
+        Ok(receipts)
+    }
+
+    /// Runs extraction over every usable attachment, tagging each receipt
+    /// with the attachment it came from. A failing attachment is logged and
+    /// skipped so it does not discard the rest of the email.
+    async fn extract_from_attachments(
+        &self,
+        attachments: &[EmailAttachment],
+        llm: &dyn LlmClient,
+        prompt_ctx: &PromptContext<'_>,
+    ) -> Vec<Receipt> {
+        let mut receipts = Vec::new();
+        for attachment in attachments {
+            let extracted = match (&attachment.text, &attachment.image) {
+                (Some(text), _) => self.extract_from_text(text, llm, prompt_ctx).await,
+                (None, Some(image)) => match &self.vision_llm {
+                    Some(llm) => {
+                        self.extract_from_image(llm.as_ref(), image, prompt_ctx)
+                            .await
+                    }
+                    None => continue,
+                },
+                (None, None) => continue,
+            };
+            match extracted {
+                Ok(list) => receipts.extend(list.transactions.into_iter().map(|mut r| {
+                    r.source_attachment = Some(attachment.name.clone());
+                    r
+                })),
+                Err(e) => {
+                    tracing::warn!(error = %e, attachment = %attachment.name, "attachment extraction failed")
+                }
+            }
+        }
+        receipts
+    }
+
+    /// Uses the configured LLM to extract structured `ReceiptList`
+    /// from email HTML by prompting an LLM and parsing JSON output.
+    async fn parse_with_ollmao(
+        &self,
+        raw: &str,
+        llm: &dyn LlmClient,
+        prompt_ctx: &PromptContext<'_>,
+    ) -> Result<ReceiptList> {
+        let text = EmailService::html_to_text(raw);
+        self.extract_from_text(&text, llm, prompt_ctx).await
+    }
+
+    /// Prompts the LLM with already-visible text, redacted and delimited.
+    /// Receipts whose amount does not appear in the text are dropped, since
+    /// the model either made them up or was told to by the email.
+    async fn extract_from_text(
+        &self,
+        text: &str,
+        llm: &dyn LlmClient,
+        prompt_ctx: &PromptContext<'_>,
+    ) -> Result<ReceiptList> {
+        println!("Parsing with {}", llm.name());
+        let sanitized = sanitize::sanitize(text);
+        if sanitized.redactions > 0 {
+            tracing::debug!(redactions = sanitized.redactions, "redacted email text");
+        }
+        let prompt = self
+            .prompts
+            .render(PromptInput::Text, prompt_ctx, &sanitized.wrapped)?;
+        let chain = llm.name();
+        let key = ExtractionCache::text_key(&prompt.text, &chain, &prompt.version);
+        if let Some(hit) = self.cached(&key).await {
+            return Ok(hit);
+        }
+        let (mut result, model) = self
+            .generate_receipts(llm, LlmRequest::json(prompt.text))
+            .await?;
+        result.transactions.retain(|receipt| match receipt.amount {
+            Some(amount) if !sanitize::amount_in_text(amount, text) => {
+                tracing::warn!(%amount, merchant = ?receipt.merchant, "dropping receipt whose amount is not in the email");
+                false
+            }
+            _ => true,
+        });
+        set_prompt_version(&mut result, &prompt.version);
+        self.remember(&key, &chain, &model, &prompt.version, &result)
+            .await;
+        Ok(result)
+    }
+
+    /// Sends a receipt image to a multimodal model.
+    async fn extract_from_image(
+        &self,
+        llm: &dyn LlmClient,
+        image: &[u8],
+        prompt_ctx: &PromptContext<'_>,
+    ) -> Result<ReceiptList> {
+        println!("Parsing image with {}", llm.name());
+        let prompt = self.prompts.render(PromptInput::Image, prompt_ctx, "")?;
+        let chain = llm.name();
+        let key = ExtractionCache::image_key(&prompt.text, image, &chain, &prompt.version);
+        if let Some(hit) = self.cached(&key).await {
+            return Ok(hit);
+        }
+        let (mut result, model) = self
+            .generate_receipts(
+                llm,
+                LlmRequest {
+                    prompt: prompt.text,
+                    images: vec![image.to_vec()],
+                    json: true,
+                },
+            )
+            .await?;
+        set_prompt_version(&mut result, &prompt.version);
+        self.remember(&key, &chain, &model, &prompt.version, &result)
+            .await;
+        Ok(result)
+    }
+
+    /// Looks up a cached extraction. Cache errors are logged and treated as misses.
+    async fn cached(&self, key: &str) -> Option<ReceiptList> {
+        match self.cache.as_ref()?.get(key).await {
+            Ok(hit) => {
+                if hit.is_some() {
+                    println!("Extraction cache hit {}", key);
+                }
+                hit
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "extraction cache lookup failed");
+                None
+            }
+        }
+    }
+
+    async fn remember(
+        &self,
+        key: &str,
+        chain: &str,
+        model: &str,
+        prompt_version: &str,
+        result: &ReceiptList,
+    ) {
+        let Some(cache) = &self.cache else {
+            return;
+        };
+        if let Err(e) = cache.put(key, chain, model, prompt_version, result).await {
+            tracing::warn!(error = %e, "failed to cache extraction");
+        }
+    }
+
+    /// Runs the request and checks the answer against the receipt schema,
+    /// re-prompting with the validation errors up to `MAX_REPAIR_ATTEMPTS`
+    /// times. Items still invalid after that are kept as `Rejected`; an
+    /// answer that never parses fails the email. Also returns the model that
+    /// answered, which each receipt records as its `extractor`.
+    async fn generate_receipts(
+        &self,
+        llm: &dyn LlmClient,
+        mut request: LlmRequest,
+    ) -> Result<(ReceiptList, String)> {
+        let original_prompt = request.prompt.clone();
+        let mut attempt = 0;
+        loop {
+            let res = llm.generate(&request).await?;
+            let checked = validation::check_response(&res.text);
+            let errors = match &checked {
+                Ok(c) => c.errors.clone(),
+                Err(e) => vec![e.clone()],
+            };
+
+            if errors.is_empty() || attempt == MAX_REPAIR_ATTEMPTS {
+                let checked = checked.map_err(|e| {
+                    anyhow!(
+                        "Invalid LLM output after {} repair attempts: {}",
+                        attempt,
+                        e
+                    )
+                })?;
+                let mut result = ReceiptList {
+                    transactions: checked.receipts,
+                };
+                for receipt in result.transactions.iter_mut() {
+                    if attempt > 0 && receipt.validation == Some(ValidationStatus::Valid) {
+                        receipt.validation = Some(ValidationStatus::Repaired);
+                    }
+                    receipt.extractor = Some(res.model.clone());
+                }
+                return Ok((result, res.model));
+            }
+
+            attempt += 1;
+            tracing::warn!(model = %res.model, attempt, errors = ?errors, "llm output failed validation, re-prompting");
+            request.prompt = format!(
+                "{}\n\nYour previous answer was:\n{}\nIt is invalid against the JSON Schema {}:\n- {}\nReturn ONLY the corrected JSON.",
+                original_prompt,
+                res.text,
+                *validation::RECEIPT_LIST_SCHEMA,
+                errors.join("\n- ")
+            );
+        }
+    }
+}
+
+fn set_prompt_version(receipts: &mut ReceiptList, version: &str) {
+    for receipt in receipts.transactions.iter_mut() {
+        receipt.prompt_version = Some(version.to_string());
+    }
+}
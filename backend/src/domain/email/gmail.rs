@@ -0,0 +1,363 @@
+use crate::domain::auth::service::AuthService;
+use crate::domain::email::models::*;
+use crate::domain::email::source::MailSource;
+use anyhow::Result;
+use async_trait::async_trait;
+use base64::Engine;
+use futures::{stream, StreamExt, TryStreamExt};
+use reqwest::{Client, StatusCode};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+const GMAIL_API: &str = "https://gmail.googleapis.com/gmail/v1/users/me";
+/// `From` lookups in flight at once when filtering history by sender.
+const SENDER_LOOKUPS: usize = 8;
+
+// TODO: ??
+fn decode_base64url(s: &str) -> Result<Vec<u8>> {
+    let mut s = s.replace('-', "+").replace('_', "/");
+    while !s.len().is_multiple_of(4) {
+        s.push('=');
+    }
+    Ok(base64::engine::general_purpose::STANDARD.decode(s)?)
+}
+
+/// Gmail REST API backed mail source, authenticated with the user's stored
+/// Google OAuth token.
+#[derive(Clone)]
+pub struct GmailSource {
+    client: Client,
+    auth_service: Arc<AuthService>,
+}
+
+impl GmailSource {
+    pub fn new(auth_service: Arc<AuthService>) -> Self {
+        GmailSource {
+            client: Client::new(),
+            auth_service,
+        }
+    }
+
+    /// Runs authentication based on the stored OAuth token.
+    async fn internal_authenticate(&self, user_id: &str) -> Result<String> {
+        let token = self.auth_service.get_valid_token(user_id, "google").await?;
+        Ok(token.access_token)
+    }
+
+    /// Renders a `MailQuery` into Gmail search syntax. The range becomes
+    /// absolute `after:`/`before:` unix seconds, so every page of a listing
+    /// runs the same search however long it takes.
+    pub fn build_search(query: &MailQuery) -> String {
+        let mut parts: Vec<String> = Vec::new();
+        if let Some(category) = &query.category {
+            parts.push(format!("category:{}", category));
+        }
+        if !query.issuers.is_empty() {
+            parts.push(format!("from:({})", query.issuers.join(" OR ")));
+        }
+        if let Some(after) = query.after {
+            parts.push(format!("after:{}", after / 1000));
+        }
+        if let Some(before) = query.before {
+            parts.push(format!("before:{}", before / 1000));
+        }
+        parts.join(" ")
+    }
+
+    /// Lists all the Messages based on the given queries.
+    /// Automatically runs pagination based on the returned response
+    /// until all pages are retrieved.
+    async fn list_all_messages(
+        &self,
+        token: &str,
+        combined_queries: &str,
+    ) -> Result<Vec<GmailMessage>> {
+        let mut all_messages: Vec<GmailMessage> = Vec::new();
+        let mut current_page_token: Option<String> = None;
+        println!("Combined query: {}", combined_queries);
+        // Run pagination on the query
+        loop {
+            let resp = self
+                .list_search_page(token, combined_queries, current_page_token.as_deref())
+                .await?;
+
+            if let Some(mut messages) = resp.messages {
+                all_messages.append(&mut messages);
+            }
+
+            if let Some(tok) = resp.next_page_token {
+                current_page_token = Some(tok);
+            } else {
+                break;
+            }
+        }
+        println!("Found {} emails to parse", all_messages.len());
+        Ok(all_messages)
+    }
+
+    /// Fetches a single page of search results.
+    async fn list_search_page(
+        &self,
+        token: &str,
+        search: &str,
+        page_token: Option<&str>,
+    ) -> Result<GmailMessagesResponse> {
+        let mut req = self
+            .client
+            .get(format!("{}/messages", GMAIL_API))
+            .bearer_auth(token)
+            .query(&[("q", search), ("maxResults", "500")]);
+        if let Some(tok) = page_token {
+            req = req.query(&[("pageToken", tok)]);
+        }
+        Ok(req.send().await?.error_for_status()?.json().await?)
+    }
+
+    /// Current mailbox `historyId`, used as the starting cursor after a full listing.
+    async fn current_history_id(&self, token: &str) -> Result<String> {
+        let profile: GmailProfile = self
+            .client
+            .get(format!("{}/profile", GMAIL_API))
+            .bearer_auth(token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(profile.history_id)
+    }
+
+    /// Collects messages added since `start_history_id` via `users.history.list`.
+    /// Returns `None` when Gmail no longer has history that far back (HTTP 404),
+    /// in which case the caller has to fall back to a full listing.
+    async fn list_history(
+        &self,
+        token: &str,
+        start_history_id: &str,
+    ) -> Result<Option<(Vec<GmailHistoryMessage>, String)>> {
+        let mut added: Vec<GmailHistoryMessage> = Vec::new();
+        let mut latest_history_id = start_history_id.to_string();
+        let mut current_page_token: Option<String> = None;
+        loop {
+            let mut req = self
+                .client
+                .get(format!("{}/history", GMAIL_API))
+                .bearer_auth(token)
+                .query(&[
+                    ("startHistoryId", start_history_id),
+                    ("historyTypes", "messageAdded"),
+                ]);
+            if let Some(tok) = &current_page_token {
+                req = req.query(&[("pageToken", tok)]);
+            }
+
+            let resp = req.send().await?;
+            if resp.status() == StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            let resp: GmailHistoryResponse = resp.error_for_status()?.json().await?;
+
+            for entry in resp.history.unwrap_or_default() {
+                added.extend(
+                    entry
+                        .messages_added
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|m| m.message),
+                );
+            }
+            if let Some(history_id) = resp.history_id {
+                latest_history_id = history_id;
+            }
+
+            match resp.next_page_token {
+                Some(tok) => current_page_token = Some(tok),
+                None => break,
+            }
+        }
+        Ok(Some((added, latest_history_id)))
+    }
+
+    /// Gmail label carrying a search `category:` (e.g. primary -> CATEGORY_PERSONAL).
+    fn category_label(category: &str) -> String {
+        match category {
+            "primary" => "CATEGORY_PERSONAL".to_string(),
+            other => format!("CATEGORY_{}", other.to_uppercase()),
+        }
+    }
+
+    /// History deltas are unfiltered, so apply the query's category and sender
+    /// constraints that the search string would otherwise have handled.
+    async fn filter_history(
+        &self,
+        token: &str,
+        query: &MailQuery,
+        added: Vec<GmailHistoryMessage>,
+    ) -> Result<Vec<MailRef>> {
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut ids = Vec::new();
+        let category_label = query.category.as_deref().map(GmailSource::category_label);
+        for message in added {
+            if !seen.insert(message.id.clone()) {
+                continue;
+            }
+            if message
+                .label_ids
+                .iter()
+                .any(|l| l == "DRAFT" || l == "SENT")
+            {
+                continue;
+            }
+            if let Some(label) = &category_label {
+                if !message.label_ids.contains(label) {
+                    continue;
+                }
+            }
+            ids.push(message.id);
+        }
+        if query.issuers.is_empty() {
+            return Ok(ids.into_iter().map(|id| MailRef { id }).collect());
+        }
+
+        let issuers: Vec<String> = query.issuers.iter().map(|i| i.to_lowercase()).collect();
+        stream::iter(ids)
+            .map(|id| async move {
+                let from = self.sender(token, &id).await?;
+                Ok((id, from))
+            })
+            .buffered(SENDER_LOOKUPS)
+            .try_filter_map(|(id, from)| {
+                let from = from.map(|f| f.to_lowercase());
+                let keep = from.is_some_and(|f| issuers.iter().any(|i| f.contains(i)));
+                async move { Ok(keep.then_some(MailRef { id })) }
+            })
+            .try_collect()
+            .await
+    }
+
+    /// Fetches only the `From` header of a message. `None` when the message
+    /// was deleted after the history entry was written (HTTP 404).
+    async fn sender(&self, token: &str, id: &str) -> Result<Option<String>> {
+        let resp = self
+            .client
+            .get(format!("{}/messages/{}", GMAIL_API, id))
+            .bearer_auth(token)
+            .query(&[("format", "metadata"), ("metadataHeaders", "From")])
+            .send()
+            .await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let msg: GmailMetadataMessage = resp.error_for_status()?.json().await?;
+        Ok(Some(
+            msg.payload
+                .into_iter()
+                .flat_map(|p| p.headers)
+                .find(|h| h.name.eq_ignore_ascii_case("from"))
+                .map(|h| h.value)
+                .unwrap_or_default(),
+        ))
+    }
+}
+
+#[async_trait]
+impl MailSource for GmailSource {
+    fn kind(&self) -> &'static str {
+        "gmail"
+    }
+
+    /// Uses the stored `historyId` cursor to list only messages added since
+    /// the last sync, falling back to a full search when there is no cursor
+    /// or Gmail has expired it.
+    async fn list_messages(&self, user: &str, query: &MailQuery) -> Result<MailListing> {
+        let token = self.internal_authenticate(user).await?;
+
+        if let Some(start_history_id) = &query.cursor {
+            match self.list_history(&token, start_history_id).await? {
+                Some((added, history_id)) => {
+                    let messages = self.filter_history(&token, query, added).await?;
+                    println!(
+                        "Found {} new emails since history {}",
+                        messages.len(),
+                        start_history_id
+                    );
+                    return Ok(MailListing {
+                        messages,
+                        cursor: Some(history_id),
+                    });
+                }
+                None => {
+                    tracing::warn!(user = %user, history_id = %start_history_id, "gmail history expired, running full sync");
+                }
+            }
+        }
+
+        // take the cursor before listing so nothing arriving mid-listing is skipped
+        let history_id = self.current_history_id(&token).await?;
+        let messages = self
+            .list_all_messages(&token, &GmailSource::build_search(query))
+            .await?;
+        Ok(MailListing {
+            messages: messages.into_iter().map(|m| MailRef { id: m.id }).collect(),
+            cursor: Some(history_id),
+        })
+    }
+
+    async fn list_page(
+        &self,
+        user: &str,
+        query: &MailQuery,
+        page_token: Option<&str>,
+    ) -> Result<MailPage> {
+        let token = self.internal_authenticate(user).await?;
+        let resp = self
+            .list_search_page(&token, &GmailSource::build_search(query), page_token)
+            .await?;
+        Ok(MailPage {
+            messages: resp
+                .messages
+                .unwrap_or_default()
+                .into_iter()
+                .map(|m| MailRef { id: m.id })
+                .collect(),
+            next_page_token: resp.next_page_token,
+        })
+    }
+
+    /// Calls `users.watch` so Gmail publishes INBOX changes to the Pub/Sub
+    /// `topic`. Registrations lapse after 7 days unless renewed.
+    async fn watch(&self, user: &str, topic: &str) -> Result<Option<i64>> {
+        let token = self.internal_authenticate(user).await?;
+        let resp: GmailWatchResponse = self
+            .client
+            .post(format!("{}/watch", GMAIL_API))
+            .bearer_auth(token)
+            .json(&GmailWatchRequest {
+                topic_name: topic.to_string(),
+                label_ids: vec!["INBOX".to_string()],
+                label_filter_behavior: "include".to_string(),
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(resp.expiration.parse().ok())
+    }
+
+    /// Downloads raw RFC822 bytes of a Gmail message and base64url-decodes them.
+    async fn fetch_raw(&self, user: &str, id: &str) -> Result<Vec<u8>> {
+        let token = self.internal_authenticate(user).await?;
+        let url = format!("{}/messages/{}?format=raw", GMAIL_API, id);
+        let raw_msg: RawGmailMessage = self
+            .client
+            .get(&url)
+            .bearer_auth(token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        decode_base64url(&raw_msg.raw)
+    }
+}
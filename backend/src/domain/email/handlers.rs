@@ -1 +1,192 @@
+use std::sync::Arc;
 
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Extension, Json};
+use serde::Deserialize;
+
+use crate::{
+    common::{api_response::ApiResponse, app_state::AppState},
+    domain::{
+        auth::models::Claims,
+        email::{
+            imap::resolve_imap_host,
+            models::{ImapAccount, ImapAuth},
+        },
+    },
+};
+
+#[derive(Deserialize)]
+pub struct ImapAccountRequest {
+    pub host: String,
+    pub port: Option<u16>,
+    pub username: String,
+    pub auth: ImapAuth,
+    pub mailboxes: Option<Vec<String>>,
+}
+
+/// Saves the caller's IMAP settings and switches their sync to the `imap` source.
+pub async fn set_imap_account(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ImapAccountRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    let port = request.port.unwrap_or(993);
+    resolve_imap_host(&request.host, port)
+        .await
+        .map_err(|err| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::error(format!("Invalid IMAP server: {err:#}"))),
+            )
+        })?;
+
+    let account = ImapAccount {
+        user_email: claims.sub.clone(),
+        host: request.host,
+        port,
+        username: request.username,
+        auth: request.auth,
+        mailboxes: request
+            .mailboxes
+            .filter(|m| !m.is_empty())
+            .unwrap_or_else(|| vec!["INBOX".to_string()]),
+    };
+
+    async {
+        state.email_service.save_imap_account(account).await?;
+        state
+            .user_service
+            .set_mail_source(&claims.sub, Some("imap"))
+            .await
+    }
+    .await
+    .map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!(
+                "Failed to save IMAP account: {err}"
+            ))),
+        )
+    })?;
+
+    Ok(Json(ApiResponse::success(())))
+}
+
+/// Removes the caller's IMAP settings and falls back to the default mail source.
+pub async fn delete_imap_account(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    async {
+        state.email_service.delete_imap_account(&claims.sub).await?;
+        state.user_service.set_mail_source(&claims.sub, None).await
+    }
+    .await
+    .map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!(
+                "Failed to delete IMAP account: {err}"
+            ))),
+        )
+    })?;
+
+    Ok(Json(ApiResponse::success(())))
+}
+
+/// Lists the caller's messages whose extraction failed, newest first.
+pub async fn list_dead_letters(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    let letters = state
+        .email_service
+        .list_dead_letters(&claims.sub)
+        .await
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(format!(
+                    "Failed to list dead letters: {err}"
+                ))),
+            )
+        })?;
+
+    Ok(Json(ApiResponse::success(letters)))
+}
+
+#[derive(Deserialize)]
+pub struct ReprocessRequest {
+    /// Dead letter ids; all of the caller's dead letters when absent.
+    pub ids: Option<Vec<String>>,
+    /// Model to use instead of the configured one, on the same LLM backend.
+    pub model: Option<String>,
+}
+
+/// Re-runs extraction for dead letters and stores any recovered receipts.
+pub async fn reprocess_dead_letters(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ReprocessRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    let summary = async {
+        let (receipts, summary) = state
+            .email_service
+            .reprocess_dead_letters(
+                &claims.sub,
+                request.ids.as_deref(),
+                request.model.as_deref(),
+            )
+            .await?;
+        if !receipts.transactions.is_empty() {
+            state.receipt_service.store(receipts).await?;
+        }
+        anyhow::Ok(summary)
+    }
+    .await
+    .map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!(
+                "Failed to reprocess dead letters: {err}"
+            ))),
+        )
+    })?;
+
+    Ok(Json(ApiResponse::success(summary)))
+}
+
+/// Hit and miss counters of the extraction cache since startup.
+pub async fn cache_stats(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state.email_service.cache_stats() {
+        Some(stats) => Ok(Json(ApiResponse::success(stats))),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error(
+                "Extraction cache is disabled".to_string(),
+            )),
+        )),
+    }
+}
+
+/// Deletes the caller's archived raw messages.
+pub async fn purge_archive(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    let deleted = state
+        .email_service
+        .purge_archive(&claims.sub)
+        .await
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(format!(
+                    "Failed to purge archive: {err}"
+                ))),
+            )
+        })?;
+
+    Ok(Json(ApiResponse::success(deleted)))
+}
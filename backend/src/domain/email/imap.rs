@@ -0,0 +1,459 @@
+use crate::domain::auth::service::AuthService;
+use crate::domain::email::models::*;
+use crate::domain::email::repository::ImapAccountRepo;
+use crate::domain::email::source::MailSource;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::sync::Mutex;
+use tokio_native_tls::TlsStream;
+
+/// Per-mailbox position of the last listing. UIDs are only comparable while
+/// the server keeps the same UIDVALIDITY for the mailbox.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MailboxCursor {
+    uidvalidity: u32,
+    uid: u32,
+}
+
+/// Untagged server response with any literals (`{n}` payloads) it carried.
+struct Untagged {
+    line: String,
+    literals: Vec<Vec<u8>>,
+}
+
+/// Minimal IMAP4rev1 client over implicit TLS, enough for
+/// login/select/search/fetch.
+struct ImapConnection {
+    stream: BufReader<TlsStream<TcpStream>>,
+    tag: u32,
+    selected: Option<(String, u32)>,
+}
+
+/// Quoted IMAP string. CR, LF and NUL cannot be quoted, and letting them
+/// through would end the command and start another.
+fn quote(s: &str) -> Result<String> {
+    if s.contains(['\r', '\n', '\0']) {
+        bail!("IMAP string contains a line break or NUL");
+    }
+    Ok(format!(
+        "\"{}\"",
+        s.replace('\\', "\\\\").replace('"', "\\\"")
+    ))
+}
+
+/// Ports FinOS connects to; the client only speaks IMAP over implicit TLS.
+const IMAP_PORTS: [u16; 1] = [993];
+
+/// Whether an address is reachable on the public internet, so user-supplied
+/// hosts cannot point the server at itself or its private network.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_unspecified()
+                || v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_broadcast()
+                || v4.is_documentation()
+                || a == 0
+                // shared address space (carrier-grade NAT)
+                || (a == 100 && (64..128).contains(&b))
+                || a >= 240)
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public_ip(IpAddr::V4(v4)),
+            None => {
+                let first = v6.segments()[0];
+                !(v6.is_unspecified()
+                    || v6.is_loopback()
+                    || v6.is_multicast()
+                    // unique local fc00::/7 and link-local fe80::/10
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Resolves a user-supplied IMAP server, refusing ports other than
+/// `IMAP_PORTS` and hosts that resolve to a non-public address.
+pub async fn resolve_imap_host(host: &str, port: u16) -> Result<SocketAddr> {
+    if !IMAP_PORTS.contains(&port) {
+        bail!("IMAP port {} is not allowed (use {:?})", port, IMAP_PORTS);
+    }
+    let addrs: Vec<SocketAddr> = lookup_host((host, port))
+        .await
+        .with_context(|| format!("Resolving {}", host))?
+        .collect();
+    if addrs.is_empty() {
+        bail!("{} did not resolve", host);
+    }
+    if let Some(addr) = addrs.iter().find(|a| !is_public_ip(a.ip())) {
+        bail!("{} resolves to non-public address {}", host, addr.ip());
+    }
+    Ok(addrs[0])
+}
+
+/// IMAP dates are day granular (`SINCE 01-Jan-2025`).
+fn imap_date(ms: i64) -> String {
+    Utc.timestamp_millis_opt(ms)
+        .single()
+        .unwrap_or_default()
+        .format("%d-%b-%Y")
+        .to_string()
+}
+
+impl ImapConnection {
+    async fn connect(host: &str, port: u16) -> Result<Self> {
+        // checked again here, since DNS may have changed since it was saved
+        let addr = resolve_imap_host(host, port).await?;
+        let tcp = TcpStream::connect(addr)
+            .await
+            .with_context(|| format!("Connecting to {}:{}", host, port))?;
+        let connector = tokio_native_tls::TlsConnector::from(native_tls::TlsConnector::new()?);
+        let tls = connector
+            .connect(host, tcp)
+            .await
+            .with_context(|| format!("TLS handshake with {}", host))?;
+        let mut conn = ImapConnection {
+            stream: BufReader::new(tls),
+            tag: 0,
+            selected: None,
+        };
+        let greeting = conn.read_line().await?;
+        if !greeting.starts_with("* OK") && !greeting.starts_with("* PREAUTH") {
+            bail!("Unexpected IMAP greeting: {}", greeting.trim_end());
+        }
+        Ok(conn)
+    }
+
+    async fn read_line(&mut self) -> Result<String> {
+        let mut line = String::new();
+        let n = self.stream.read_line(&mut line).await?;
+        if n == 0 {
+            bail!("IMAP connection closed");
+        }
+        Ok(line)
+    }
+
+    /// Literal length announced at the end of a response line, e.g. `{1234}`.
+    fn literal_len(line: &str) -> Option<usize> {
+        let trimmed = line.trim_end();
+        let open = trimmed.strip_suffix('}')?.rfind('{')?;
+        trimmed[open + 1..trimmed.len() - 1].parse().ok()
+    }
+
+    /// Sends a command and collects untagged responses until its tagged completion.
+    async fn command(&mut self, cmd: &str) -> Result<Vec<Untagged>> {
+        self.tag += 1;
+        let tag = format!("A{}", self.tag);
+        self.stream
+            .write_all(format!("{} {}\r\n", tag, cmd).as_bytes())
+            .await?;
+        self.stream.flush().await?;
+
+        let mut responses: Vec<Untagged> = Vec::new();
+        loop {
+            let mut line = self.read_line().await?;
+            if let Some(rest) = line.strip_prefix(&format!("{} ", tag)) {
+                if rest.starts_with("OK") {
+                    return Ok(responses);
+                }
+                bail!("IMAP command failed: {}", rest.trim_end());
+            }
+            if line.starts_with('+') {
+                // continuation request (e.g. SASL error details); answer with empty line
+                self.stream.write_all(b"\r\n").await?;
+                continue;
+            }
+            let mut literals = Vec::new();
+            while let Some(len) = ImapConnection::literal_len(&line) {
+                let mut buf = vec![0u8; len];
+                self.stream.read_exact(&mut buf).await?;
+                literals.push(buf);
+                line.push_str(&self.read_line().await?);
+            }
+            responses.push(Untagged { line, literals });
+        }
+    }
+
+    async fn login(&mut self, username: &str, password: &str) -> Result<()> {
+        self.command(&format!("LOGIN {} {}", quote(username)?, quote(password)?))
+            .await
+            .context("IMAP LOGIN")?;
+        Ok(())
+    }
+
+    async fn authenticate_xoauth2(&mut self, username: &str, access_token: &str) -> Result<()> {
+        let sasl = format!("user={}\x01auth=Bearer {}\x01\x01", username, access_token);
+        let encoded = base64::engine::general_purpose::STANDARD.encode(sasl);
+        self.command(&format!("AUTHENTICATE XOAUTH2 {}", encoded))
+            .await
+            .context("IMAP AUTHENTICATE XOAUTH2")?;
+        Ok(())
+    }
+
+    /// Selects (read-only) `mailbox` and returns its UIDVALIDITY.
+    async fn examine(&mut self, mailbox: &str) -> Result<u32> {
+        if let Some((selected, uidvalidity)) = &self.selected {
+            if selected == mailbox {
+                return Ok(*uidvalidity);
+            }
+        }
+        let responses = self
+            .command(&format!("EXAMINE {}", quote(mailbox)?))
+            .await
+            .with_context(|| format!("Selecting mailbox {}", mailbox))?;
+        let uidvalidity = responses
+            .iter()
+            .find_map(|r| {
+                let start = r.line.find("[UIDVALIDITY ")? + "[UIDVALIDITY ".len();
+                let end = r.line[start..].find(']')? + start;
+                r.line[start..end].parse::<u32>().ok()
+            })
+            .context("Server did not report UIDVALIDITY")?;
+        self.selected = Some((mailbox.to_string(), uidvalidity));
+        Ok(uidvalidity)
+    }
+
+    async fn uid_search(&mut self, criteria: &str) -> Result<Vec<u32>> {
+        let responses = self.command(&format!("UID SEARCH {}", criteria)).await?;
+        Ok(responses
+            .iter()
+            .filter_map(|r| r.line.strip_prefix("* SEARCH"))
+            .flat_map(|rest| rest.split_whitespace().filter_map(|n| n.parse().ok()))
+            .collect())
+    }
+
+    async fn uid_fetch_raw(&mut self, uid: u32) -> Result<Vec<u8>> {
+        let responses = self
+            .command(&format!("UID FETCH {} BODY.PEEK[]", uid))
+            .await?;
+        responses
+            .into_iter()
+            .find(|r| r.line.contains("FETCH"))
+            .and_then(|r| r.literals.into_iter().next())
+            .with_context(|| format!("UID {} not found", uid))
+    }
+}
+
+/// IMAPS mail source for non-Gmail mailboxes (Fastmail, Dovecot, ...).
+///
+/// Message ids have the form `<mailbox>:<uidvalidity>:<uid>` and the cursor
+/// is a JSON map of mailbox to the last seen UIDVALIDITY/UID.
+pub struct ImapSource {
+    accounts: ImapAccountRepo,
+    auth_service: Arc<AuthService>,
+    sessions: Mutex<HashMap<String, Arc<Mutex<Option<ImapConnection>>>>>,
+}
+
+impl ImapSource {
+    pub fn new(accounts: ImapAccountRepo, auth_service: Arc<AuthService>) -> Self {
+        ImapSource {
+            accounts,
+            auth_service,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn open(&self, account: &ImapAccount) -> Result<ImapConnection> {
+        let mut conn = ImapConnection::connect(&account.host, account.port).await?;
+        match &account.auth {
+            ImapAuth::AppPassword { password } => conn.login(&account.username, password).await?,
+            ImapAuth::XOAuth2 { provider } => {
+                let token = self
+                    .auth_service
+                    .get_valid_token(&account.user_email, provider)
+                    .await?;
+                conn.authenticate_xoauth2(&account.username, &token.access_token)
+                    .await?
+            }
+        }
+        Ok(conn)
+    }
+
+    async fn account(&self, user: &str) -> Result<ImapAccount> {
+        self.accounts
+            .get(user)
+            .await?
+            .with_context(|| format!("No IMAP account configured for {}", user))
+    }
+
+    /// Returns the cached connection slot for `user`, so a sync reuses one
+    /// login for listing and all fetches.
+    async fn session(&self, user: &str) -> Arc<Mutex<Option<ImapConnection>>> {
+        self.sessions
+            .lock()
+            .await
+            .entry(user.to_string())
+            .or_default()
+            .clone()
+    }
+
+    fn search_criteria(query: &MailQuery, from_uid: Option<u32>) -> Result<String> {
+        let mut parts: Vec<String> = Vec::new();
+        if let Some(uid) = from_uid {
+            parts.push(format!("UID {}:*", uid));
+        }
+        // IMAP OR is binary: OR FROM a OR FROM b FROM c
+        let issuers = &query.issuers;
+        for (idx, issuer) in issuers.iter().enumerate() {
+            if idx + 1 < issuers.len() {
+                parts.push("OR".to_string());
+            }
+            parts.push(format!("FROM {}", quote(issuer)?));
+        }
+        if let Some(after) = query.after {
+            parts.push(format!("SINCE {}", imap_date(after)));
+        }
+        if let Some(before) = query.before {
+            parts.push(format!("BEFORE {}", imap_date(before)));
+        }
+        if parts.is_empty() {
+            Ok("ALL".to_string())
+        } else {
+            Ok(parts.join(" "))
+        }
+    }
+
+    async fn list_with(
+        conn: &mut ImapConnection,
+        account: &ImapAccount,
+        query: &MailQuery,
+        cursors: &mut HashMap<String, MailboxCursor>,
+    ) -> Result<Vec<MailRef>> {
+        let mut refs = Vec::new();
+        for mailbox in &account.mailboxes {
+            let uidvalidity = conn.examine(mailbox).await?;
+            let last_uid = cursors
+                .get(mailbox)
+                .filter(|c| c.uidvalidity == uidvalidity)
+                .map(|c| c.uid);
+            let criteria = ImapSource::search_criteria(query, last_uid.map(|uid| uid + 1))?;
+            let mut uids = conn.uid_search(&criteria).await?;
+            // `n:*` always matches the highest UID, even when it is below n
+            uids.retain(|uid| last_uid.is_none_or(|last| *uid > last));
+            uids.sort_unstable();
+
+            let max_uid = uids.last().copied().or(last_uid).unwrap_or(0);
+            cursors.insert(
+                mailbox.clone(),
+                MailboxCursor {
+                    uidvalidity,
+                    uid: max_uid,
+                },
+            );
+            refs.extend(uids.into_iter().map(|uid| MailRef {
+                id: format!("{}:{}:{}", mailbox, uidvalidity, uid),
+            }));
+        }
+        Ok(refs)
+    }
+
+    async fn fetch_with(conn: &mut ImapConnection, id: &str) -> Result<Vec<u8>> {
+        let mut parts = id.rsplitn(3, ':');
+        let uid: u32 = parts.next().unwrap_or_default().parse()?;
+        let uidvalidity: u32 = parts.next().unwrap_or_default().parse()?;
+        let mailbox = parts.next().context("Malformed IMAP message id")?;
+        if conn.examine(mailbox).await? != uidvalidity {
+            bail!(
+                "UIDVALIDITY of {} changed, message {} is stale",
+                mailbox,
+                id
+            );
+        }
+        conn.uid_fetch_raw(uid).await
+    }
+}
+
+#[async_trait]
+impl MailSource for ImapSource {
+    fn kind(&self) -> &'static str {
+        "imap"
+    }
+
+    async fn list_messages(&self, user: &str, query: &MailQuery) -> Result<MailListing> {
+        let account = self.account(user).await?;
+        let mut cursors: HashMap<String, MailboxCursor> = match &query.cursor {
+            Some(raw) => serde_json::from_str(raw).unwrap_or_default(),
+            None => HashMap::new(),
+        };
+
+        let slot = self.session(user).await;
+        let mut guard = slot.lock().await;
+        // a fresh login per listing; fetches reuse it afterwards
+        *guard = Some(self.open(&account).await?);
+        let conn = guard.as_mut().expect("connection just opened");
+        let messages = ImapSource::list_with(conn, &account, query, &mut cursors).await?;
+        println!("Found {} IMAP emails to parse", messages.len());
+
+        Ok(MailListing {
+            messages,
+            cursor: Some(serde_json::to_string(&cursors)?),
+        })
+    }
+
+    async fn fetch_raw(&self, user: &str, id: &str) -> Result<Vec<u8>> {
+        let slot = self.session(user).await;
+        let mut guard = slot.lock().await;
+        if let Some(conn) = guard.as_mut() {
+            match ImapSource::fetch_with(conn, id).await {
+                Ok(bytes) => return Ok(bytes),
+                Err(e) => tracing::warn!(error = %e, id = %id, "IMAP fetch failed, reconnecting"),
+            }
+        }
+        let account = self.account(user).await?;
+        let conn = guard.insert(self.open(&account).await?);
+        ImapSource::fetch_with(conn, id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_escapes_and_refuses_line_breaks() {
+        let cases = [
+            ("INBOX", Some(r#""INBOX""#)),
+            (r#"a"b\c"#, Some(r#""a\"b\\c""#)),
+            ("pw\r\nA2 DELETE INBOX", None),
+            ("pw\nx", None),
+            ("pw\0x", None),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(quote(raw).ok().as_deref(), expected, "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn only_public_addresses_are_allowed() {
+        let cases = [
+            ("93.184.216.34", true),
+            ("2606:2800:220:1::1", true),
+            ("127.0.0.1", false),
+            ("10.1.2.3", false),
+            ("172.16.0.1", false),
+            ("192.168.1.1", false),
+            ("169.254.169.254", false),
+            ("100.64.0.1", false),
+            ("0.0.0.0", false),
+            ("::1", false),
+            ("fd00::1", false),
+            ("fe80::1", false),
+            ("::ffff:127.0.0.1", false),
+        ];
+        for (ip, public) in cases {
+            assert_eq!(is_public_ip(ip.parse().unwrap()), public, "{:?}", ip);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use crate::domain::email::models::*;
+use crate::domain::email::source::MailSource;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use mail_parser::MessageParser;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Reads mail exported to the local filesystem: Maildir trees (`cur`/`new`),
+/// loose `.eml` files and `mbox` files.
+///
+/// If `<root>/<user email>` exists it is used as that user's mailbox,
+/// otherwise `<root>` is shared by every user. Message ids are paths relative
+/// to the mailbox, with `#<n>` appended for the n-th message of an mbox.
+#[derive(Clone)]
+pub struct MaildirSource {
+    root: PathBuf,
+}
+
+impl MaildirSource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        MaildirSource { root: root.into() }
+    }
+
+    fn mailbox_dir(&self, user: &str) -> PathBuf {
+        let per_user = self.root.join(user);
+        if per_user.is_dir() {
+            per_user
+        } else {
+            self.root.clone()
+        }
+    }
+
+    fn is_mbox(path: &Path) -> bool {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        name == "mbox" || name.ends_with(".mbox")
+    }
+
+    fn is_message_file(path: &Path) -> bool {
+        let in_maildir = path
+            .parent()
+            .and_then(|p| p.file_name())
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n == "cur" || n == "new");
+        let is_eml = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("eml"));
+        in_maildir || is_eml
+    }
+
+    /// Recursively collects candidate files, skipping Maildir `tmp` folders.
+    fn walk(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in fs::read_dir(dir).with_context(|| format!("Reading {}", dir.display()))? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if path.file_name().and_then(|n| n.to_str()) != Some("tmp") {
+                    MaildirSource::walk(&path, out)?;
+                }
+            } else if MaildirSource::is_mbox(&path) || MaildirSource::is_message_file(&path) {
+                out.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Splits an mbox file on its `From ` separator lines.
+    fn split_mbox(bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut messages: Vec<Vec<u8>> = Vec::new();
+        let mut current: Option<Vec<u8>> = None;
+        for line in bytes.split_inclusive(|b| *b == b'\n') {
+            if line.starts_with(b"From ") {
+                if let Some(done) = current.take() {
+                    messages.push(done);
+                }
+                current = Some(Vec::new());
+                continue;
+            }
+            let body = current.get_or_insert_with(Vec::new);
+            // undo mboxrd quoting of body lines that start with "From "
+            match line.strip_prefix(b">") {
+                Some(rest) if rest.starts_with(b"From ") || rest.starts_with(b">From ") => {
+                    body.extend_from_slice(rest)
+                }
+                _ => body.extend_from_slice(line),
+            }
+        }
+        if let Some(done) = current {
+            messages.push(done);
+        }
+        messages
+    }
+
+    fn matches(query: &MailQuery, raw: &[u8]) -> bool {
+        let Some(headers) = MessageParser::default().parse_headers(raw) else {
+            return false;
+        };
+        if !query.issuers.is_empty() {
+            let from = headers
+                .from()
+                .and_then(|addrs| addrs.first())
+                .and_then(|addr| addr.address())
+                .map(|a| a.to_lowercase())
+                .unwrap_or_default();
+            if !query
+                .issuers
+                .iter()
+                .any(|issuer| from.contains(&issuer.to_lowercase()))
+            {
+                return false;
+            }
+        }
+        let timestamp_ms = headers.date().map(|dt| dt.to_timestamp() * 1000);
+        match (timestamp_ms, query.after, query.before) {
+            (Some(ts), Some(after), _) if ts < after => false,
+            (Some(ts), _, Some(before)) if ts >= before => false,
+            _ => true,
+        }
+    }
+
+    fn list_blocking(mailbox: &Path, query: &MailQuery) -> Result<Vec<MailRef>> {
+        let mut files = Vec::new();
+        MaildirSource::walk(mailbox, &mut files)?;
+        files.sort();
+
+        let mut refs = Vec::new();
+        for path in files {
+            let rel = path
+                .strip_prefix(mailbox)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .to_string();
+            let bytes = fs::read(&path).with_context(|| format!("Reading {}", path.display()))?;
+            if MaildirSource::is_mbox(&path) {
+                for (idx, msg) in MaildirSource::split_mbox(&bytes).iter().enumerate() {
+                    if MaildirSource::matches(query, msg) {
+                        refs.push(MailRef {
+                            id: format!("{}#{}", rel, idx),
+                        });
+                    }
+                }
+            } else if MaildirSource::matches(query, &bytes) {
+                refs.push(MailRef { id: rel });
+            }
+        }
+        Ok(refs)
+    }
+
+    fn fetch_blocking(mailbox: &Path, id: &str) -> Result<Vec<u8>> {
+        let (rel, index) = match id.rsplit_once('#') {
+            Some((rel, idx)) if MaildirSource::is_mbox(Path::new(rel)) => {
+                (rel, Some(idx.parse::<usize>().context("Bad mbox index")?))
+            }
+            _ => (id, None),
+        };
+        let root = mailbox.canonicalize()?;
+        let path = root.join(rel).canonicalize()?;
+        if !path.starts_with(&root) {
+            bail!("message id {} escapes the mailbox", id);
+        }
+        let bytes = fs::read(&path).with_context(|| format!("Reading {}", path.display()))?;
+        match index {
+            Some(idx) => MaildirSource::split_mbox(&bytes)
+                .into_iter()
+                .nth(idx)
+                .with_context(|| format!("mbox message {} not found", id)),
+            None => Ok(bytes),
+        }
+    }
+}
+
+#[async_trait]
+impl MailSource for MaildirSource {
+    fn kind(&self) -> &'static str {
+        "maildir"
+    }
+
+    /// Local folders have no change feed, so every listing rescans the
+    /// mailbox and relies on the message ledger to skip processed messages.
+    async fn list_messages(&self, user: &str, query: &MailQuery) -> Result<MailListing> {
+        let mailbox = self.mailbox_dir(user);
+        let query = query.clone();
+        let messages =
+            tokio::task::spawn_blocking(move || MaildirSource::list_blocking(&mailbox, &query))
+                .await??;
+        println!("Found {} local emails to parse", messages.len());
+        Ok(MailListing {
+            messages,
+            cursor: None,
+        })
+    }
+
+    async fn fetch_raw(&self, user: &str, id: &str) -> Result<Vec<u8>> {
+        let mailbox = self.mailbox_dir(user);
+        let id = id.to_string();
+        tokio::task::spawn_blocking(move || MaildirSource::fetch_blocking(&mailbox, &id)).await?
+    }
+}
@@ -1,4 +1,5 @@
-use serde::Deserialize;
+use crate::domain::receipt::models::{Receipt, ReceiptList};
+use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize)]
 pub struct RawGmailMessage {
@@ -13,6 +14,20 @@ pub struct ParsedEmailContent {
     pub text: Option<String>,
     pub html: Option<String>,
     pub timestamp: Option<i64>,
+    pub attachments: Vec<EmailAttachment>,
+    /// Hash of the archived raw message, when the archive is enabled.
+    pub raw_sha256: Option<String>,
+    /// `Content-Language` header or the HTML `lang` attribute, e.g. `de-DE`.
+    pub locale: Option<String>,
+}
+
+/// Attachment content usable for receipt extraction: extracted text for
+/// PDFs and text parts, raw bytes for images.
+pub struct EmailAttachment {
+    pub name: String,
+    pub content_type: String,
+    pub text: Option<String>,
+    pub image: Option<Vec<u8>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -30,3 +45,264 @@ pub struct GmailMessage {
     #[serde(rename = "threadId")]
     pub thread_id: String,
 }
+
+/// Source-agnostic description of the messages a sync is interested in.
+/// Each `MailSource` renders this into its own search syntax.
+#[derive(Debug, Clone, Default)]
+pub struct MailQuery {
+    pub issuers: Vec<String>,
+    pub category: Option<String>,
+    /// Inclusive lower bound, unix millis.
+    pub after: Option<i64>,
+    /// Exclusive upper bound, unix millis.
+    pub before: Option<i64>,
+    /// Opaque position returned by the previous listing of the same source.
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MailRef {
+    pub id: String,
+}
+
+#[derive(Debug, Default)]
+pub struct MailListing {
+    pub messages: Vec<MailRef>,
+    pub cursor: Option<String>,
+}
+
+/// One page of a full search; `next_page_token` is `None` on the last page.
+#[derive(Debug, Default)]
+pub struct MailPage {
+    pub messages: Vec<MailRef>,
+    pub next_page_token: Option<String>,
+}
+
+/// Result of importing one page of historical mail.
+pub struct ImportedPage {
+    pub listed: usize,
+    /// Messages not already in the ledger, which were processed now.
+    pub processed: usize,
+    pub receipts: ReceiptList,
+    pub next_page_token: Option<String>,
+}
+
+/// How FinOS authenticates against an IMAP server.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImapAuth {
+    /// Plain `LOGIN` with an app-specific password.
+    AppPassword { password: String },
+    /// SASL XOAUTH2 with the OAuth token stored for `provider`.
+    #[serde(rename = "xoauth2")]
+    XOAuth2 { provider: String },
+}
+
+/// Keeps the app password out of logs.
+impl std::fmt::Debug for ImapAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImapAuth::AppPassword { .. } => f
+                .debug_struct("AppPassword")
+                .field("password", &"<redacted>")
+                .finish(),
+            ImapAuth::XOAuth2 { provider } => f
+                .debug_struct("XOAuth2")
+                .field("provider", provider)
+                .finish(),
+        }
+    }
+}
+
+fn default_imap_port() -> u16 {
+    993
+}
+
+fn default_mailboxes() -> Vec<String> {
+    vec!["INBOX".to_string()]
+}
+
+/// IMAP mailbox connection settings for a user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImapAccount {
+    #[serde(rename = "_id")]
+    pub user_email: String,
+    pub host: String,
+    #[serde(default = "default_imap_port")]
+    pub port: u16,
+    pub username: String,
+    pub auth: ImapAuth,
+    #[serde(default = "default_mailboxes")]
+    pub mailboxes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GmailProfile {
+    #[serde(rename = "emailAddress")]
+    pub email_address: String,
+    #[serde(rename = "historyId")]
+    pub history_id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GmailWatchRequest {
+    #[serde(rename = "topicName")]
+    pub topic_name: String,
+    #[serde(rename = "labelIds")]
+    pub label_ids: Vec<String>,
+    #[serde(rename = "labelFilterBehavior")]
+    pub label_filter_behavior: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GmailWatchResponse {
+    #[serde(rename = "historyId")]
+    pub history_id: String,
+    /// Unix millis, sent as a string.
+    pub expiration: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GmailHistoryResponse {
+    pub history: Option<Vec<GmailHistory>>,
+    #[serde(rename = "nextPageToken")]
+    pub next_page_token: Option<String>,
+    #[serde(rename = "historyId")]
+    pub history_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GmailHistory {
+    #[serde(rename = "messagesAdded")]
+    pub messages_added: Option<Vec<GmailHistoryMessageAdded>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GmailHistoryMessageAdded {
+    pub message: GmailHistoryMessage,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct GmailHistoryMessage {
+    pub id: String,
+    #[serde(rename = "labelIds", default)]
+    pub label_ids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GmailMetadataMessage {
+    pub payload: Option<GmailMessagePayload>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GmailMessagePayload {
+    #[serde(default)]
+    pub headers: Vec<GmailHeader>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GmailHeader {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageStatus {
+    /// Subject did not look like a transaction email.
+    SkippedSubject,
+    /// Processed, but nothing was extracted.
+    NoTransactions,
+    Parsed,
+    /// Errored; retried at `next_retry` until the attempts run out.
+    Failed,
+}
+
+/// Processing record for one message of one mail source.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    #[serde(rename = "_id")]
+    pub id: String, // <-- "<user>/<source>/<message id>"
+    pub user_email: String,
+    pub source: String,
+    pub msg_id: String,
+    pub status: MessageStatus,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    /// Unix millis after which a failed message is retried; `None` when not
+    /// failed or when retries are exhausted.
+    pub next_retry: Option<i64>,
+    pub receipts: u32,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// A message whose extraction failed, kept for inspection and reprocessing.
+/// `source` and `msg_id` locate the raw message in the user's mailbox.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    #[serde(rename = "_id")]
+    pub id: String, // <-- same as the ledger entry id
+    pub user_email: String,
+    pub source: String,
+    pub msg_id: String,
+    pub issuer: Option<String>,
+    pub subject: Option<String>,
+    /// Outermost error first, followed by its causes.
+    pub error_chain: Vec<String>,
+    /// Archived raw message, when the archive is enabled.
+    pub raw_sha256: Option<String>,
+    pub attempts: u32,
+    /// Whether automatic retries have run out.
+    pub exhausted: bool,
+    /// LLM that was used for the failed attempt.
+    pub model: String,
+    pub failed_at: i64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReprocessSummary {
+    pub processed: usize,
+    /// Dead letters that now processed cleanly and were removed.
+    pub recovered: Vec<String>,
+    pub failed: Vec<String>,
+    pub receipts: usize,
+}
+
+/// Links one user's message to its archived bytes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveRef {
+    #[serde(rename = "_id")]
+    pub id: String, // <-- same as the ledger entry id
+    pub user_email: String,
+    pub source: String,
+    pub msg_id: String,
+    pub sha256: String,
+    pub size: u64,
+    pub archived_at: i64,
+}
+
+/// A stored extraction result. `_id` hashes the normalized input together
+/// with the model and prompt version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedExtraction {
+    #[serde(rename = "_id")]
+    pub id: String,
+    /// Configured model chain the entry is keyed on.
+    #[serde(default)]
+    pub chain: String,
+    /// Model of the chain that answered.
+    pub model: String,
+    pub prompt_version: String,
+    pub receipts: Vec<Receipt>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub memory_hits: u64,
+    /// Misses in memory answered from Mongo.
+    pub store_hits: u64,
+    pub misses: u64,
+    pub memory_entries: usize,
+}
@@ -0,0 +1,211 @@
+use anyhow::{bail, Context, Result};
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Prompts compiled into the binary; `PROMPTS_DIR` adds to them.
+const BUILTIN_PROMPTS: &[&str] = &[
+    include_str!("../../../prompts/receipt-text.v1.yaml"),
+    include_str!("../../../prompts/receipt-text.v2.yaml"),
+    include_str!("../../../prompts/receipt-image.v1.yaml"),
+];
+
+const VARIABLES: &[&str] = &["email_text", "issuer", "locale", "schema", "examples"];
+
+static PLACEHOLDER_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{\s*([A-Za-z_]+)\s*\}\}").unwrap());
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptInput {
+    /// Email bodies and text or PDF attachments.
+    #[default]
+    Text,
+    /// Images for the vision model.
+    Image,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PromptExample {
+    pub input: String,
+    /// The JSON the model should answer with.
+    pub output: String,
+}
+
+/// One prompt file. `template` uses `{{email_text}}`, `{{issuer}}`,
+/// `{{locale}}`, `{{schema}}` and `{{examples}}`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PromptSpec {
+    pub name: String,
+    pub version: u32,
+    #[serde(default)]
+    pub input: PromptInput,
+    /// Regex matched against the sender address (case-insensitive).
+    pub issuer: Option<String>,
+    /// Language the prompt is for, e.g. `de`; matched against the email's.
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub examples: Vec<PromptExample>,
+    pub template: String,
+}
+
+struct CompiledPrompt {
+    spec: PromptSpec,
+    issuer: Option<Regex>,
+}
+
+/// What a prompt is chosen by and filled with.
+pub struct PromptContext<'a> {
+    pub sender: &'a str,
+    pub issuer: &'a str,
+    pub locale: Option<&'a str>,
+    pub schema: &'a str,
+}
+
+pub struct RenderedPrompt {
+    pub text: String,
+    /// `<name>@v<version>`, stored on each receipt as `prompt_version`.
+    pub version: String,
+}
+
+/// Versioned extraction prompts, chosen per issuer and locale.
+pub struct PromptLibrary {
+    prompts: Vec<CompiledPrompt>,
+}
+
+impl PromptLibrary {
+    pub fn builtin() -> Result<Self> {
+        let mut library = PromptLibrary {
+            prompts: Vec::new(),
+        };
+        for raw in BUILTIN_PROMPTS {
+            library.add(serde_yaml::from_str(raw).context("Parsing built-in prompt")?)?;
+        }
+        Ok(library)
+    }
+
+    /// Built-in prompts plus every `.yaml`/`.yml` file in `dir`.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut library = PromptLibrary::builtin()?;
+        let entries = std::fs::read_dir(dir)
+            .with_context(|| format!("Reading prompts folder {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if !matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("yaml" | "yml")
+            ) {
+                continue;
+            }
+            let raw = std::fs::read_to_string(&path)
+                .with_context(|| format!("Reading prompt {}", path.display()))?;
+            let spec = serde_yaml::from_str(&raw)
+                .with_context(|| format!("Parsing prompt {}", path.display()))?;
+            library.add(spec)?;
+        }
+        println!("Loaded {} prompts", library.prompts.len());
+        Ok(library)
+    }
+
+    fn add(&mut self, spec: PromptSpec) -> Result<()> {
+        let version = spec_version(&spec);
+        if self
+            .prompts
+            .iter()
+            .any(|p| spec_version(&p.spec) == version)
+        {
+            bail!("Prompt {} is defined twice", version);
+        }
+        for captures in PLACEHOLDER_RE.captures_iter(&spec.template) {
+            if !VARIABLES.contains(&&captures[1]) {
+                bail!("Prompt {} uses unknown variable {}", version, &captures[1]);
+            }
+        }
+        if spec.input == PromptInput::Text && !spec.template.contains("email_text") {
+            bail!("Prompt {} never includes {{{{email_text}}}}", version);
+        }
+        let issuer = spec
+            .issuer
+            .as_deref()
+            .map(|re| RegexBuilder::new(re).case_insensitive(true).build())
+            .transpose()
+            .with_context(|| format!("Invalid issuer pattern in prompt {}", version))?;
+        self.prompts.push(CompiledPrompt { spec, issuer });
+        Ok(())
+    }
+
+    /// Picks the most specific prompt for the email: issuer-specific before
+    /// locale-specific before generic, then the highest version.
+    fn select(&self, input: PromptInput, ctx: &PromptContext) -> Option<&CompiledPrompt> {
+        let language = ctx.locale.map(primary_language);
+        self.prompts
+            .iter()
+            .filter(|p| p.spec.input == input)
+            .filter(|p| p.issuer.as_ref().is_none_or(|re| re.is_match(ctx.sender)))
+            .filter(|p| {
+                p.spec
+                    .locale
+                    .as_deref()
+                    .is_none_or(|l| Some(primary_language(l)) == language)
+            })
+            .max_by_key(|p| (p.issuer.is_some(), p.spec.locale.is_some(), p.spec.version))
+    }
+
+    pub fn render(
+        &self,
+        input: PromptInput,
+        ctx: &PromptContext,
+        email_text: &str,
+    ) -> Result<RenderedPrompt> {
+        let prompt = self
+            .select(input, ctx)
+            .with_context(|| format!("No {:?} prompt matches {}", input, ctx.sender))?;
+        let examples = prompt
+            .spec
+            .examples
+            .iter()
+            .enumerate()
+            .map(|(i, e)| {
+                format!(
+                    "Example {}:\nInput:\n{}\nOutput:\n{}",
+                    i + 1,
+                    e.input.trim(),
+                    e.output.trim()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let text = PLACEHOLDER_RE
+            .replace_all(
+                &prompt.spec.template,
+                |captures: &regex::Captures| match &captures[1] {
+                    "email_text" => email_text.to_string(),
+                    "issuer" => ctx.issuer.to_string(),
+                    "locale" => ctx.locale.unwrap_or("unknown").to_string(),
+                    "schema" => ctx.schema.to_string(),
+                    _ => examples.clone(),
+                },
+            )
+            .into_owned();
+        Ok(RenderedPrompt {
+            text,
+            version: spec_version(&prompt.spec),
+        })
+    }
+}
+
+fn spec_version(spec: &PromptSpec) -> String {
+    format!("{}@v{}", spec.name, spec.version)
+}
+
+/// `de` for `de-CH` or `de_CH`.
+fn primary_language(locale: &str) -> String {
+    locale
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
@@ -1,15 +1,26 @@
-use anyhow::{Context, Result};
+use crate::common::secret::SecretBox;
+use crate::domain::email::models::{
+    ArchiveRef, CachedExtraction, DeadLetter, ImapAccount, ImapAuth, LedgerEntry, MessageStatus,
+};
+use anyhow::{bail, Context, Result};
+use futures::TryStreamExt;
 use mongodb::{
     bson::{doc, DateTime},
     Client, Collection,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrackedEmails {
     #[serde(rename = "_id")]
     pub id: String, // <-- is the user's email address
+    /// Processed message ids from before the ledger; migrated and cleared on the next sync.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub emails: Vec<String>,
+    /// Last listing cursor per mail source kind.
+    #[serde(default)]
+    pub cursors: HashMap<String, String>,
     pub created_at: i64,
     pub updated_at: i64,
 }
@@ -26,15 +37,16 @@ impl EmailRepo {
         }
     }
 
-    pub async fn set_tracked_emails(
+    pub async fn set_cursors(
         &self,
         email_addr: &str,
-        tracked_emails: Vec<String>,
+        cursors: HashMap<String, String>,
     ) -> Result<()> {
         let filter = doc! { "_id": email_addr};
         let to_upsert = TrackedEmails {
             id: email_addr.to_string(),
-            emails: tracked_emails,
+            emails: vec![],
+            cursors,
             created_at: DateTime::now().timestamp_millis(),
             updated_at: DateTime::now().timestamp_millis(),
         };
@@ -42,15 +54,358 @@ impl EmailRepo {
             .replace_one(filter, to_upsert)
             .upsert(true)
             .await
-            .with_context(|| format!("Failed to set tracked emails for {}", email_addr))?;
+            .with_context(|| format!("Failed to set cursors for {}", email_addr))?;
         Ok(())
     }
 
     pub async fn get_tracked_emails(&self, email_addr: &str) -> Result<Option<TrackedEmails>> {
+        self.collection
+            .find_one(doc! { "_id": email_addr })
+            .await
+            .with_context(|| format!("Failed to get tracked emails for {}", email_addr))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LedgerRepo {
+    collection: Collection<LedgerEntry>,
+}
+
+impl LedgerRepo {
+    pub fn new(client: &Client, database: &str) -> Self {
+        LedgerRepo {
+            collection: client.database(database).collection("message_ledger"),
+        }
+    }
+
+    pub fn entry_id(user_email: &str, source: &str, msg_id: &str) -> String {
+        format!("{}/{}/{}", user_email, source, msg_id)
+    }
+
+    async fn find_by(&self, filter: mongodb::bson::Document) -> Result<Vec<LedgerEntry>> {
+        let mut result = Vec::new();
+        let mut cursor = self.collection.find(filter).await?;
+        while let Some(entry) = cursor.try_next().await? {
+            result.push(entry);
+        }
+        Ok(result)
+    }
+
+    /// Ledger entries for the given messages, keyed by message id.
+    pub async fn get_many(
+        &self,
+        user_email: &str,
+        source: &str,
+        msg_ids: &[String],
+    ) -> Result<HashMap<String, LedgerEntry>> {
+        let ids: Vec<String> = msg_ids
+            .iter()
+            .map(|id| LedgerRepo::entry_id(user_email, source, id))
+            .collect();
+        let entries = self
+            .find_by(doc! { "_id": { "$in": ids } })
+            .await
+            .with_context(|| format!("Failed to read message ledger for {}", user_email))?;
+        Ok(entries.into_iter().map(|e| (e.msg_id.clone(), e)).collect())
+    }
+
+    /// Failed messages whose retry time has passed.
+    pub async fn due_retries(
+        &self,
+        user_email: &str,
+        source: &str,
+        now: i64,
+    ) -> Result<Vec<LedgerEntry>> {
+        self.find_by(doc! {
+            "user_email": user_email,
+            "source": source,
+            "status": "failed",
+            "next_retry": { "$lte": now },
+        })
+        .await
+        .with_context(|| format!("Failed to read due retries for {}", user_email))
+    }
+
+    pub async fn upsert(&self, entry: LedgerEntry) -> Result<()> {
+        let id = entry.id.clone();
+        self.collection
+            .replace_one(doc! { "_id": &id }, entry)
+            .upsert(true)
+            .await
+            .with_context(|| format!("Failed to record ledger entry {}", id))?;
+        Ok(())
+    }
+
+    /// Imports ids from the old `tracked_emails.emails` list as parsed messages.
+    pub async fn import_legacy(
+        &self,
+        user_email: &str,
+        source: &str,
+        msg_ids: Vec<String>,
+    ) -> Result<()> {
+        let now = DateTime::now().timestamp_millis();
+        let entries = msg_ids.into_iter().map(|msg_id| LedgerEntry {
+            id: LedgerRepo::entry_id(user_email, source, &msg_id),
+            user_email: user_email.to_string(),
+            source: source.to_string(),
+            msg_id,
+            status: MessageStatus::Parsed,
+            attempts: 1,
+            last_error: None,
+            next_retry: None,
+            receipts: 0,
+            created_at: now,
+            updated_at: now,
+        });
+        // ids already in the ledger fail as duplicates; the rest are still inserted
+        if let Err(e) = self.collection.insert_many(entries).ordered(false).await {
+            tracing::warn!(error = %e, user = %user_email, "legacy ledger import was partial");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeadLetterRepo {
+    collection: Collection<DeadLetter>,
+}
+
+impl DeadLetterRepo {
+    pub fn new(client: &Client, database: &str) -> Self {
+        DeadLetterRepo {
+            collection: client.database(database).collection("dead_letters"),
+        }
+    }
+
+    pub async fn upsert(&self, letter: DeadLetter) -> Result<()> {
+        let id = letter.id.clone();
+        self.collection
+            .replace_one(doc! { "_id": &id }, letter)
+            .upsert(true)
+            .await
+            .with_context(|| format!("Failed to save dead letter {}", id))?;
+        Ok(())
+    }
+
+    /// The user's dead letters, newest first; restricted to `ids` when given.
+    pub async fn list(&self, user_email: &str, ids: Option<&[String]>) -> Result<Vec<DeadLetter>> {
+        let mut filter = doc! { "user_email": user_email };
+        if let Some(ids) = ids {
+            filter.insert("_id", doc! { "$in": ids });
+        }
+        let mut result = Vec::new();
+        let mut cursor = self
+            .collection
+            .find(filter)
+            .sort(doc! { "failed_at": -1 })
+            .await
+            .with_context(|| format!("Failed to list dead letters for {}", user_email))?;
+        while let Some(letter) = cursor.try_next().await? {
+            result.push(letter);
+        }
+        Ok(result)
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        self.collection
+            .delete_one(doc! { "_id": id })
+            .await
+            .with_context(|| format!("Failed to delete dead letter {}", id))?;
+        Ok(())
+    }
+}
+
+/// IMAP settings, with app passwords encrypted by `secrets` before they are
+/// written and decrypted when read.
+#[derive(Debug, Clone)]
+pub struct ImapAccountRepo {
+    collection: Collection<ImapAccount>,
+    secrets: Option<SecretBox>,
+}
+
+impl ImapAccountRepo {
+    pub fn new(client: &Client, database: &str, secrets: Option<SecretBox>) -> Self {
+        ImapAccountRepo {
+            collection: client.database(database).collection("imap_accounts"),
+            secrets,
+        }
+    }
+
+    fn secrets(&self) -> Result<&SecretBox> {
+        match &self.secrets {
+            Some(secrets) => Ok(secrets),
+            None => bail!("IMAP_SECRET_KEY must be set to store IMAP app passwords"),
+        }
+    }
+
+    pub async fn upsert(&self, mut account: ImapAccount) -> Result<()> {
+        if let ImapAuth::AppPassword { password } = &mut account.auth {
+            if !SecretBox::is_sealed(password) {
+                *password = self.secrets()?.seal(password)?;
+            }
+        }
+        let user_email = account.user_email.clone();
+        self.collection
+            .replace_one(doc! { "_id": &user_email }, account)
+            .upsert(true)
+            .await
+            .with_context(|| format!("Failed to save IMAP account for {}", user_email))?;
+        Ok(())
+    }
+
+    pub async fn get(&self, user_email: &str) -> Result<Option<ImapAccount>> {
+        let mut account = self
+            .collection
+            .find_one(doc! { "_id": user_email })
+            .await
+            .with_context(|| format!("Failed to get IMAP account for {}", user_email))?;
+        if let Some(ImapAuth::AppPassword { password }) = account.as_mut().map(|a| &mut a.auth) {
+            // passwords saved before encryption are read as they are
+            if SecretBox::is_sealed(password) {
+                *password = self
+                    .secrets()?
+                    .open(password)
+                    .with_context(|| format!("IMAP password of {}", user_email))?;
+            }
+        }
+        Ok(account)
+    }
+
+    /// Encrypts app passwords stored before encryption. Returns how many were
+    /// rewritten; does nothing without a key.
+    pub async fn seal_passwords(&self) -> Result<u64> {
+        if self.secrets.is_none() {
+            return Ok(0);
+        }
+        let mut cursor = self
+            .collection
+            .find(doc! { "auth.type": "app_password" })
+            .await
+            .context("Failed to list IMAP accounts")?;
+        let mut sealed = 0;
+        while let Some(account) = cursor.try_next().await? {
+            let ImapAuth::AppPassword { password } = &account.auth else {
+                continue;
+            };
+            if !SecretBox::is_sealed(password) {
+                self.upsert(account).await?;
+                sealed += 1;
+            }
+        }
+        Ok(sealed)
+    }
+
+    pub async fn delete(&self, user_email: &str) -> Result<()> {
+        self.collection
+            .delete_one(doc! { "_id": user_email })
+            .await
+            .with_context(|| format!("Failed to delete IMAP account for {}", user_email))?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArchiveRepo {
+    collection: Collection<ArchiveRef>,
+}
+
+impl ArchiveRepo {
+    pub fn new(client: &Client, database: &str) -> Self {
+        ArchiveRepo {
+            collection: client.database(database).collection("raw_archive"),
+        }
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<ArchiveRef>> {
+        self.collection
+            .find_one(doc! { "_id": id })
+            .await
+            .with_context(|| format!("Failed to get archive ref {}", id))
+    }
+
+    pub async fn upsert(&self, archive_ref: ArchiveRef) -> Result<()> {
+        let id = archive_ref.id.clone();
+        self.collection
+            .replace_one(doc! { "_id": &id }, archive_ref)
+            .upsert(true)
+            .await
+            .with_context(|| format!("Failed to save archive ref {}", id))?;
+        Ok(())
+    }
+
+    /// Deletes the matching refs and returns the hashes they pointed to.
+    async fn delete_where(&self, filter: mongodb::bson::Document) -> Result<Vec<String>> {
+        let mut hashes = Vec::new();
+        let mut cursor = self.collection.find(filter.clone()).await?;
+        while let Some(archive_ref) = cursor.try_next().await? {
+            hashes.push(archive_ref.sha256);
+        }
+        self.collection.delete_many(filter).await?;
+        hashes.sort();
+        hashes.dedup();
+        Ok(hashes)
+    }
+
+    pub async fn list_for_user(&self, user_email: &str) -> Result<Vec<ArchiveRef>> {
+        let mut result = Vec::new();
+        let mut cursor = self
+            .collection
+            .find(doc! { "user_email": user_email })
+            .sort(doc! { "archived_at": 1 })
+            .await
+            .with_context(|| format!("Failed to list archive refs for {}", user_email))?;
+        while let Some(archive_ref) = cursor.try_next().await? {
+            result.push(archive_ref);
+        }
+        Ok(result)
+    }
+
+    pub async fn delete_for_user(&self, user_email: &str) -> Result<Vec<String>> {
+        self.delete_where(doc! { "user_email": user_email })
+            .await
+            .with_context(|| format!("Failed to purge archive refs for {}", user_email))
+    }
+
+    pub async fn delete_older_than(&self, cutoff: i64) -> Result<Vec<String>> {
+        self.delete_where(doc! { "archived_at": { "$lt": cutoff } })
+            .await
+            .context("Failed to expire archive refs")
+    }
+
+    pub async fn is_referenced(&self, sha256: &str) -> Result<bool> {
         Ok(self
             .collection
-            .find_one(doc! { "_id": email_addr })
+            .find_one(doc! { "sha256": sha256 })
+            .await?
+            .is_some())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExtractionCacheRepo {
+    collection: Collection<CachedExtraction>,
+}
+
+impl ExtractionCacheRepo {
+    pub fn new(client: &Client, database: &str) -> Self {
+        ExtractionCacheRepo {
+            collection: client.database(database).collection("extraction_cache"),
+        }
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<CachedExtraction>> {
+        self.collection
+            .find_one(doc! { "_id": key })
             .await
-            .with_context(|| format!("Failed to get tracked emails for {}", email_addr))?)
+            .with_context(|| format!("Failed to read cached extraction {}", key))
+    }
+
+    pub async fn upsert(&self, entry: &CachedExtraction) -> Result<()> {
+        self.collection
+            .replace_one(doc! { "_id": &entry.id }, entry)
+            .upsert(true)
+            .await
+            .with_context(|| format!("Failed to cache extraction {}", entry.id))?;
+        Ok(())
     }
 }
@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use axum::{
+    middleware,
+    routing::{delete, get, post, put},
+    Router,
+};
+
+use crate::{
+    common::app_state::AppState,
+    domain::{
+        auth::handlers::authorization_middleware,
+        email::handlers::{
+            cache_stats, delete_imap_account, list_dead_letters, purge_archive,
+            reprocess_dead_letters, set_imap_account,
+        },
+    },
+};
+
+pub fn routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route(
+            "/mail/imap",
+            put(set_imap_account).delete(delete_imap_account),
+        )
+        .route("/mail/archive", delete(purge_archive))
+        .route("/mail/cache", get(cache_stats))
+        .route("/mail/dead-letters", get(list_dead_letters))
+        .route("/mail/dead-letters/reprocess", post(reprocess_dead_letters))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            authorization_middleware,
+        ))
+        .with_state(state)
+}
@@ -1,34 +1,33 @@
-use crate::domain::auth::repository::TokenStore;
-use crate::domain::auth::service::AuthService;
+use crate::domain::email::archive::RawArchive;
+use crate::domain::email::extractor::Extractor;
 use crate::domain::email::models::*;
-use crate::domain::email::repository::EmailRepo;
+use crate::domain::email::repository::{DeadLetterRepo, EmailRepo, ImapAccountRepo, LedgerRepo};
+use crate::domain::email::source::MailSource;
+use crate::domain::llm::client::LlmClient;
 use crate::domain::receipt::models::{Receipt, ReceiptList};
 use anyhow::{Context, Result};
-use base64::Engine;
+use chrono::Utc;
 use ego_tree::NodeRef;
 use futures::{stream, StreamExt};
-use mail_parser::{Message, MessageParser};
-use ollama_rs::{generation::completion::request::GenerationRequest, Ollama};
+use mail_parser::{Message, MessageParser, MessagePart, MimeHeaders};
 use once_cell::sync::Lazy;
 use regex::{escape, Regex};
-use reqwest::Client;
 use scraper::{Html, Node};
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::vec;
+
+/// Attachments above this size are skipped rather than parsed.
+const MAX_ATTACHMENT_BYTES: usize = 10 * 1024 * 1024;
+
+/// Processing attempts before a failing message is given up on.
+const MAX_ATTEMPTS: u32 = 5;
+/// First retry delay; doubled on each further failure up to `RETRY_MAX_MS`.
+const RETRY_BASE_MS: i64 = 5 * 60 * 1000;
+const RETRY_MAX_MS: i64 = 24 * 60 * 60 * 1000;
 
 static SUBJECT_RE: Lazy<Regex> =
     Lazy::new(|| build_keyword_regex(&["transaction", "spent", "payment"]));
 
-// TODO: ??
-fn decode_base64url(s: &str) -> Result<Vec<u8>> {
-    let mut s = s.replace('-', "+").replace('_', "/");
-    while s.len() % 4 != 0 {
-        s.push('=');
-    }
-    Ok(base64::engine::general_purpose::STANDARD.decode(s)?)
-}
-
 fn build_keyword_regex(words: &[&str]) -> Regex {
     let body = words
         .iter()
@@ -40,104 +39,474 @@ fn build_keyword_regex(words: &[&str]) -> Regex {
 
 #[derive(Clone)]
 pub struct EmailService {
-    client: Client,
-    ollama: Ollama,
+    sources: HashMap<String, Arc<dyn MailSource>>,
+    default_source: String,
+    imap_accounts: ImapAccountRepo,
     db_client: EmailRepo,
-    auth_service: Arc<AuthService>,
-    model_name: String,
+    ledger: LedgerRepo,
+    dead_letters: DeadLetterRepo,
+    archive: Option<Arc<RawArchive>>,
+    extractor: Arc<Extractor>,
+}
+
+/// Outcome of processing one message, with the headers read before any failure.
+struct ProcessedMessage {
+    subject: Option<String>,
+    issuer: Option<String>,
+    raw_sha256: Option<String>,
+    result: Result<Option<Vec<Receipt>>>,
 }
 
 impl EmailService {
-    /// Creates a new `EmailService`, loading `OLLAMA_MODEL` from the environment
-    /// and initializing the HTTP and Ollama clients.
-    pub fn new(model_name: String, db_client: EmailRepo, auth_service: Arc<AuthService>) -> Self {
+    /// Creates a new `EmailService` over the given mail sources (keyed by
+    /// their `kind`).
+    pub fn new(
+        extractor: Extractor,
+        db_client: EmailRepo,
+        ledger: LedgerRepo,
+        dead_letters: DeadLetterRepo,
+        imap_accounts: ImapAccountRepo,
+        sources: Vec<Arc<dyn MailSource>>,
+        default_source: String,
+    ) -> Self {
         EmailService {
-            model_name: model_name,
-            client: reqwest::Client::new(),
-            ollama: Ollama::default(),
-            db_client: db_client,
-            auth_service,
+            extractor: Arc::new(extractor),
+            sources: sources
+                .into_iter()
+                .map(|source| (source.kind().to_string(), source))
+                .collect(),
+            default_source,
+            imap_accounts,
+            db_client,
+            ledger,
+            dead_letters,
+            archive: None,
+        }
+    }
+
+    /// Keeps the raw bytes of every fetched message in `archive`.
+    pub fn with_archive(mut self, archive: Arc<RawArchive>) -> Self {
+        self.archive = Some(archive);
+        self
+    }
+
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.extractor.cache_stats()
+    }
+
+    /// Deletes the user's archived raw messages. Returns the number of blobs removed.
+    pub async fn purge_archive(&self, user_email: &str) -> Result<usize> {
+        match &self.archive {
+            Some(archive) => archive.purge_user(user_email).await,
+            None => Ok(0),
+        }
+    }
+
+    /// Expires archived messages past the retention period.
+    pub async fn apply_archive_retention(&self) -> Result<usize> {
+        match &self.archive {
+            Some(archive) => archive.apply_retention().await,
+            None => Ok(0),
         }
     }
 
-    /// Returns the set of previously processed (tracked) email IDs.
-    async fn get_tracked_emails(&self, email_addr: &str) -> Result<HashSet<String>> {
-        println!("Retrieving tracked emails for user: {}", email_addr);
-        if let Some(tracked_emails) = self
+    pub fn archive_retention_enabled(&self) -> bool {
+        self.archive.as_ref().is_some_and(|a| a.retention_enabled())
+    }
+
+    /// Resolves the mail source for a user, falling back to the configured default.
+    fn source_for(&self, kind: Option<&str>) -> Result<Arc<dyn MailSource>> {
+        let kind = kind.unwrap_or(&self.default_source);
+        self.sources
+            .get(kind)
+            .cloned()
+            .with_context(|| format!("Mail source {} is not configured", kind))
+    }
+
+    /// Registers push notifications for the user's mailbox, if their source supports it.
+    pub async fn watch_mailbox(
+        &self,
+        email_addr: &str,
+        source_kind: Option<&str>,
+        topic: &str,
+    ) -> Result<Option<i64>> {
+        self.source_for(source_kind)?
+            .watch(email_addr, topic)
+            .await
+            .with_context(|| format!("Registering mailbox watch for {}", email_addr))
+    }
+
+    /// Stores the IMAP connection settings used by the `imap` mail source.
+    pub async fn save_imap_account(&self, account: ImapAccount) -> Result<()> {
+        self.imap_accounts.upsert(account).await
+    }
+
+    pub async fn delete_imap_account(&self, user_email: &str) -> Result<()> {
+        self.imap_accounts.delete(user_email).await
+    }
+
+    /// Returns the stored listing cursors. Message ids tracked before the
+    /// ledger existed are imported into it for `source_kind` on first use.
+    async fn get_cursors(
+        &self,
+        email_addr: &str,
+        source_kind: &str,
+    ) -> Result<HashMap<String, String>> {
+        println!("Retrieving cursors for user: {}", email_addr);
+        let Some(tracked_emails) = self
             .db_client
             .get_tracked_emails(email_addr)
             .await
             .with_context(|| format!("Getting tracked emails for {}", email_addr))?
-        {
-            Ok(tracked_emails.emails.into_iter().collect())
-        } else {
-            Ok(HashSet::new())
+        else {
+            return Ok(HashMap::new());
+        };
+        if !tracked_emails.emails.is_empty() {
+            println!(
+                "Importing {} tracked emails into the ledger",
+                tracked_emails.emails.len()
+            );
+            self.ledger
+                .import_legacy(email_addr, source_kind, tracked_emails.emails)
+                .await?;
+            self.db_client
+                .set_cursors(email_addr, tracked_emails.cursors.clone())
+                .await?;
         }
+        Ok(tracked_emails.cursors)
     }
 
-    /// Persists the provided tracked email IDs.
-    async fn update_tracked_emails(
+    /// Records the outcome of processing one message. Failures are retried
+    /// with exponential backoff until `MAX_ATTEMPTS` is reached and kept as
+    /// dead letters until a later attempt succeeds.
+    async fn record_outcome(
         &self,
         email_addr: &str,
-        tracked_emails: HashSet<String>,
+        source_kind: &str,
+        msg_id: &str,
+        previous: Option<&LedgerEntry>,
+        message: &ProcessedMessage,
+        model: &str,
     ) -> Result<()> {
-        let tracked_emails_list: Vec<String> = tracked_emails.into_iter().collect();
-        self.db_client
-            .set_tracked_emails(email_addr, tracked_emails_list)
+        let now = Utc::now().timestamp_millis();
+        let id = LedgerRepo::entry_id(email_addr, source_kind, msg_id);
+        let attempts = previous.map_or(0, |e| e.attempts) + 1;
+        let (status, last_error, next_retry, receipts) = match &message.result {
+            Ok(None) => (MessageStatus::SkippedSubject, None, None, 0),
+            Ok(Some(r)) if r.is_empty() => (MessageStatus::NoTransactions, None, None, 0),
+            Ok(Some(r)) => (MessageStatus::Parsed, None, None, r.len() as u32),
+            Err(e) => {
+                let next_retry = (attempts < MAX_ATTEMPTS).then(|| {
+                    let backoff = RETRY_BASE_MS.saturating_mul(1 << (attempts - 1).min(16));
+                    now + backoff.min(RETRY_MAX_MS)
+                });
+                (
+                    MessageStatus::Failed,
+                    Some(format!("{:#}", e)),
+                    next_retry,
+                    0,
+                )
+            }
+        };
+
+        match &message.result {
+            Err(e) => {
+                self.dead_letters
+                    .upsert(DeadLetter {
+                        id: id.clone(),
+                        user_email: email_addr.to_string(),
+                        source: source_kind.to_string(),
+                        msg_id: msg_id.to_string(),
+                        issuer: message.issuer.clone(),
+                        subject: message.subject.clone(),
+                        error_chain: e.chain().map(|c| c.to_string()).collect(),
+                        raw_sha256: message.raw_sha256.clone(),
+                        attempts,
+                        exhausted: next_retry.is_none(),
+                        model: model.to_string(),
+                        failed_at: now,
+                    })
+                    .await?
+            }
+            Ok(_) if previous.is_some_and(|e| e.status == MessageStatus::Failed) => {
+                self.dead_letters.delete(&id).await?
+            }
+            Ok(_) => {}
+        }
+
+        self.ledger
+            .upsert(LedgerEntry {
+                id,
+                user_email: email_addr.to_string(),
+                source: source_kind.to_string(),
+                msg_id: msg_id.to_string(),
+                status,
+                attempts,
+                last_error,
+                next_retry,
+                receipts,
+                created_at: previous.map_or(now, |e| e.created_at),
+                updated_at: now,
+            })
             .await
-            .with_context(|| format!("Updating tracked emails for {}", email_addr))?;
-        Ok(())
     }
 
-    /// Queries Gmail using the provided search terms, fetches and parses
-    /// untracked messages, extracts receipts with Ollama, and returns all
-    /// parsed transactions. Updates the tracked email IDs afterward.
+    /// Processes one message with `llm` and records the outcome, returning
+    /// its receipts (empty when skipped).
+    async fn process_and_record(
+        &self,
+        source: &dyn MailSource,
+        email_addr: &str,
+        mail: &MailRef,
+        previous: Option<&LedgerEntry>,
+        llm: &dyn LlmClient,
+    ) -> Result<Vec<Receipt>> {
+        let message = self
+            .single_process(source, email_addr, mail, &SUBJECT_RE, llm)
+            .await;
+        if let Err(e) = &message.result {
+            tracing::warn!(error = %e, id = %mail.id, "single process failed");
+        }
+        if let Err(e) = self
+            .record_outcome(
+                email_addr,
+                source.kind(),
+                &mail.id,
+                previous,
+                &message,
+                &llm.name(),
+            )
+            .await
+        {
+            tracing::error!(error = %e, id = %mail.id, "failed to update message ledger");
+        }
+        message.result.map(Option::unwrap_or_default)
+    }
+
+    pub async fn list_dead_letters(&self, email_addr: &str) -> Result<Vec<DeadLetter>> {
+        self.dead_letters.list(email_addr, None).await
+    }
+
+    /// Messages to re-extract, as `(source kind, message id)`: either every
+    /// archived message of the user or the user's mailbox listing for `query`.
+    pub async fn list_for_reextract(
+        &self,
+        email_addr: &str,
+        source_kind: Option<&str>,
+        query: Option<MailQuery>,
+    ) -> Result<Vec<(String, String)>> {
+        match query {
+            Some(query) => {
+                let source = self.source_for(source_kind)?;
+                let listing = source.list_messages(email_addr, &query).await?;
+                Ok(listing
+                    .messages
+                    .into_iter()
+                    .map(|m| (source.kind().to_string(), m.id))
+                    .collect())
+            }
+            None => {
+                let archive = self
+                    .archive
+                    .as_ref()
+                    .context("The raw archive is not enabled")?;
+                archive.list_user(email_addr).await
+            }
+        }
+    }
+
+    /// Runs one message through extraction again, reading it from the
+    /// archive when possible. With `record` the ledger and dead letters are
+    /// updated like a regular sync; a dry run leaves them untouched.
+    pub async fn reextract_message(
+        &self,
+        email_addr: &str,
+        source_kind: &str,
+        msg_id: &str,
+        model: Option<&str>,
+        record: bool,
+    ) -> Result<Vec<Receipt>> {
+        let source = self.source_for(Some(source_kind))?;
+        let llm = self.extractor.llm_for(model)?;
+        let mail = MailRef {
+            id: msg_id.to_string(),
+        };
+        if !record {
+            return self
+                .single_process(
+                    source.as_ref(),
+                    email_addr,
+                    &mail,
+                    &SUBJECT_RE,
+                    llm.as_ref(),
+                )
+                .await
+                .result
+                .map(Option::unwrap_or_default);
+        }
+        let previous = self
+            .ledger
+            .get_many(email_addr, source_kind, std::slice::from_ref(&mail.id))
+            .await?
+            .remove(msg_id);
+        self.process_and_record(
+            source.as_ref(),
+            email_addr,
+            &mail,
+            previous.as_ref(),
+            llm.as_ref(),
+        )
+        .await
+    }
+
+    /// Runs the given dead letters (all of the user's when `ids` is `None`)
+    /// through extraction again, optionally with another model on the same
+    /// LLM backend. Returns the recovered receipts for the caller to store.
+    pub async fn reprocess_dead_letters(
+        &self,
+        email_addr: &str,
+        ids: Option<&[String]>,
+        model: Option<&str>,
+    ) -> Result<(ReceiptList, ReprocessSummary)> {
+        let llm = self.extractor.llm_for(model)?;
+        let letters = self.dead_letters.list(email_addr, ids).await?;
+        let mut summary = ReprocessSummary::default();
+        let mut receipts = ReceiptList {
+            transactions: Vec::new(),
+        };
+
+        for letter in letters {
+            let source = self.source_for(Some(&letter.source))?;
+            let previous = self
+                .ledger
+                .get_many(
+                    email_addr,
+                    &letter.source,
+                    std::slice::from_ref(&letter.msg_id),
+                )
+                .await?
+                .remove(&letter.msg_id);
+            let mail = MailRef {
+                id: letter.msg_id.clone(),
+            };
+            let extracted = self
+                .process_and_record(
+                    source.as_ref(),
+                    email_addr,
+                    &mail,
+                    previous.as_ref(),
+                    llm.as_ref(),
+                )
+                .await;
+
+            summary.processed += 1;
+            match extracted {
+                Ok(extracted) => {
+                    summary.recovered.push(letter.id);
+                    summary.receipts += extracted.len();
+                    receipts.transactions.extend(extracted);
+                }
+                Err(_) => summary.failed.push(letter.id),
+            }
+        }
+
+        Ok((receipts, summary))
+    }
+
+    /// Lists messages matching `query` from the user's mail source and
+    /// processes the ones the ledger has not seen, plus failed messages that
+    /// are due for a retry. Extracts receipts, records each message's outcome
+    /// in the ledger, and returns all parsed transactions. Updates the
+    /// source cursor afterward.
     pub async fn query_and_process_untracked(
         &self,
         email_addr: &str,
-        queries: Vec<String>,
+        source_kind: Option<&str>,
+        mut query: MailQuery,
         worker_count: usize,
     ) -> Result<ReceiptList> {
         println!("Processing...");
-        let mut tracked_emails: HashSet<String> = self.get_tracked_emails(email_addr).await?;
+        let source = self.source_for(source_kind)?;
+        let kind = source.kind();
+        let mut cursors = self.get_cursors(email_addr, kind).await?;
         let mut all_receipts: ReceiptList = ReceiptList {
             transactions: Vec::new(),
         };
 
-        // get authenticated token
-        let token = Arc::new(self.internal_authenticate(email_addr, "google").await?);
-
-        println!("Getting emails based on queries");
-        // get email ids by queries
-        let emails = self.list_all_messages(&token, &queries.join(" ")).await?;
+        println!("Getting emails from {} source", kind);
+        // get email ids by query, resuming from the last cursor
+        query.cursor = cursors.get(kind).cloned();
+        let listing = source.list_messages(email_addr, &query).await?;
+        let cursor_changed = match listing.cursor {
+            Some(cursor) => {
+                cursors.insert(kind.to_string(), cursor.clone()).as_ref() != Some(&cursor)
+            }
+            None => false,
+        };
 
-        // omit out emails that are seen (track their ids)
-        let untracked_emails: Vec<GmailMessage> = emails
+        // omit messages the ledger already has; failed ones come back through due_retries
+        let listed_ids: Vec<String> = listing.messages.iter().map(|m| m.id.clone()).collect();
+        let mut entries = self.ledger.get_many(email_addr, kind, &listed_ids).await?;
+        let mut to_process: Vec<MailRef> = listing
+            .messages
             .into_iter()
-            .filter(|m| !tracked_emails.contains(&m.id))
+            .filter(|m| !entries.contains_key(&m.id))
             .collect();
+        let now = Utc::now().timestamp_millis();
+        for entry in self.ledger.due_retries(email_addr, kind, now).await? {
+            to_process.push(MailRef {
+                id: entry.msg_id.clone(),
+            });
+            entries.insert(entry.msg_id.clone(), entry);
+        }
 
         // early exit.
-        if untracked_emails.is_empty() {
+        if to_process.is_empty() {
             println!("No new emails to process.");
+            if cursor_changed {
+                self.db_client.set_cursors(email_addr, cursors).await?;
+            }
             return Ok(all_receipts);
         }
 
-        let receipts: Vec<Receipt> = stream::iter(untracked_emails)
+        let receipts = self
+            .process_batch(&source, email_addr, to_process, &entries, worker_count)
+            .await;
+
+        all_receipts.transactions.extend(receipts);
+
+        println!("All Receipts -> {:#?}", all_receipts);
+
+        if cursor_changed {
+            self.db_client.set_cursors(email_addr, cursors).await?;
+        }
+        Ok(all_receipts)
+    }
+
+    /// Processes messages concurrently, recording each outcome in the
+    /// ledger, and returns their receipts.
+    async fn process_batch(
+        &self,
+        source: &Arc<dyn MailSource>,
+        email_addr: &str,
+        messages: Vec<MailRef>,
+        entries: &HashMap<String, LedgerEntry>,
+        worker_count: usize,
+    ) -> Vec<Receipt> {
+        stream::iter(messages)
             .map(|m| {
-                let token = token.clone();
                 let s = self.clone();
+                let source = source.clone();
                 async move {
-                    match s
-                        .single_process(email_addr, &m, token.as_str(), &SUBJECT_RE)
-                        .await
-                    {
-                        Ok(receipts) => receipts,
-                        Err(e) => {
-                            tracing::warn!(error = %e, id = %m.id, "single process failed");
-                            return vec![];
-                        }
-                    }
+                    s.process_and_record(
+                        source.as_ref(),
+                        email_addr,
+                        &m,
+                        entries.get(&m.id),
+                        s.extractor.llm(),
+                    )
+                    .await
+                    .unwrap_or_default()
                 }
             })
             .buffer_unordered(worker_count)
@@ -146,136 +515,177 @@ impl EmailService {
             // consume and flatten
             .into_iter()
             .flatten()
-            .collect::<Vec<Receipt>>();
-
-        for receipt in receipts {
-            if let Some(ref msg_id) = receipt.msg_id {
-                tracked_emails.insert(msg_id.clone());
-            }
-            all_receipts.transactions.push(receipt);
-        }
-
-        println!("All Receipts -> {:#?}", all_receipts);
+            .collect()
+    }
 
-        // update tracked emails
-        self.update_tracked_emails(email_addr, tracked_emails)
+    /// Lists one page of `query` as a full search (the sync cursor is
+    /// neither used nor moved) and processes the messages the ledger has not
+    /// seen. Pass the returned `next_page_token` to continue.
+    pub async fn import_page(
+        &self,
+        email_addr: &str,
+        source_kind: Option<&str>,
+        query: &MailQuery,
+        page_token: Option<&str>,
+        worker_count: usize,
+    ) -> Result<ImportedPage> {
+        let source = self.source_for(source_kind)?;
+        let page = source.list_page(email_addr, query, page_token).await?;
+        let listed_ids: Vec<String> = page.messages.iter().map(|m| m.id.clone()).collect();
+        let entries = self
+            .ledger
+            .get_many(email_addr, source.kind(), &listed_ids)
             .await?;
-        Ok(all_receipts)
+        let to_process: Vec<MailRef> = page
+            .messages
+            .into_iter()
+            .filter(|m| !entries.contains_key(&m.id))
+            .collect();
+        let processed = to_process.len();
+        let receipts = self
+            .process_batch(&source, email_addr, to_process, &entries, worker_count)
+            .await;
+        Ok(ImportedPage {
+            listed: listed_ids.len(),
+            processed,
+            receipts: ReceiptList {
+                transactions: receipts,
+            },
+            next_page_token: page.next_page_token,
+        })
     }
 
+    /// Fetches one message and extracts its receipts. A `None` result means
+    /// the subject did not pass `regex` and the message was not parsed.
     async fn single_process(
         &self,
+        source: &dyn MailSource,
         addr: &str,
-        email: &GmailMessage,
-        token: &str,
+        email: &MailRef,
         regex: &Regex,
-    ) -> Result<Vec<Receipt>> {
-        let mut parsed_receipts: Vec<Receipt> = Vec::new();
+        llm: &dyn LlmClient,
+    ) -> ProcessedMessage {
+        let parsed_email_content = match self.fetch_and_parse_email(source, addr, &email.id).await {
+            Ok(content) => content,
+            Err(e) => {
+                return ProcessedMessage {
+                    subject: None,
+                    issuer: None,
+                    raw_sha256: None,
+                    result: Err(e),
+                }
+            }
+        };
+        let issuer = parsed_email_content
+            .from_name
+            .as_deref()
+            .or(parsed_email_content.from_addr.as_deref())
+            .map(str::to_string);
+        let result = self
+            .extract_receipts(
+                addr,
+                email,
+                regex,
+                llm,
+                &parsed_email_content,
+                issuer.as_deref(),
+            )
+            .await;
+        ProcessedMessage {
+            raw_sha256: parsed_email_content.raw_sha256.clone(),
+            subject: parsed_email_content.subject,
+            issuer,
+            result,
+        }
+    }
 
-        let parsed_email_content = self.fetch_and_parse_email(&token, &email.id).await?;
-        if !regex.is_match(parsed_email_content.subject.as_deref().unwrap()) {
-            return Ok(vec![]);
+    async fn extract_receipts(
+        &self,
+        addr: &str,
+        email: &MailRef,
+        regex: &Regex,
+        llm: &dyn LlmClient,
+        parsed_email_content: &ParsedEmailContent,
+        issuer: Option<&str>,
+    ) -> Result<Option<Vec<Receipt>>> {
+        let mut parsed_receipts: Vec<Receipt> = Vec::new();
+        if !regex.is_match(parsed_email_content.subject.as_deref().unwrap_or_default()) {
+            return Ok(None);
         }
 
-        let issuer = parsed_email_content.from_name.as_deref().unwrap();
+        let issuer = issuer.unwrap_or_default();
         let receipts = self
-            .parse_with_ollmao(&parsed_email_content.html.unwrap())
+            .extractor
+            .extract(parsed_email_content, issuer, llm)
             .await?;
 
-        for mut receipt in receipts.transactions {
+        for mut receipt in receipts {
             receipt.msg_id = Some(email.id.to_string());
             receipt.issuer = Some(issuer.to_string());
             receipt.owner = Some(addr.to_string());
-            receipt.timestamp = Some(parsed_email_content.timestamp.clone().unwrap());
+            receipt.timestamp = receipt.timestamp.or(parsed_email_content.timestamp);
+            receipt.raw_sha256 = parsed_email_content.raw_sha256.clone();
             parsed_receipts.push(receipt);
         }
 
-        Ok(parsed_receipts)
+        Ok(Some(parsed_receipts))
     }
 
-    /// Lists all the Messages based on the given queries.
-    /// Automatically runs pagination based on the returned response
-    /// For Gmail API
-    /// Lists Gmail messages matching the query string, following pagination
-    /// until all pages are retrieved.
-    async fn list_all_messages(
-        &self,
-        token: &str,
-        combined_queries: &str,
-    ) -> Result<Vec<GmailMessage>> {
-        let mut all_messages: Vec<GmailMessage> = Vec::new();
-        let mut current_page_token: Option<String> = None;
-        println!("Combined query: {}", combined_queries);
-        // Run pagination on the query
-        loop {
-            let mut req = self
-                .client
-                .get("https://gmail.googleapis.com/gmail/v1/users/me/messages")
-                .bearer_auth(token)
-                .query(&[("q", combined_queries)]);
-
-            if let Some(tok) = &current_page_token {
-                req = req.query(&[("pageToken", tok)]);
-            }
-
-            let resp: GmailMessagesResponse = req.send().await?.error_for_status()?.json().await?;
-
-            if let Some(mut messages) = resp.messages {
-                all_messages.append(&mut messages);
-            }
-
-            if let Some(tok) = resp.next_page_token {
-                current_page_token = Some(tok);
-            } else {
-                break;
-            }
-        }
-        println!("Found {} emails to parse", all_messages.len());
-        Ok(all_messages)
-    }
-
-    /// Runs authentication based on the stored OAuth token.
-    async fn internal_authenticate(&self, user_id: &str, provider: &str) -> Result<String> {
-        println!("Getting token from store");
-        let token = self.auth_service.get_valid_token(user_id, provider).await?;
-        Ok(token.access_token)
-    }
-
-    /// Retrieves the email based on the GmailMessage ID and extracts the content
-    /// Fetches a Gmail message by ID and extracts normalized content
+    /// Retrieves the raw message from the mail source and extracts the content
     /// (subject, from, text, html).
-    async fn fetch_and_parse_email(&self, token: &str, id: &str) -> Result<ParsedEmailContent> {
-        let bytes = self.fetch_email_raw(token, id).await?;
-        let message = self.parse_message(&bytes);
-        let extracted = EmailService::extract_email_content(&message);
+    async fn fetch_and_parse_email(
+        &self,
+        source: &dyn MailSource,
+        addr: &str,
+        id: &str,
+    ) -> Result<ParsedEmailContent> {
+        let (bytes, raw_sha256) = self.fetch_raw(source, addr, id).await?;
+        let message = EmailService::parse_message(&bytes)?;
+        let mut extracted = EmailService::extract_email_content(&message);
+        extracted.raw_sha256 = raw_sha256;
         Ok(extracted)
     }
 
-    /// Downloads raw RFC822 bytes of a Gmail message and base64url-decodes them.
-    async fn fetch_email_raw(&self, token: &str, id: &str) -> Result<Vec<u8>> {
-        let url = format!(
-            "https://gmail.googleapis.com/gmail/v1/users/me/messages/{}?format=raw",
-            id
-        );
-        let raw_msg: RawGmailMessage = self
-            .client
-            .get(&url)
-            .bearer_auth(token)
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?;
-        Ok(decode_base64url(&raw_msg.raw)?)
+    /// Reads the message from the archive when it was stored before, so
+    /// re-extraction works for messages since deleted from the mailbox.
+    /// Otherwise downloads it and archives the bytes.
+    async fn fetch_raw(
+        &self,
+        source: &dyn MailSource,
+        addr: &str,
+        id: &str,
+    ) -> Result<(Vec<u8>, Option<String>)> {
+        let Some(archive) = &self.archive else {
+            return Ok((source.fetch_raw(addr, id).await?, None));
+        };
+        let ref_id = LedgerRepo::entry_id(addr, source.kind(), id);
+        if let Some((sha256, bytes)) = archive.load(&ref_id).await? {
+            return Ok((bytes, Some(sha256)));
+        }
+        let bytes = source.fetch_raw(addr, id).await?;
+        // an archive outage should not stop extraction
+        let sha256 = match archive
+            .store(&ref_id, addr, source.kind(), id, &bytes)
+            .await
+        {
+            Ok(sha256) => Some(sha256),
+            Err(e) => {
+                tracing::warn!(error = %e, id = %id, "failed to archive raw message");
+                None
+            }
+        };
+        Ok((bytes, sha256))
     }
 
     /// Parses RFC822 bytes into a `mail_parser::Message`.
-    fn parse_message<'a>(&self, bytes: &'a [u8]) -> mail_parser::Message<'a> {
-        MessageParser::default().parse(bytes).expect("parse email")
+    pub fn parse_message(bytes: &[u8]) -> Result<mail_parser::Message<'_>> {
+        MessageParser::default()
+            .parse(bytes)
+            .context("Failed to parse RFC822 message")
     }
 
     /// Extracts high-level fields into `ParsedEmailContent` for downstream use.
-    fn extract_email_content(parsed: &Message<'_>) -> ParsedEmailContent {
+    pub fn extract_email_content(parsed: &Message<'_>) -> ParsedEmailContent {
         let subject = parsed.subject().map(|s| s.to_string());
         let timestamp = parsed.date().map(|dt| dt.to_timestamp());
         let (from_name, from_addr) = parsed
@@ -290,6 +700,17 @@ impl EmailService {
             .unwrap_or((None, None));
         let text = parsed.body_text(0).map(|x| x.to_string());
         let html = parsed.body_html(0).map(|x| x.to_string());
+        let locale = parsed
+            .content_language()
+            .as_text_list()
+            .and_then(|langs| langs.first())
+            .map(|lang| lang.trim().to_string())
+            .or_else(|| html.as_deref().and_then(EmailService::html_lang));
+        let attachments = parsed
+            .attachments()
+            .enumerate()
+            .filter_map(|(idx, part)| EmailService::extract_attachment(idx, part))
+            .collect();
 
         ParsedEmailContent {
             subject,
@@ -298,13 +719,88 @@ impl EmailService {
             text,
             html,
             timestamp,
+            attachments,
+            raw_sha256: None,
+            locale,
         }
     }
 
+    /// The `lang` attribute of the `<html>` element.
+    fn html_lang(html: &str) -> Option<String> {
+        let selector = scraper::Selector::parse("html[lang]").ok()?;
+        Html::parse_document(html)
+            .select(&selector)
+            .next()?
+            .value()
+            .attr("lang")
+            .map(|lang| lang.trim().to_string())
+            .filter(|lang| !lang.is_empty())
+    }
+
+    /// Turns a MIME attachment into extraction input: PDF text (pure Rust,
+    /// via `pdf-extract`), decoded text parts, or raw image bytes.
+    fn extract_attachment(idx: usize, part: &MessagePart<'_>) -> Option<EmailAttachment> {
+        let contents = part.contents();
+        if contents.is_empty() || contents.len() > MAX_ATTACHMENT_BYTES {
+            return None;
+        }
+        let name = part
+            .attachment_name()
+            .map(|n| n.to_string())
+            .unwrap_or_else(|| format!("attachment-{}", idx));
+        let content_type = part
+            .content_type()
+            .map(|ct| match ct.subtype() {
+                Some(sub) => format!("{}/{}", ct.ctype(), sub),
+                None => ct.ctype().to_string(),
+            })
+            .unwrap_or_default()
+            .to_lowercase();
+        let is_pdf = content_type == "application/pdf" || name.to_lowercase().ends_with(".pdf");
+
+        let (text, image) = if is_pdf {
+            // pdf-extract panics on some malformed documents
+            let bytes = contents.to_vec();
+            let text = std::panic::catch_unwind(move || pdf_extract::extract_text_from_mem(&bytes))
+                .ok()
+                .and_then(|r| r.ok())
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty());
+            (text, None)
+        } else if content_type == "text/html" {
+            let text = part.text_contents().map(EmailService::html_to_text);
+            (text, None)
+        } else if content_type.starts_with("text/") {
+            (part.text_contents().map(|t| t.to_string()), None)
+        } else if content_type.starts_with("image/") {
+            // inline images are logos and tracking pixels referenced by the HTML body
+            let is_inline = part.content_id().is_some()
+                || part
+                    .content_disposition()
+                    .is_some_and(|d| d.ctype().eq_ignore_ascii_case("inline"));
+            if is_inline {
+                return None;
+            }
+            (None, Some(contents.to_vec()))
+        } else {
+            return None;
+        };
+        if text.is_none() && image.is_none() {
+            return None;
+        }
+
+        Some(EmailAttachment {
+            name,
+            content_type,
+            text,
+            image,
+        })
+    }
+
     /// Converts HTML into visible text by traversing the DOM and removing
     /// non-visible nodes and redundant whitespace/newlines.
     /// TOOD: optimise?
-    fn html_to_text(html: &str) -> String {
+    pub fn html_to_text(html: &str) -> String {
         let doc = Html::parse_document(html);
 
         // gather text by walking the DOM and skipping non-visible containers.
@@ -338,18 +834,17 @@ impl EmailService {
                             | "h4"
                             | "h5"
                             | "h6"
-                    ) {
-                        if !out.ends_with('\n') {
-                            out.push('\n');
-                        }
+                    ) && !out.ends_with('\n')
+                    {
+                        out.push('\n');
                     }
                     for c in n.children() {
                         walk(c, out);
                     }
-                    if matches!(name, "p" | "div" | "li" | "tr" | "section" | "article") {
-                        if !out.ends_with('\n') {
-                            out.push('\n');
-                        }
+                    if matches!(name, "p" | "div" | "li" | "tr" | "section" | "article")
+                        && !out.ends_with('\n')
+                    {
+                        out.push('\n');
                     }
                     return;
                 }
@@ -381,22 +876,4 @@ impl EmailService {
             .collect::<Vec<_>>()
             .join("\n")
     }
-
-    /// Uses the configured Ollama model to extract structured `ReceiptList`
-    /// from email HTML by prompting an LLM and parsing JSON output.
-    async fn parse_with_ollmao(&self, raw: &str) -> Result<ReceiptList> {
-        println!("Parsing with Ollama: {}", self.model_name);
-        let text = EmailService::html_to_text(raw);
-        let prompt = format!("Identify the transactions in this text \n {} \n and Return ONLY valid JSON for the schema: {{ 'transactions': [ {{'merchant': '...', 'amount': 0.0, 'currency': '...'}} ] }}", text);
-        let res = self
-            .ollama
-            .generate(
-                GenerationRequest::new(self.model_name.clone(), prompt)
-                    .format(ollama_rs::generation::parameters::FormatType::Json),
-            )
-            .await?;
-        // println!("Ollama response: {}", res.response);
-        let result: ReceiptList = serde_json::from_str(&res.response)?;
-        Ok(result)
-    }
 }
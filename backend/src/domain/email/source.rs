@@ -0,0 +1,49 @@
+use crate::domain::email::models::{MailListing, MailPage, MailQuery};
+use anyhow::Result;
+use async_trait::async_trait;
+
+/// A mailbox the ingestion pipeline can read receipts from.
+///
+/// Implementations only deal with transport: listing candidate messages and
+/// handing back raw RFC822 bytes. Parsing and extraction stay in `EmailService`.
+#[async_trait]
+pub trait MailSource: Send + Sync {
+    /// Short identifier used to key cursors (e.g. `gmail`, `maildir`).
+    fn kind(&self) -> &'static str;
+
+    /// Lists messages for `user` matching `query`, returning the cursor to
+    /// hand back on the next listing (if the source supports one).
+    async fn list_messages(&self, user: &str, query: &MailQuery) -> Result<MailListing>;
+
+    /// Lists one page of a full search for `query` (its `cursor` is ignored).
+    /// Sources without server-side paging return everything as a single page.
+    async fn list_page(
+        &self,
+        user: &str,
+        query: &MailQuery,
+        page_token: Option<&str>,
+    ) -> Result<MailPage> {
+        if page_token.is_some() {
+            return Ok(MailPage::default());
+        }
+        let query = MailQuery {
+            cursor: None,
+            ..query.clone()
+        };
+        let listing = self.list_messages(user, &query).await?;
+        Ok(MailPage {
+            messages: listing.messages,
+            next_page_token: None,
+        })
+    }
+
+    /// Downloads the raw RFC822 bytes of a message returned by `list_messages`.
+    async fn fetch_raw(&self, user: &str, id: &str) -> Result<Vec<u8>>;
+
+    /// Registers (or renews) push notifications for `user` on `topic`.
+    /// Returns the registration expiry in unix millis, or `None` when the
+    /// source has no push support.
+    async fn watch(&self, _user: &str, _topic: &str) -> Result<Option<i64>> {
+        Ok(None)
+    }
+}
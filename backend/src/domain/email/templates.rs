@@ -0,0 +1,390 @@
+use crate::common::money::Money;
+use crate::domain::email::models::ParsedEmailContent;
+use crate::domain::{
+    instrument::models::InstrumentType,
+    receipt::models::{PaymentDetails, Receipt, TransactionKind},
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone};
+use regex::Regex;
+use scraper::{Html, Selector};
+use serde::Deserialize;
+use std::path::Path;
+
+/// How a single receipt field is pulled out of an email.
+///
+/// `selector` picks the first matching element of the HTML body (its text,
+/// or `attr` when set); otherwise the visible text is used. `regex` then
+/// narrows that down (named group `value`, else group 1, else the whole
+/// match). `value` is a constant and ignores everything else.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FieldRule {
+    pub regex: Option<String>,
+    pub selector: Option<String>,
+    pub attr: Option<String>,
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplateFields {
+    pub merchant: Option<FieldRule>,
+    pub amount: FieldRule,
+    pub currency: Option<FieldRule>,
+    pub date: Option<FieldRule>,
+    /// Last four digits of the card or account charged.
+    pub last4: Option<FieldRule>,
+}
+
+/// One issuer format, as written in the YAML rules file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplateSpec {
+    pub name: String,
+    /// Regex matched against the sender address (case-insensitive).
+    pub issuer: Option<String>,
+    /// Regex matched against the subject (case-insensitive).
+    pub subject: Option<String>,
+    /// chrono format for `date`; RFC 3339 is tried when absent.
+    pub date_format: Option<String>,
+    /// UTC offset such as `+08:00` for dates written without one; UTC when
+    /// absent.
+    pub timezone: Option<String>,
+    /// Amounts written as `1.234,56`.
+    #[serde(default)]
+    pub decimal_comma: bool,
+    /// What the matched emails report; purchases when absent.
+    #[serde(default)]
+    pub kind: TransactionKind,
+    pub fields: TemplateFields,
+}
+
+#[derive(Debug, Deserialize)]
+struct TemplateFile {
+    templates: Vec<TemplateSpec>,
+}
+
+struct CompiledRule {
+    regex: Option<Regex>,
+    selector: Option<Selector>,
+    attr: Option<String>,
+    value: Option<String>,
+}
+
+struct CompiledTemplate {
+    spec: TemplateSpec,
+    issuer: Option<Regex>,
+    subject: Option<Regex>,
+    merchant: Option<CompiledRule>,
+    amount: CompiledRule,
+    currency: Option<CompiledRule>,
+    date: Option<CompiledRule>,
+    last4: Option<CompiledRule>,
+    offset: FixedOffset,
+}
+
+/// Declarative per-issuer extractors evaluated before the LLM.
+#[derive(Default)]
+pub struct ExtractionTemplates {
+    templates: Vec<CompiledTemplate>,
+}
+
+fn compile_rule(rule: &FieldRule) -> Result<CompiledRule> {
+    if rule.regex.is_none() && rule.selector.is_none() && rule.value.is_none() {
+        bail!("Field rule needs a regex, selector or value");
+    }
+    Ok(CompiledRule {
+        regex: rule
+            .regex
+            .as_ref()
+            .map(|p| Regex::new(p).with_context(|| format!("Invalid regex {}", p)))
+            .transpose()?,
+        selector: rule
+            .selector
+            .as_ref()
+            .map(|s| {
+                Selector::parse(s).map_err(|e| anyhow::anyhow!("Invalid selector {}: {}", s, e))
+            })
+            .transpose()?,
+        attr: rule.attr.clone(),
+        value: rule.value.clone(),
+    })
+}
+
+fn compile_optional(rule: &Option<FieldRule>) -> Result<Option<CompiledRule>> {
+    rule.as_ref().map(compile_rule).transpose()
+}
+
+fn case_insensitive(pattern: &Option<String>) -> Result<Option<Regex>> {
+    pattern
+        .as_ref()
+        .map(|p| Regex::new(&format!("(?i){}", p)).with_context(|| format!("Invalid regex {}", p)))
+        .transpose()
+}
+
+impl CompiledRule {
+    fn apply(&self, text: &str, html: Option<&Html>) -> Option<String> {
+        if let Some(value) = &self.value {
+            return Some(value.clone());
+        }
+        let source = match &self.selector {
+            Some(selector) => {
+                let el = html?.select(selector).next()?;
+                match &self.attr {
+                    Some(attr) => el.value().attr(attr)?.to_string(),
+                    None => el.text().collect::<Vec<_>>().join(" "),
+                }
+            }
+            None => text.to_string(),
+        };
+        let value = match &self.regex {
+            Some(re) => {
+                let caps = re.captures(&source)?;
+                caps.name("value")
+                    .or_else(|| caps.get(1))
+                    .or_else(|| caps.get(0))?
+                    .as_str()
+                    .to_string()
+            }
+            None => source,
+        };
+        let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!value.is_empty()).then_some(value)
+    }
+}
+
+fn parse_amount(raw: &str, decimal_comma: bool) -> Option<Money> {
+    let cleaned: String = raw
+        .chars()
+        .filter(|c| c.is_ascii_digit() || matches!(c, '.' | ',' | '-'))
+        .collect();
+    let normalized = if decimal_comma {
+        cleaned.replace('.', "").replace(',', ".")
+    } else {
+        cleaned.replace(',', "")
+    };
+    Money::parse(&normalized)
+}
+
+/// Returns unix seconds, matching the timestamps taken from email headers.
+/// Dates without an offset in the text are read at `offset`.
+fn parse_date(raw: &str, format: Option<&str>, offset: FixedOffset) -> Option<i64> {
+    let Some(fmt) = format else {
+        return DateTime::parse_from_rfc3339(raw)
+            .ok()
+            .map(|dt| dt.timestamp());
+    };
+    if let Ok(dt) = DateTime::parse_from_str(raw, fmt) {
+        return Some(dt.timestamp());
+    }
+    NaiveDateTime::parse_from_str(raw, fmt)
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(raw, fmt)
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+        .and_then(|dt| offset.from_local_datetime(&dt).single())
+        .map(|dt| dt.timestamp())
+}
+
+fn parse_offset(template: &str, raw: Option<&str>) -> Result<FixedOffset> {
+    let utc = FixedOffset::east_opt(0).expect("zero offset is valid");
+    match raw.map(str::trim) {
+        None | Some("Z") | Some("UTC") => Ok(utc),
+        Some(raw) => raw.parse().map_err(|_| {
+            anyhow::anyhow!(
+                "Template {} has timezone `{}`; expected an offset such as +08:00",
+                template,
+                raw
+            )
+        }),
+    }
+}
+
+impl ExtractionTemplates {
+    /// Loads and compiles the YAML rules file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("Reading extraction templates {}", path.display()))?;
+        ExtractionTemplates::from_yaml(&raw)
+    }
+
+    pub fn from_yaml(raw: &str) -> Result<Self> {
+        let file: TemplateFile =
+            serde_yaml::from_str(raw).context("Parsing extraction templates")?;
+        let templates = file
+            .templates
+            .into_iter()
+            .map(|spec| {
+                if spec.issuer.is_none() && spec.subject.is_none() {
+                    bail!("Template {} needs an issuer or subject pattern", spec.name);
+                }
+                Ok(CompiledTemplate {
+                    issuer: case_insensitive(&spec.issuer)?,
+                    subject: case_insensitive(&spec.subject)?,
+                    merchant: compile_optional(&spec.fields.merchant)?,
+                    amount: compile_rule(&spec.fields.amount)?,
+                    currency: compile_optional(&spec.fields.currency)?,
+                    date: compile_optional(&spec.fields.date)?,
+                    last4: compile_optional(&spec.fields.last4)?,
+                    offset: parse_offset(&spec.name, spec.timezone.as_deref())?,
+                    spec,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        println!("Loaded {} extraction templates", templates.len());
+        Ok(ExtractionTemplates { templates })
+    }
+
+    /// Finds the first template matching the email's sender and subject and
+    /// evaluates it. Returns `None` when no template matches or the matching
+    /// template could not find an amount, so the caller can use the LLM.
+    pub fn apply(&self, content: &ParsedEmailContent, text: &str) -> Option<Receipt> {
+        let from = content.from_addr.as_deref().unwrap_or_default();
+        let subject = content.subject.as_deref().unwrap_or_default();
+        let template = self.templates.iter().find(|t| {
+            t.issuer.as_ref().is_none_or(|re| re.is_match(from))
+                && t.subject.as_ref().is_none_or(|re| re.is_match(subject))
+        })?;
+
+        let html = content.html.as_deref().map(Html::parse_document);
+        let field = |rule: &Option<CompiledRule>| rule.as_ref()?.apply(text, html.as_ref());
+
+        let amount = template
+            .amount
+            .apply(text, html.as_ref())
+            .and_then(|raw| parse_amount(&raw, template.spec.decimal_comma));
+        let Some(amount) = amount else {
+            tracing::warn!(template = %template.spec.name, "template matched but found no amount");
+            return None;
+        };
+
+        Some(Receipt {
+            msg_id: None,
+            owner: None,
+            issuer: None,
+            merchant: field(&template.merchant),
+            merchant_raw: None,
+            merchant_id: None,
+            amount: Some(amount),
+            currency: field(&template.currency).map(|c| c.to_uppercase()),
+            categories: None,
+            category_source: None,
+            category_rule: None,
+            timestamp: field(&template.date).and_then(|raw| {
+                parse_date(&raw, template.spec.date_format.as_deref(), template.offset)
+            }),
+            source_attachment: None,
+            extractor: Some(format!("template:{}", template.spec.name)),
+            prompt_version: None,
+            validation: None,
+            raw_sha256: None,
+            line_items: None,
+            subtotal: None,
+            tax: None,
+            tip: None,
+            shipping: None,
+            discount: None,
+            totals_check: None,
+            kind: Some(template.spec.kind),
+            refund_of: None,
+            payment: field(&template.last4)
+                .and_then(|raw| PaymentDetails::last4_of(&raw))
+                .map(|last4| PaymentDetails {
+                    instrument_type: Some(InstrumentType::Card),
+                    last4: Some(last4),
+                    ..Default::default()
+                }),
+            instrument_id: None,
+            converted: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offset(raw: &str) -> FixedOffset {
+        parse_offset("test", Some(raw)).unwrap()
+    }
+
+    #[test]
+    fn dates_are_read_at_the_template_offset() {
+        let utc = offset("UTC");
+        let sgt = offset("+08:00");
+        let fmt = Some("%d %b %H:%M (SGT) %Y");
+        // 2024-03-05 14:30 in Singapore is 06:30 UTC
+        assert_eq!(
+            parse_date("05 Mar 14:30 (SGT) 2024", fmt, sgt),
+            Some(1_709_620_200)
+        );
+        assert_eq!(
+            parse_date("05 Mar 14:30 (SGT) 2024", fmt, utc),
+            Some(1_709_620_200 + 8 * 3600)
+        );
+        assert_eq!(
+            parse_date("2024-03-05", Some("%Y-%m-%d"), offset("-05:00")),
+            Some(1_709_614_800)
+        );
+    }
+
+    #[test]
+    fn offsets_in_the_text_win() {
+        let fmt = Some("%Y-%m-%d %H:%M %z");
+        assert_eq!(
+            parse_date("2024-03-05 14:30 +0800", fmt, offset("-05:00")),
+            Some(1_709_620_200)
+        );
+        assert_eq!(
+            parse_date("2024-03-05T14:30:00+08:00", None, offset("-05:00")),
+            Some(1_709_620_200)
+        );
+    }
+
+    #[test]
+    fn bad_dates_and_offsets() {
+        let utc = offset("Z");
+        assert_eq!(parse_date("yesterday", Some("%Y-%m-%d"), utc), None);
+        assert_eq!(parse_date("2024-02-30", Some("%Y-%m-%d"), utc), None);
+        assert_eq!(parse_date("05 Mar 2024", None, utc), None);
+        assert!(parse_offset("test", Some("SGT")).is_err());
+        assert!(parse_offset("test", Some("+25:00")).is_err());
+    }
+
+    #[test]
+    fn example_templates_apply() {
+        let raw = std::fs::read_to_string(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("extraction_templates.example.yaml"),
+        )
+        .unwrap();
+        let templates = ExtractionTemplates::from_yaml(&raw).unwrap();
+        let text = "Amount: SGD 1,234.50\nTo: COFFEE BEAN\nDate & Time: 05 Mar 14:30 (SGT) 2024\nCard ending 4242";
+        let content = ParsedEmailContent {
+            subject: Some("Card Transaction Alert".to_string()),
+            from_name: None,
+            from_addr: Some("ibanking.alert@dbs.com".to_string()),
+            text: Some(text.to_string()),
+            html: None,
+            timestamp: None,
+            attachments: vec![],
+            raw_sha256: None,
+            locale: None,
+        };
+        let receipt = templates.apply(&content, text).unwrap();
+        assert_eq!(receipt.amount, Money::parse("1234.50"));
+        assert_eq!(receipt.currency.as_deref(), Some("SGD"));
+        assert_eq!(receipt.merchant.as_deref(), Some("COFFEE BEAN"));
+        assert_eq!(receipt.timestamp, Some(1_709_620_200));
+        assert_eq!(
+            receipt.payment.and_then(|p| p.last4).as_deref(),
+            Some("4242")
+        );
+    }
+
+    #[test]
+    fn invalid_timezone_fails_to_load() {
+        let raw = "templates:\n  - name: t\n    issuer: x\n    timezone: SGT\n    fields:\n      amount:\n        value: '1'\n";
+        assert!(ExtractionTemplates::from_yaml(raw).is_err());
+    }
+}
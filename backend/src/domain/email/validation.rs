@@ -0,0 +1,668 @@
+use crate::common::money::Money;
+use crate::domain::receipt::models::{
+    CategorySource, LineItem, PaymentDetails, Receipt, TransactionKind, ValidationStatus,
+};
+use jsonschema::Validator;
+use once_cell::sync::Lazy;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+/// JSON Schema every LLM extraction response is checked against.
+pub static RECEIPT_LIST_SCHEMA: Lazy<Value> = Lazy::new(|| {
+    // amounts may be plain numbers or exact decimal strings such as "12.30"
+    let money = json!({ "type": ["number", "string"], "pattern": DECIMAL_PATTERN });
+    let optional_money =
+        json!({ "type": ["number", "string", "null"], "pattern": DECIMAL_PATTERN });
+    json!({
+        "type": "object",
+        "required": ["transactions"],
+        "properties": {
+            "transactions": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["amount"],
+                    "properties": {
+                        "merchant": { "type": ["string", "null"] },
+                        "amount": money,
+                        "currency": { "type": ["string", "null"], "pattern": "^[A-Z]{3}$" },
+                        "kind": {
+                            "enum": ["purchase", "refund", "reversal", "credit", "transfer", null]
+                        },
+                        "categories": {
+                            "type": ["array", "null"],
+                            "items": { "type": "string" }
+                        },
+                        "line_items": {
+                            "type": ["array", "null"],
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "description": { "type": ["string", "null"] },
+                                    "quantity": { "type": ["number", "null"] },
+                                    "unit_price": optional_money,
+                                    "total": optional_money
+                                }
+                            }
+                        },
+                        "payment": {
+                            "type": ["object", "null"],
+                            "properties": {
+                                "type": { "enum": ["card", "account", "wallet", null] },
+                                "last4": { "type": ["string", "null"], "pattern": "^[0-9]{4}$" },
+                                "issuer": { "type": ["string", "null"] },
+                                "nickname": { "type": ["string", "null"] }
+                            }
+                        },
+                        "subtotal": optional_money,
+                        "tax": optional_money,
+                        "tip": optional_money,
+                        "shipping": optional_money,
+                        "discount": optional_money
+                    }
+                }
+            }
+        }
+    })
+});
+
+/// What money strings look like once coerced.
+const DECIMAL_PATTERN: &str = r"^-?[0-9]+(\.[0-9]+)?$";
+
+static VALIDATOR: Lazy<Validator> =
+    Lazy::new(|| jsonschema::validator_for(&RECEIPT_LIST_SCHEMA).expect("receipt schema is valid"));
+
+/// Symbols that name a single currency. `$` and `¥` are shared by several
+/// currencies, so they are stripped from amounts but never turned into a code.
+const CURRENCY_SYMBOLS: &[(&str, &str)] = &[("€", "EUR"), ("£", "GBP"), ("₹", "INR"), ("₩", "KRW")];
+const AMBIGUOUS_SYMBOLS: &[char] = &['$', '¥'];
+
+/// Money fields besides `amount` that get the same string coercion.
+const BREAKDOWN_FIELDS: &[&str] = &["subtotal", "tax", "tip", "shipping", "discount"];
+const LINE_ITEM_MONEY_FIELDS: &[&str] = &["unit_price", "total"];
+
+/// Other words models use for a transaction kind.
+const KIND_ALIASES: &[(&str, &str)] = &[
+    ("payment", "purchase"),
+    ("debit", "purchase"),
+    ("charge", "purchase"),
+    ("return", "refund"),
+    ("chargeback", "reversal"),
+    ("void", "reversal"),
+    ("cashback", "credit"),
+];
+
+/// Result of checking one model response.
+pub struct CheckedResponse {
+    /// Every extracted receipt, with `validation` set.
+    pub receipts: Vec<Receipt>,
+    /// Schema errors left after coercion, for the repair prompt.
+    pub errors: Vec<String>,
+}
+
+/// Parses, coerces and validates an extraction response. `Err` means the
+/// response is not usable at all (not JSON, or no `transactions` list).
+pub fn check_response(text: &str) -> Result<CheckedResponse, String> {
+    let (mut value, mut repaired) = parse_lenient(text)?;
+    repaired |= coerce_envelope(&mut value);
+
+    let mut item_repaired = Vec::new();
+    if let Some(items) = value.get_mut("transactions").and_then(Value::as_array_mut) {
+        for item in items.iter_mut() {
+            item_repaired.push(coerce_receipt(item));
+        }
+    }
+
+    let mut item_errors: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    for err in VALIDATOR.iter_errors(&value) {
+        let path = err.instance_path.to_string();
+        let index = path
+            .strip_prefix("/transactions/")
+            .and_then(|rest| rest.split('/').next())
+            .and_then(|i| i.parse::<usize>().ok());
+        match index {
+            Some(i) => item_errors
+                .entry(i)
+                .or_default()
+                .push(format!("{}: {}", path, err)),
+            None => return Err(format!("/{}: {}", path.trim_start_matches('/'), err)),
+        }
+    }
+
+    let items = value["transactions"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let receipts = items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let status = if item_errors.contains_key(&i) {
+                ValidationStatus::Rejected
+            } else if repaired || item_repaired[i] {
+                ValidationStatus::Repaired
+            } else {
+                ValidationStatus::Valid
+            };
+            receipt_from_value(item, status)
+        })
+        .collect();
+
+    Ok(CheckedResponse {
+        receipts,
+        errors: item_errors.into_values().flatten().collect(),
+    })
+}
+
+/// Parses the response, falling back to the outermost `{...}` when the model
+/// wrapped the JSON in prose or a code fence. The flag reports the fallback.
+fn parse_lenient(text: &str) -> Result<(Value, bool), String> {
+    let err = match serde_json::from_str(text.trim()) {
+        Ok(value) => return Ok((value, false)),
+        Err(e) => e,
+    };
+    let inner = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => return Err(format!("response is not JSON: {}", err)),
+    };
+    serde_json::from_str(inner)
+        .map(|value| (value, true))
+        .map_err(|_| format!("response is not JSON: {}", err))
+}
+
+/// Accepts a bare array or a single receipt object in place of `{transactions: [...]}`.
+fn coerce_envelope(value: &mut Value) -> bool {
+    match value {
+        Value::Array(items) => {
+            *value = json!({ "transactions": std::mem::take(items) });
+            true
+        }
+        Value::Object(map) if !map.contains_key("transactions") && map.contains_key("amount") => {
+            let item = Value::Object(std::mem::take(map));
+            *value = json!({ "transactions": [item] });
+            true
+        }
+        Value::Object(map) if map.get("transactions") == Some(&Value::Null) => {
+            map.insert("transactions".to_string(), json!([]));
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Fixes fields whose meaning is unambiguous: money strings for `amount`
+/// (rewritten as plain decimals), lower-case or symbol currencies and blank
+/// strings. Returns whether anything changed.
+fn coerce_receipt(item: &mut Value) -> bool {
+    let Some(map) = item.as_object_mut() else {
+        return false;
+    };
+    let mut changed = false;
+
+    if let Some(Value::String(raw)) = map.get("currency") {
+        let normalized = normalize_currency(raw);
+        if normalized.as_ref() != Some(raw) {
+            map.insert(
+                "currency".to_string(),
+                normalized.map_or(Value::Null, Value::String),
+            );
+            changed = true;
+        }
+    }
+
+    if let Some(Value::String(raw)) = map.get("amount") {
+        if let Some((amount, currency)) = parse_money(raw) {
+            let amount = amount.to_string();
+            if raw != &amount {
+                map.insert("amount".to_string(), json!(amount));
+                changed = true;
+            }
+            if let Some(code) = currency {
+                if matches!(map.get("currency"), None | Some(Value::Null)) {
+                    map.insert("currency".to_string(), json!(code));
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    for field in BREAKDOWN_FIELDS {
+        changed |= coerce_money(map, field);
+    }
+
+    if let Some(Value::String(raw)) = map.get("kind") {
+        let lower = raw.trim().to_lowercase();
+        let kind = KIND_ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == lower)
+            .map_or(lower.as_str(), |(_, kind)| kind)
+            .to_string();
+        if &kind != raw {
+            map.insert("kind".to_string(), json!(kind));
+            changed = true;
+        }
+    }
+    if let Some(payment) = map.get_mut("payment").and_then(Value::as_object_mut) {
+        changed |= coerce_payment(payment);
+    }
+
+    // amounts are magnitudes; a negative one without a kind is money coming back
+    if let Some(amount) = map.get("amount").and_then(money_of) {
+        if amount.is_negative() {
+            map.insert("amount".to_string(), json!(amount.abs().to_string()));
+            if matches!(map.get("kind"), None | Some(Value::Null)) {
+                map.insert("kind".to_string(), json!("refund"));
+            }
+            changed = true;
+        }
+    }
+    if let Some(items) = map.get_mut("line_items").and_then(Value::as_array_mut) {
+        for item in items.iter_mut().filter_map(Value::as_object_mut) {
+            changed |= coerce_quantity(item);
+            for field in LINE_ITEM_MONEY_FIELDS {
+                changed |= coerce_money(item, field);
+            }
+        }
+    }
+
+    if let Some(Value::String(raw)) = map.get("merchant") {
+        let trimmed = raw.trim();
+        let merchant = (!trimmed.is_empty()).then(|| trimmed.to_string());
+        if merchant.as_ref() != Some(raw) {
+            map.insert(
+                "merchant".to_string(),
+                merchant.map_or(Value::Null, Value::String),
+            );
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+/// Reduces masked or numeric `last4` values to four digits and lower-cases `type`.
+fn coerce_payment(map: &mut Map<String, Value>) -> bool {
+    let mut changed = false;
+    let last4 = match map.get("last4") {
+        Some(Value::String(raw)) => Some(raw.clone()),
+        Some(Value::Number(n)) => Some(n.to_string()),
+        _ => None,
+    };
+    if let Some(raw) = last4 {
+        if let Some(digits) = PaymentDetails::last4_of(&raw) {
+            if map.get("last4") != Some(&json!(digits)) {
+                map.insert("last4".to_string(), json!(digits));
+                changed = true;
+            }
+        }
+    }
+    if let Some(Value::String(raw)) = map.get("type") {
+        let lower = raw.trim().to_lowercase();
+        if &lower != raw {
+            map.insert("type".to_string(), json!(lower));
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Rewrites a money string in `map[field]` as a plain decimal, dropping the
+/// symbol.
+fn coerce_money(map: &mut Map<String, Value>, field: &str) -> bool {
+    let Some(Value::String(raw)) = map.get(field) else {
+        return false;
+    };
+    let Some((amount, _)) = parse_money(raw) else {
+        return false;
+    };
+    let amount = amount.to_string();
+    if raw == &amount {
+        return false;
+    }
+    map.insert(field.to_string(), json!(amount));
+    true
+}
+
+/// Turns a numeric `quantity` string into a number.
+fn coerce_quantity(map: &mut Map<String, Value>) -> bool {
+    let Some(Value::String(raw)) = map.get("quantity") else {
+        return false;
+    };
+    let Ok(quantity) = raw.trim().parse::<f64>() else {
+        return false;
+    };
+    map.insert("quantity".to_string(), json!(quantity));
+    true
+}
+
+/// An amount written as a number or as a decimal string. Numbers go through
+/// their shortest decimal form, so `12.3` is exactly `12.3`.
+fn money_of(value: &Value) -> Option<Money> {
+    match value {
+        Value::String(raw) => Money::parse(raw),
+        Value::Number(n) => Money::parse(&n.to_string()),
+        _ => None,
+    }
+}
+
+fn normalize_currency(raw: &str) -> Option<String> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    if let Some((_, code)) = CURRENCY_SYMBOLS.iter().find(|(s, _)| *s == trimmed) {
+        return Some(code.to_string());
+    }
+    if trimmed.len() == 3 && trimmed.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Some(trimmed.to_ascii_uppercase());
+    }
+    Some(raw.to_string())
+}
+
+/// Parses strings like `$12.00`, `EUR 1.234,56` or `(4.50)`. Anything with
+/// words besides an ISO code is left alone for the schema to reject.
+fn parse_money(raw: &str) -> Option<(Money, Option<String>)> {
+    let mut rest = raw.trim().to_string();
+    let mut currency = None;
+    for (symbol, code) in CURRENCY_SYMBOLS {
+        if rest.contains(symbol) {
+            rest = rest.replace(symbol, "");
+            currency = Some(code.to_string());
+        }
+    }
+    rest.retain(|c| !AMBIGUOUS_SYMBOLS.contains(&c));
+
+    let letters: String = rest.chars().filter(|c| c.is_alphabetic()).collect();
+    if !letters.is_empty() {
+        if letters.len() != 3 || !letters.chars().all(|c| c.is_ascii_uppercase()) {
+            return None;
+        }
+        rest = rest.replace(&letters, "");
+        currency = Some(letters);
+    }
+
+    if !rest
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '.' | ',' | '-' | '+' | '(' | ')' | ' ' | '\''))
+    {
+        return None;
+    }
+    let negative = rest.contains('-') || (rest.contains('(') && rest.contains(')'));
+    let digits: String = rest
+        .chars()
+        .filter(|c| c.is_ascii_digit() || matches!(c, '.' | ','))
+        .collect();
+    let normalized = match (digits.rfind('.'), digits.rfind(',')) {
+        (Some(dot), Some(comma)) if comma > dot => digits.replace('.', "").replace(',', "."),
+        (Some(_), Some(_)) => digits.replace(',', ""),
+        (None, Some(comma)) if digits.matches(',').count() == 1 && digits.len() - comma == 3 => {
+            digits.replace(',', ".")
+        }
+        (None, Some(_)) => digits.replace(',', ""),
+        _ => digits,
+    };
+    let amount = Money::parse(&normalized)?;
+    let amount = if negative {
+        Money::new(-amount.minor(), amount.exponent())
+    } else {
+        amount
+    };
+    Some((amount, currency))
+}
+
+/// Keeps only the fields the prompt asks for, so stray or mistyped keys in
+/// a rejected item do not discard the whole receipt.
+fn receipt_from_value(item: &Value, status: ValidationStatus) -> Receipt {
+    let empty = Map::new();
+    let map = item.as_object().unwrap_or(&empty);
+    let string = |key: &str| map.get(key).and_then(Value::as_str).map(str::to_string);
+    let money = |key: &str| map.get(key).and_then(money_of);
+    let mut receipt = Receipt {
+        msg_id: None,
+        owner: None,
+        issuer: None,
+        merchant: string("merchant"),
+        merchant_raw: None,
+        merchant_id: None,
+        amount: money("amount"),
+        currency: string("currency"),
+        categories: map.get("categories").and_then(Value::as_array).map(|c| {
+            c.iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        }),
+        category_source: None,
+        category_rule: None,
+        timestamp: None,
+        source_attachment: None,
+        extractor: None,
+        prompt_version: None,
+        validation: Some(status),
+        raw_sha256: None,
+        line_items: map
+            .get("line_items")
+            .and_then(Value::as_array)
+            .map(|items| items.iter().map(line_item_from_value).collect()),
+        subtotal: money("subtotal"),
+        tax: money("tax"),
+        tip: money("tip"),
+        shipping: money("shipping"),
+        discount: money("discount"),
+        totals_check: None,
+        kind: map
+            .get("kind")
+            .and_then(|kind| serde_json::from_value::<TransactionKind>(kind.clone()).ok()),
+        refund_of: None,
+        payment: map.get("payment").and_then(payment_from_value),
+        instrument_id: None,
+        converted: None,
+    };
+    receipt.totals_check = receipt.check_totals();
+    if receipt.categories.as_ref().is_some_and(|c| !c.is_empty()) {
+        receipt.category_source = Some(CategorySource::Llm);
+    }
+    receipt
+}
+
+/// Keeps the valid parts of `payment`; `None` when nothing usable is left.
+fn payment_from_value(value: &Value) -> Option<PaymentDetails> {
+    let string = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+    let payment = PaymentDetails {
+        instrument_type: value
+            .get("type")
+            .and_then(|t| serde_json::from_value(t.clone()).ok()),
+        last4: string("last4").filter(|l| l.len() == 4 && l.chars().all(|c| c.is_ascii_digit())),
+        issuer: string("issuer"),
+        nickname: string("nickname"),
+    };
+    (payment != PaymentDetails::default()).then_some(payment)
+}
+
+fn line_item_from_value(item: &Value) -> LineItem {
+    let money = |key: &str| item.get(key).and_then(money_of);
+    LineItem {
+        description: item
+            .get("description")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        quantity: item.get("quantity").and_then(Value::as_f64),
+        unit_price: money("unit_price"),
+        total: money("total"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(raw: &str) -> Money {
+        Money::parse(raw).unwrap()
+    }
+
+    fn single(text: &str) -> Receipt {
+        let mut checked = check_response(text).unwrap();
+        assert_eq!(checked.receipts.len(), 1, "{}", text);
+        checked.receipts.remove(0)
+    }
+
+    #[test]
+    fn parse_money_strings() {
+        let cases = [
+            ("12", "12", None),
+            ("$12.00", "12.00", None),
+            ("€ 9,99", "9.99", Some("EUR")),
+            ("EUR 1.234,56", "1234.56", Some("EUR")),
+            ("1,234.56", "1234.56", None),
+            ("1,234", "1234", None),
+            ("(4.50)", "-4.50", None),
+            ("-£3", "-3", Some("GBP")),
+            ("SGD 1'000.05", "1000.05", Some("SGD")),
+            ("¥1200", "1200", None),
+            ("0.123456789", "0.123456789", None),
+        ];
+        for (raw, amount, currency) in cases {
+            let (parsed, code) = parse_money(raw).unwrap_or_else(|| panic!("{:?}", raw));
+            assert_eq!(parsed.to_string(), amount, "{:?}", raw);
+            assert_eq!(code.as_deref(), currency, "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn parse_money_rejects_words_and_junk() {
+        for raw in [
+            "",
+            "free",
+            "about 12",
+            "12 dollars",
+            "usd 12",
+            "12#50",
+            "1.2.3",
+        ] {
+            assert!(parse_money(raw).is_none(), "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn money_values_keep_their_decimals() {
+        assert_eq!(money_of(&json!("12.30")).unwrap().exponent(), 2);
+        assert_eq!(money_of(&json!(12.3)), Some(money("12.3")));
+        assert_eq!(money_of(&json!(7)), Some(money("7")));
+        assert_eq!(money_of(&json!("12,30")), None);
+        assert_eq!(money_of(&json!(true)), None);
+    }
+
+    #[test]
+    fn valid_responses_are_kept_as_is() {
+        let receipt = single(
+            r#"{"transactions":[{"merchant":"Shop","amount":12.5,"currency":"USD","tax":"1.25"}]}"#,
+        );
+        assert_eq!(receipt.validation, Some(ValidationStatus::Valid));
+        assert_eq!(receipt.amount, Some(money("12.5")));
+        assert_eq!(receipt.tax.map(|t| t.exponent()), Some(2));
+        assert_eq!(receipt.currency.as_deref(), Some("USD"));
+    }
+
+    #[test]
+    fn coercions_mark_the_receipt_repaired() {
+        let cases = [
+            (r#"{"transactions":[{"amount":"$12.00"}]}"#, "12.00", None),
+            (
+                r#"{"transactions":[{"amount":"EUR 1.234,56","currency":null}]}"#,
+                "1234.56",
+                Some("EUR"),
+            ),
+            (
+                r#"{"transactions":[{"amount":5,"currency":"sgd"}]}"#,
+                "5",
+                Some("SGD"),
+            ),
+            (
+                r#"{"transactions":[{"amount":5,"currency":"£"}]}"#,
+                "5",
+                Some("GBP"),
+            ),
+            (r#"[{"amount":3.2}]"#, "3.2", None),
+            (r#"{"amount":3.2}"#, "3.2", None),
+            (
+                r#"Here you go: {"transactions":[{"amount":1}]} "#,
+                "1",
+                None,
+            ),
+        ];
+        for (text, amount, currency) in cases {
+            let receipt = single(text);
+            assert_eq!(
+                receipt.validation,
+                Some(ValidationStatus::Repaired),
+                "{}",
+                text
+            );
+            assert_eq!(receipt.amount, Some(money(amount)), "{}", text);
+            assert_eq!(receipt.currency.as_deref(), currency, "{}", text);
+        }
+    }
+
+    #[test]
+    fn negative_amounts_become_refunds() {
+        let receipt = single(r#"{"transactions":[{"amount":"-4.50"}]}"#);
+        assert_eq!(receipt.amount, Some(money("4.50")));
+        assert_eq!(receipt.kind, Some(TransactionKind::Refund));
+
+        let receipt = single(r#"{"transactions":[{"amount":-4.5,"kind":"Chargeback"}]}"#);
+        assert_eq!(receipt.amount, Some(money("4.5")));
+        assert_eq!(receipt.kind, Some(TransactionKind::Reversal));
+    }
+
+    #[test]
+    fn line_items_and_payment_are_coerced() {
+        let receipt = single(
+            r#"{"transactions":[{"amount":"20.00","line_items":[
+                {"description":"Tea","quantity":"2","unit_price":"$5.00","total":"10.00"}
+            ],"payment":{"type":"Card","last4":"**** 4242"}}]}"#,
+        );
+        assert_eq!(receipt.validation, Some(ValidationStatus::Repaired));
+        let item = &receipt.line_items.as_ref().unwrap()[0];
+        assert_eq!(item.quantity, Some(2.0));
+        assert_eq!(item.unit_price, Some(money("5.00")));
+        assert_eq!(item.total, Some(money("10.00")));
+        let payment = receipt.payment.unwrap();
+        assert_eq!(payment.last4.as_deref(), Some("4242"));
+    }
+
+    #[test]
+    fn bad_items_are_rejected_with_errors() {
+        let cases = [
+            r#"{"transactions":[{"merchant":"Shop"}]}"#,
+            r#"{"transactions":[{"amount":"about twelve"}]}"#,
+            r#"{"transactions":[{"amount":12,"currency":"dollars"}]}"#,
+            r#"{"transactions":[{"amount":12,"kind":"gift"}]}"#,
+            r#"{"transactions":[{"amount":12,"tax":"1e3"}]}"#,
+            r#"{"transactions":[{"amount":12,"payment":{"last4":"12"}}]}"#,
+        ];
+        for text in cases {
+            let checked = check_response(text).unwrap();
+            assert_eq!(
+                checked.receipts[0].validation,
+                Some(ValidationStatus::Rejected),
+                "{}",
+                text
+            );
+            assert!(!checked.errors.is_empty(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn unusable_responses_are_errors() {
+        for text in [
+            "",
+            "no receipts here",
+            "{\"transactions\": 5}",
+            "{\"other\": 1}",
+        ] {
+            assert!(check_response(text).is_err(), "{:?}", text);
+        }
+        let empty = check_response(r#"{"transactions":null}"#).unwrap();
+        assert!(empty.receipts.is_empty());
+    }
+}
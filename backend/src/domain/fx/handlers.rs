@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{api_response::ApiResponse, app_state::AppState},
+    domain::{
+        auth::models::Claims,
+        fx::models::{is_currency_code, parse_rates},
+    },
+};
+
+#[derive(Deserialize)]
+pub struct RateQuery {
+    pub from: String,
+    /// Defaults to the caller's base currency.
+    pub to: Option<String>,
+    /// `YYYY-MM-DD`; defaults to today.
+    pub date: Option<NaiveDate>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BaseCurrency {
+    pub currency: String,
+}
+
+/// Rate between two currencies on a date, as used for receipt conversion.
+pub async fn get_rate(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RateQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    let to = match query.to {
+        Some(to) => to,
+        None => state
+            .fx_service
+            .base_currency(&claims.sub)
+            .await
+            .map_err(|e| error_response("Failed to load base currency", e))?,
+    };
+    let (from, to) = (query.from.to_uppercase(), to.to_uppercase());
+    if !is_currency_code(&from) || !is_currency_code(&to) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::error("Invalid currency code".to_string())),
+        ));
+    }
+    let date = query.date.unwrap_or_else(|| Utc::now().date_naive());
+    match state.fx_service.quote(&from, &to, date).await {
+        Ok(Some(quote)) => Ok(Json(ApiResponse::success(quote))),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error(format!(
+                "No {}/{} rate for {}",
+                from, to, date
+            ))),
+        )),
+        Err(e) => Err(error_response("Failed to look up rate", e)),
+    }
+}
+
+/// Loads rates from a CSV or ECB XML request body. Admin only.
+pub async fn import_rates(
+    State(state): State<Arc<AppState>>,
+    body: String,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    let rates = parse_rates(&body)
+        .map_err(|reason| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(reason))))?;
+    match state.fx_service.import(rates).await {
+        Ok(report) => Ok(Json(ApiResponse::success(report))),
+        Err(e) => Err(error_response("Failed to import rates", e)),
+    }
+}
+
+pub async fn get_base_currency(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state.fx_service.base_currency(&claims.sub).await {
+        Ok(currency) => Ok(Json(ApiResponse::success(BaseCurrency { currency }))),
+        Err(e) => Err(error_response("Failed to load base currency", e)),
+    }
+}
+
+/// Changes the caller's base currency and re-converts their receipts.
+pub async fn set_base_currency(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<BaseCurrency>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    let currency = request.currency.trim().to_uppercase();
+    if !is_currency_code(&currency) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::error(format!(
+                "`{}` is not a currency code",
+                request.currency
+            ))),
+        ));
+    }
+    match state
+        .fx_service
+        .set_base_currency(&claims.sub, &currency)
+        .await
+    {
+        Ok(report) => Ok(Json(ApiResponse::success(report))),
+        Err(e) => Err(error_response("Failed to set base currency", e)),
+    }
+}
+
+/// Re-converts the caller's stored receipts with the current rates.
+pub async fn convert_receipts(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state.fx_service.convert_stored(&claims.sub).await {
+        Ok(report) => Ok(Json(ApiResponse::success(report))),
+        Err(e) => Err(error_response("Failed to convert receipts", e)),
+    }
+}
+
+fn error_response(context: &str, e: anyhow::Error) -> (StatusCode, Json<ApiResponse<()>>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiResponse::error(format!("{}: {}", context, e))),
+    )
+}
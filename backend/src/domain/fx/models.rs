@@ -0,0 +1,271 @@
+use crate::common::money::Money;
+use chrono::NaiveDate;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Base currency of ECB reference rates.
+pub const ECB_BASE: &str = "EUR";
+
+/// Units of `currency` one unit of `base` bought on `date`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FxRate {
+    /// `<date>:<base>:<currency>`, so re-importing a file replaces its rates.
+    #[serde(rename = "_id")]
+    pub id: String,
+    /// `YYYY-MM-DD`; sorts like the date it names.
+    pub date: String,
+    pub base: String,
+    pub currency: String,
+    pub rate: Money,
+}
+
+impl FxRate {
+    pub fn new(date: NaiveDate, base: &str, currency: &str, rate: Money) -> Self {
+        let date = date.format("%Y-%m-%d").to_string();
+        FxRate {
+            id: format!("{}:{}:{}", date, base, currency),
+            date,
+            base: base.to_string(),
+            currency: currency.to_string(),
+            rate,
+        }
+    }
+}
+
+/// A receipt's amount in its owner's base currency, with the rate it was
+/// converted at so the figure can be reproduced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FxConversion {
+    pub currency: String,
+    pub amount: Money,
+    /// Units of `currency` per unit of the receipt's currency.
+    pub rate: Money,
+    /// Date of the rate, which may be a few days before the receipt's.
+    pub rate_date: String,
+}
+
+/// Rate between two currencies as found for a date.
+#[derive(Debug, Clone, Serialize)]
+pub struct FxQuote {
+    pub from: String,
+    pub to: String,
+    pub rate: Money,
+    pub rate_date: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub rates: usize,
+    pub first_date: Option<String>,
+    pub last_date: Option<String>,
+    /// Stored receipts re-converted with the new rates.
+    pub converted: ConvertReport,
+}
+
+/// Outcome of re-converting stored receipts.
+#[derive(Debug, Default, Serialize)]
+pub struct ConvertReport {
+    pub checked: usize,
+    pub updated: usize,
+    /// Receipts left without a conversion because no rate was found.
+    pub missing: usize,
+}
+
+pub fn is_currency_code(code: &str) -> bool {
+    code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase())
+}
+
+static ECB_TIME_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"<Cube\s+time\s*=\s*["'](\d{4}-\d{2}-\d{2})["']"#).unwrap());
+static ECB_RATE_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"<Cube\s+currency\s*=\s*["']([A-Z]{3})["']\s+rate\s*=\s*["']([0-9.]+)["']"#)
+        .unwrap()
+});
+
+/// Reads an ECB-style XML file (`<Cube time="…"><Cube currency="USD"
+/// rate="1.0956"/>…`, EUR-based) or a CSV with `date,base,currency,rate`
+/// rows. A CSV header row, blank lines and `#` comments are skipped.
+pub fn parse_rates(raw: &str) -> Result<Vec<FxRate>, String> {
+    if raw.trim_start().starts_with('<') {
+        parse_ecb_xml(raw)
+    } else {
+        parse_csv(raw)
+    }
+}
+
+fn parse_ecb_xml(raw: &str) -> Result<Vec<FxRate>, String> {
+    let mut days: Vec<(usize, NaiveDate)> = Vec::new();
+    for caps in ECB_TIME_RE.captures_iter(raw) {
+        let date = parse_date(&caps[1])?;
+        days.push((caps.get(0).map_or(0, |m| m.start()), date));
+    }
+    let mut rates = Vec::new();
+    for caps in ECB_RATE_RE.captures_iter(raw) {
+        let start = caps.get(0).map_or(0, |m| m.start());
+        let Some((_, date)) = days.iter().rev().find(|(pos, _)| *pos < start) else {
+            return Err(format!("Rate for {} outside a dated <Cube>", &caps[1]));
+        };
+        let rate = parse_rate(&caps[2])?;
+        rates.push(FxRate::new(*date, ECB_BASE, &caps[1], rate));
+    }
+    if rates.is_empty() {
+        return Err("No rates found in the XML".to_string());
+    }
+    Ok(rates)
+}
+
+fn parse_csv(raw: &str) -> Result<Vec<FxRate>, String> {
+    let mut rates = Vec::new();
+    for (idx, line) in raw.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if rates.is_empty()
+            && fields
+                .first()
+                .is_some_and(|f| f.eq_ignore_ascii_case("date"))
+        {
+            continue;
+        }
+        let [date, base, currency, rate] = fields[..] else {
+            return Err(format!(
+                "Line {}: expected date,base,currency,rate",
+                idx + 1
+            ));
+        };
+        let (base, currency) = (base.to_uppercase(), currency.to_uppercase());
+        if !is_currency_code(&base) || !is_currency_code(&currency) {
+            return Err(format!("Line {}: invalid currency code", idx + 1));
+        }
+        let date = parse_date(date).map_err(|e| format!("Line {}: {}", idx + 1, e))?;
+        let rate = parse_rate(rate).map_err(|e| format!("Line {}: {}", idx + 1, e))?;
+        rates.push(FxRate::new(date, &base, &currency, rate));
+    }
+    if rates.is_empty() {
+        return Err("No rates found in the CSV".to_string());
+    }
+    Ok(rates)
+}
+
+fn parse_date(raw: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d").map_err(|_| format!("Invalid date `{}`", raw))
+}
+
+fn parse_rate(raw: &str) -> Result<Money, String> {
+    Money::parse(raw)
+        .filter(|rate| rate.minor() > 0)
+        .ok_or_else(|| format!("Invalid rate `{}`", raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `id` and rate of each parsed row, or the error.
+    fn parsed(raw: &str) -> Result<Vec<(String, String)>, String> {
+        parse_rates(raw).map(|rates| {
+            rates
+                .into_iter()
+                .map(|r| (r.id, r.rate.to_string()))
+                .collect()
+        })
+    }
+
+    fn rows(expected: &[(&str, &str)]) -> Result<Vec<(String, String)>, String> {
+        Ok(expected
+            .iter()
+            .map(|(id, rate)| (id.to_string(), rate.to_string()))
+            .collect())
+    }
+
+    #[test]
+    fn csv_rows_are_read_with_their_line_numbers_in_errors() {
+        let cases = [
+            (
+                "date,base,currency,rate\n2024-03-01,EUR,USD,1.0812\n",
+                rows(&[("2024-03-01:EUR:USD", "1.0812")]),
+            ),
+            (
+                "# ECB rates\n\n2024-03-01, eur, sgd ,1.4523\n2024-03-04,USD,JPY,150.1",
+                rows(&[
+                    ("2024-03-01:EUR:SGD", "1.4523"),
+                    ("2024-03-04:USD:JPY", "150.1"),
+                ]),
+            ),
+            (
+                "2024-03-01,EUR,USD,1.08\ndate,base,currency,rate",
+                Err("Line 2: invalid currency code".to_string()),
+            ),
+            (
+                "2024-03-01,EUR,USD",
+                Err("Line 1: expected date,base,currency,rate".to_string()),
+            ),
+            (
+                "2024-03-01,EURO,USD,1.08",
+                Err("Line 1: invalid currency code".to_string()),
+            ),
+            (
+                "2024-02-30,EUR,USD,1.08",
+                Err("Line 1: Invalid date `2024-02-30`".to_string()),
+            ),
+            (
+                "2024-03-01,EUR,USD,0",
+                Err("Line 1: Invalid rate `0`".to_string()),
+            ),
+            (
+                "2024-03-01,EUR,USD,-1.2",
+                Err("Line 1: Invalid rate `-1.2`".to_string()),
+            ),
+            (
+                "date,base,currency,rate\n",
+                Err("No rates found in the CSV".to_string()),
+            ),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(parsed(raw), expected, "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn ecb_rates_take_the_date_of_their_cube() {
+        let cases = [
+            (
+                r#"<?xml version="1.0"?>
+                <gesmes:Envelope><Cube>
+                  <Cube time='2024-03-04'>
+                    <Cube currency='USD' rate='1.0845'/>
+                    <Cube currency='JPY' rate='162.82'/>
+                  </Cube>
+                  <Cube time="2024-03-01"><Cube currency="USD" rate="1.0832"/></Cube>
+                </Cube></gesmes:Envelope>"#,
+                rows(&[
+                    ("2024-03-04:EUR:USD", "1.0845"),
+                    ("2024-03-04:EUR:JPY", "162.82"),
+                    ("2024-03-01:EUR:USD", "1.0832"),
+                ]),
+            ),
+            (
+                r#"<Cube><Cube currency="USD" rate="1.08"/><Cube time="2024-03-01"/></Cube>"#,
+                Err("Rate for USD outside a dated <Cube>".to_string()),
+            ),
+            (
+                r#"<Cube time="2024-03-01"><Cube currency="USD" rate="0.0"/></Cube>"#,
+                Err("Invalid rate `0.0`".to_string()),
+            ),
+            (
+                r#"<Cube time="2024-13-01"><Cube currency="USD" rate="1.1"/></Cube>"#,
+                Err("Invalid date `2024-13-01`".to_string()),
+            ),
+            (
+                r#"<Cube time="2024-03-01"></Cube>"#,
+                Err("No rates found in the XML".to_string()),
+            ),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(parsed(raw), expected, "{:?}", raw);
+        }
+    }
+}
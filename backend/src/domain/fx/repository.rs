@@ -0,0 +1,60 @@
+use crate::domain::fx::models::FxRate;
+use anyhow::{Context, Result};
+use futures::TryStreamExt;
+use mongodb::{bson::doc, Client, Collection};
+
+/// Rates written per round trip when importing.
+const IMPORT_BATCH: usize = 1000;
+
+#[derive(Clone)]
+pub struct FxRateRepo {
+    collection: Collection<FxRate>,
+}
+
+impl FxRateRepo {
+    pub fn new(client: &Client, database: &str) -> Self {
+        FxRateRepo {
+            collection: client.database(database).collection("fx_rates"),
+        }
+    }
+
+    /// Inserts the rates, replacing any with the same date and pair.
+    pub async fn upsert(&self, rates: &[FxRate]) -> Result<()> {
+        for batch in rates.chunks(IMPORT_BATCH) {
+            let ids: Vec<&str> = batch.iter().map(|r| r.id.as_str()).collect();
+            self.collection
+                .delete_many(doc! {"_id": {"$in": ids}})
+                .await
+                .context("failed to clear replaced FX rates")?;
+            self.collection
+                .insert_many(batch)
+                .await
+                .context("failed to insert FX rates")?;
+        }
+        Ok(())
+    }
+
+    /// Rates quoting any of `currencies` dated from `earliest` to `latest`
+    /// (inclusive), newest first.
+    pub async fn quotes_between(
+        &self,
+        currencies: &[&str],
+        earliest: &str,
+        latest: &str,
+    ) -> Result<Vec<FxRate>> {
+        let mut result = Vec::new();
+        let mut cursor = self
+            .collection
+            .find(doc! {
+                "currency": {"$in": currencies},
+                "date": {"$gte": earliest, "$lte": latest},
+            })
+            .sort(doc! {"date": -1})
+            .await
+            .with_context(|| format!("failed to look up FX rates for {:?}", currencies))?;
+        while let Some(rate) = cursor.try_next().await? {
+            result.push(rate);
+        }
+        Ok(result)
+    }
+}
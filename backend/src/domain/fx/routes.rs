@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+
+use crate::{
+    common::app_state::AppState,
+    domain::{
+        auth::handlers::{admin_middleware, authorization_middleware},
+        fx::handlers::{
+            convert_receipts, get_base_currency, get_rate, import_rates, set_base_currency,
+        },
+    },
+};
+
+pub fn routes(state: Arc<AppState>) -> Router {
+    // rates are shared by all users, so only admins may load them
+    let admin = Router::new()
+        .route("/fx/rates/import", post(import_rates))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            admin_middleware,
+        ));
+
+    Router::new()
+        .route("/fx/rate", get(get_rate))
+        .route(
+            "/fx/base-currency",
+            get(get_base_currency).put(set_base_currency),
+        )
+        .route("/fx/convert", post(convert_receipts))
+        .merge(admin)
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            authorization_middleware,
+        ))
+        .with_state(state)
+}
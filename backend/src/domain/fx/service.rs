@@ -0,0 +1,249 @@
+use crate::{
+    common::money::{currency_exponent, Money},
+    domain::{
+        fx::{
+            models::{parse_rates, ConvertReport, FxConversion, FxQuote, FxRate, ImportReport},
+            repository::FxRateRepo,
+        },
+        receipt::{models::Receipt, repository::ReceiptRepo},
+        user::repository::UserRepo,
+    },
+};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::collections::{BTreeSet, HashMap};
+
+/// How far back a rate may be used, to cover weekends and bank holidays.
+const MAX_RATE_AGE_DAYS: i64 = 7;
+
+#[derive(Clone)]
+pub struct FxService {
+    db_client: FxRateRepo,
+    users: UserRepo,
+    receipts: ReceiptRepo,
+    default_base: String,
+}
+
+type QuoteCache = HashMap<(String, String, NaiveDate), Option<FxQuote>>;
+
+/// FxService keeps the rate table and converts receipts to their owner's
+/// base currency at the rate of the transaction date.
+impl FxService {
+    pub fn new(
+        db_client: FxRateRepo,
+        users: UserRepo,
+        receipts: ReceiptRepo,
+        default_base: String,
+    ) -> Self {
+        FxService {
+            db_client,
+            users,
+            receipts,
+            default_base,
+        }
+    }
+
+    /// Loads a CSV or ECB XML file into the rate table.
+    pub async fn import_file(&self, path: &str) -> Result<ImportReport> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read FX rates file {}", path))?;
+        let rates = parse_rates(&raw).map_err(|e| anyhow!("{}: {}", path, e))?;
+        self.import(rates).await
+    }
+
+    pub async fn import(&self, rates: Vec<FxRate>) -> Result<ImportReport> {
+        self.db_client.upsert(&rates).await?;
+        let mut report = ImportReport {
+            rates: rates.len(),
+            first_date: rates.iter().map(|r| r.date.clone()).min(),
+            last_date: rates.iter().map(|r| r.date.clone()).max(),
+            ..Default::default()
+        };
+        println!(
+            "Imported {} FX rates ({} to {})",
+            report.rates,
+            report.first_date.as_deref().unwrap_or_default(),
+            report.last_date.as_deref().unwrap_or_default()
+        );
+        // receipts stored while a rate was missing may convert now
+        report.converted = self.convert_owners(false).await?;
+        Ok(report)
+    }
+
+    /// Converts receipts stored before conversions existed. Once all have
+    /// been through it, this is a single empty query.
+    pub async fn backfill(&self) -> Result<ConvertReport> {
+        self.convert_owners(true).await
+    }
+
+    async fn convert_owners(&self, unconverted_only: bool) -> Result<ConvertReport> {
+        let mut total = ConvertReport::default();
+        for owner in self.receipts.owners(unconverted_only).await? {
+            let report = self.convert_stored(&owner).await?;
+            total.checked += report.checked;
+            total.updated += report.updated;
+            total.missing += report.missing;
+        }
+        Ok(total)
+    }
+
+    /// Units of `to` per unit of `from` on `date`, from the newest rates at
+    /// most `MAX_RATE_AGE_DAYS` old. Pairs without a direct rate go through
+    /// a base both are quoted against (EUR for ECB data).
+    pub async fn quote(&self, from: &str, to: &str, date: NaiveDate) -> Result<Option<FxQuote>> {
+        if from == to {
+            return Ok(Some(FxQuote {
+                from: from.to_string(),
+                to: to.to_string(),
+                rate: Money::new(1, 0),
+                rate_date: format_date(date),
+            }));
+        }
+        let earliest = format_date(date - Duration::days(MAX_RATE_AGE_DAYS));
+        let rates = self
+            .db_client
+            .quotes_between(&[from, to], &earliest, &format_date(date))
+            .await?;
+
+        let bases: BTreeSet<&str> = rates.iter().map(|r| r.base.as_str()).collect();
+        let mut best: Option<FxQuote> = None;
+        for base in bases {
+            // units of `currency` per unit of `base`, and the date quoted
+            let side = |currency: &str| -> Option<(Money, Option<&str>)> {
+                if currency == base {
+                    return Some((Money::new(1, 0), None));
+                }
+                rates
+                    .iter()
+                    .find(|r| r.base == base && r.currency == currency)
+                    .map(|r| (r.rate, Some(r.date.as_str())))
+            };
+            let (Some((from_rate, from_date)), Some((to_rate, to_date))) = (side(from), side(to))
+            else {
+                continue;
+            };
+            let Some(rate) = to_rate.checked_div(from_rate) else {
+                continue;
+            };
+            let rate_date = [from_date, to_date]
+                .into_iter()
+                .flatten()
+                .min()
+                .unwrap_or_default()
+                .to_string();
+            if best.as_ref().is_none_or(|b| rate_date > b.rate_date) {
+                best = Some(FxQuote {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    rate,
+                    rate_date,
+                });
+            }
+        }
+        if best.is_none() {
+            tracing::warn!(from, to, %date, "no FX rate found");
+        }
+        Ok(best)
+    }
+
+    pub async fn base_currency(&self, email: &str) -> Result<String> {
+        let user = self.users.find_user_by_email(email).await?;
+        Ok(user
+            .and_then(|u| u.base_currency)
+            .unwrap_or_else(|| self.default_base.clone()))
+    }
+
+    /// Sets the user's base currency and re-converts their receipts to it.
+    pub async fn set_base_currency(&self, email: &str, currency: &str) -> Result<ConvertReport> {
+        println!("Setting base currency of {} to {}", email, currency);
+        self.users.update_base_currency(email, currency).await?;
+        self.convert_stored(email).await
+    }
+
+    /// Fills in `converted` on receipts about to be stored.
+    pub async fn convert(&self, receipts: &mut [Receipt]) -> Result<()> {
+        let mut bases: HashMap<String, String> = HashMap::new();
+        let mut quotes = QuoteCache::new();
+        for receipt in receipts.iter_mut() {
+            let Some(owner) = receipt.owner.clone() else {
+                continue;
+            };
+            if !bases.contains_key(&owner) {
+                let base = self.base_currency(&owner).await?;
+                bases.insert(owner.clone(), base);
+            }
+            receipt.converted = self
+                .conversion(receipt, &bases[&owner], &mut quotes)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Re-converts the user's stored receipts, e.g. after new rates were
+    /// imported or the base currency changed.
+    pub async fn convert_stored(&self, email: &str) -> Result<ConvertReport> {
+        let base = self.base_currency(email).await?;
+        let mut quotes = QuoteCache::new();
+        let mut report = ConvertReport::default();
+        for (id, receipt) in self.receipts.by_email_with_ids(email).await? {
+            if receipt.amount.is_none() {
+                continue;
+            }
+            report.checked += 1;
+            let converted = self.conversion(&receipt, &base, &mut quotes).await?;
+            if converted.is_none() {
+                report.missing += 1;
+            }
+            if converted != receipt.converted {
+                self.receipts.set_conversion(id, converted.as_ref()).await?;
+                report.updated += 1;
+            }
+        }
+        self.receipts.mark_unconverted(email).await?;
+        println!(
+            "Converted receipts of {} to {}: {} of {} updated, {} without a rate",
+            email, base, report.updated, report.checked, report.missing
+        );
+        Ok(report)
+    }
+
+    /// The receipt's amount in `base` at the rate of its date (today when it
+    /// has none), rounded to the base currency's minor unit.
+    async fn conversion(
+        &self,
+        receipt: &Receipt,
+        base: &str,
+        quotes: &mut QuoteCache,
+    ) -> Result<Option<FxConversion>> {
+        let (Some(amount), Some(currency)) = (receipt.amount, receipt.currency.as_deref()) else {
+            return Ok(None);
+        };
+        let currency = currency.trim().to_uppercase();
+        let date = receipt
+            .timestamp
+            .and_then(|t| DateTime::from_timestamp(t, 0))
+            .unwrap_or_else(Utc::now)
+            .date_naive();
+        let key = (currency.clone(), base.to_string(), date);
+        if !quotes.contains_key(&key) {
+            let quote = self.quote(&currency, base, date).await?;
+            quotes.insert(key.clone(), quote);
+        }
+        let Some(quote) = &quotes[&key] else {
+            return Ok(None);
+        };
+        let converted = amount
+            .checked_mul(quote.rate)
+            .and_then(|a| a.rescale(currency_exponent(Some(base))));
+        Ok(converted.map(|amount| FxConversion {
+            currency: base.to_string(),
+            amount,
+            rate: quote.rate,
+            rate_date: quote.rate_date.clone(),
+        }))
+    }
+}
+
+fn format_date(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
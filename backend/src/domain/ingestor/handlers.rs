@@ -1,9 +1,17 @@
 use std::sync::Arc;
 
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
 use serde::Deserialize;
 
-use crate::common::{api_response::ApiResponse, app_state::AppState};
+use crate::{
+    common::{api_response::ApiResponse, app_state::AppState},
+    domain::ingestor::models::PubSubPushRequest,
+};
 
 #[derive(Deserialize)]
 pub struct SyncRequest {
@@ -28,3 +36,64 @@ pub async fn trigger_sync(
 
     Ok(Json(ApiResponse::success(())))
 }
+
+#[derive(Deserialize)]
+pub struct PushAuth {
+    token: Option<String>,
+}
+
+/// Pub/Sub push endpoint for Gmail `users.watch` notifications.
+///
+/// Authenticated with the shared secret, passed either as `?token=` or as
+/// `Authorization: Bearer <secret or HS256 JWT>`. Valid notifications are
+/// acknowledged so Pub/Sub does not redeliver them; malformed ones get a
+/// 400 and server-side failures a 500, which Pub/Sub retries.
+pub async fn gmail_push(
+    State(app): State<Arc<AppState>>,
+    Query(auth): Query<PushAuth>,
+    headers: HeaderMap,
+    Json(request): Json<PubSubPushRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    let token = auth
+        .token
+        .or_else(|| {
+            headers
+                .get(header::AUTHORIZATION)
+                .and_then(|h| h.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+                .map(str::to_string)
+        })
+        .unwrap_or_default();
+    app.ingestor_service
+        .verify_push_token(&token)
+        .map_err(|err| {
+            (
+                StatusCode::UNAUTHORIZED,
+                Json(ApiResponse::error(format!("Rejected push: {err}"))),
+            )
+        })?;
+
+    let notification = app.ingestor_service.decode_push(&request).map_err(|err| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::error(format!(
+                "Invalid push notification: {err}"
+            ))),
+        )
+    })?;
+    // a 5xx makes Pub/Sub redeliver, which is what a lookup failure needs
+    let queued = app
+        .ingestor_service
+        .handle_push(notification)
+        .await
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(format!(
+                    "Failed to queue push notification: {err}"
+                ))),
+            )
+        })?;
+
+    Ok(Json(ApiResponse::success(queued)))
+}
@@ -1,2 +1,25 @@
-// Ingestor-specific models can be added here as needed
-// For now, this file is empty but ready for future models
+use serde::Deserialize;
+
+/// Envelope Pub/Sub POSTs to push subscriptions.
+#[derive(Debug, Deserialize)]
+pub struct PubSubPushRequest {
+    pub message: PubSubMessage,
+    pub subscription: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PubSubMessage {
+    /// Base64 encoded `GmailPushNotification` JSON.
+    pub data: String,
+    #[serde(rename = "messageId")]
+    pub message_id: Option<String>,
+}
+
+/// Payload Gmail publishes when a watched mailbox changes.
+#[derive(Debug, Deserialize)]
+pub struct GmailPushNotification {
+    #[serde(rename = "emailAddress")]
+    pub email_address: String,
+    #[serde(rename = "historyId")]
+    pub history_id: u64,
+}
@@ -4,7 +4,10 @@ use axum::{middleware, routing::post, Router};
 
 use crate::{
     common::app_state::AppState,
-    domain::{auth::handlers::authorization_middleware, ingestor::handlers::trigger_sync},
+    domain::{
+        auth::handlers::authorization_middleware,
+        ingestor::handlers::{gmail_push, trigger_sync},
+    },
 };
 
 pub fn routes(state: Arc<AppState>) -> Router {
@@ -14,5 +17,7 @@ pub fn routes(state: Arc<AppState>) -> Router {
             state.clone(),
             authorization_middleware,
         ))
+        // added after the JWT layer: Pub/Sub authenticates with the push secret instead
+        .route("/webhooks/gmail", post(gmail_push))
         .with_state(state)
 }
@@ -1,15 +1,29 @@
 use std::{
+    collections::HashSet,
+    sync::Mutex,
     time::{SystemTime, UNIX_EPOCH},
     vec,
 };
 
 use crate::domain::{
-    email::service::EmailService,
+    email::{models::MailQuery, service::EmailService},
+    ingestor::models::{GmailPushNotification, PubSubPushRequest},
     receipt::{models::ReceiptList, service::ReceiptService},
     user::{models::User, service::UserService},
 };
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
 use std::sync::Arc;
+use tokio::sync::{mpsc, Notify};
+
+/// Gmail push (Pub/Sub) settings. Both are optional; without a topic no
+/// watches are registered and without a secret the webhook rejects everything.
+#[derive(Clone, Debug, Default)]
+pub struct PushConfig {
+    pub secret: Option<String>,
+    pub topic: Option<String>,
+}
 
 /// Ingestor service should be run with a cronjob
 /// to process and track emails relating to receipts
@@ -20,6 +34,32 @@ pub struct IngestorService {
     email_service: Arc<EmailService>,
     user_service: Arc<UserService>,
     issuers_email: Vec<String>,
+    push: PushConfig,
+    sync_tx: mpsc::UnboundedSender<String>,
+    sync_rx: tokio::sync::Mutex<Option<mpsc::UnboundedReceiver<String>>>,
+    pending_syncs: Mutex<HashSet<String>>,
+    /// Users being synced right now, by either the scheduled sync or the
+    /// push queue, so the two never fetch the same mailbox at once.
+    in_flight: Mutex<HashSet<String>>,
+    /// Signalled whenever a user leaves `in_flight`.
+    sync_done: Notify,
+}
+
+/// Holds a user in `in_flight` until dropped.
+struct InFlight<'a> {
+    service: &'a IngestorService,
+    email: String,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.service
+            .in_flight
+            .lock()
+            .expect("in-flight syncs poisoned")
+            .remove(&self.email);
+        self.service.sync_done.notify_waiters();
+    }
 }
 
 impl IngestorService {
@@ -28,13 +68,173 @@ impl IngestorService {
         receipt_service: Arc<ReceiptService>,
         user_service: Arc<UserService>,
         issuers_email: Vec<String>,
+        push: PushConfig,
     ) -> Self {
+        let (sync_tx, sync_rx) = mpsc::unbounded_channel();
         IngestorService {
-            receipt_service: receipt_service,
-            email_service: email_service,
-            user_service: user_service,
+            receipt_service,
+            email_service,
+            user_service,
             issuers_email,
+            push,
+            sync_tx,
+            sync_rx: tokio::sync::Mutex::new(Some(sync_rx)),
+            pending_syncs: Mutex::new(HashSet::new()),
+            in_flight: Mutex::new(HashSet::new()),
+            sync_done: Notify::new(),
+        }
+    }
+
+    /// Marks the user as being synced; `None` when a sync is already running.
+    fn claim(&self, email: &str) -> Option<InFlight<'_>> {
+        let claimed = self
+            .in_flight
+            .lock()
+            .expect("in-flight syncs poisoned")
+            .insert(email.to_string());
+        claimed.then(|| InFlight {
+            service: self,
+            email: email.to_string(),
+        })
+    }
+
+    /// Claims the user, first waiting for a running sync of theirs to finish.
+    async fn claim_when_free(&self, email: &str) -> InFlight<'_> {
+        loop {
+            // registered before the attempt, so a release in between wakes us
+            let done = self.sync_done.notified();
+            if let Some(claim) = self.claim(email) {
+                return claim;
+            }
+            done.await;
+        }
+    }
+
+    /// Checks the credential a push request came with: either the shared
+    /// secret itself (`?token=` on the subscription URL) or an HS256 JWT
+    /// signed with it.
+    pub fn verify_push_token(&self, token: &str) -> Result<()> {
+        let Some(secret) = self.push.secret.as_deref() else {
+            bail!("push notifications are not configured");
+        };
+        let matches = token.len() == secret.len()
+            && token
+                .bytes()
+                .zip(secret.bytes())
+                .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+                == 0;
+        if matches {
+            return Ok(());
         }
+        decode::<serde_json::Value>(
+            token,
+            &DecodingKey::from_secret(secret.as_bytes()),
+            &Validation::new(Algorithm::HS256),
+        )
+        .context("invalid push token")?;
+        Ok(())
+    }
+
+    /// Decodes the Gmail notification carried by a Pub/Sub push request.
+    pub fn decode_push(&self, request: &PubSubPushRequest) -> Result<GmailPushNotification> {
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(request.message.data.trim())
+            .context("push message data is not base64")?;
+        serde_json::from_slice(&data).context("push message data is not a Gmail notification")
+    }
+
+    /// Queues a sync for the notification's mailbox. Returns whether a sync
+    /// was queued (unknown or inactive users are ignored).
+    pub async fn handle_push(&self, notification: GmailPushNotification) -> Result<bool> {
+        println!(
+            "Push notification for {} at history {}",
+            notification.email_address, notification.history_id
+        );
+
+        match self
+            .user_service
+            .find_by_email(&notification.email_address)
+            .await?
+        {
+            Some(user) if user.active => Ok(self.queue_sync(user.email)),
+            _ => Ok(false),
+        }
+    }
+
+    /// Queues a targeted sync for one user. Notifications for a user that is
+    /// already waiting in the queue are coalesced.
+    pub fn queue_sync(&self, email: String) -> bool {
+        let newly_pending = self
+            .pending_syncs
+            .lock()
+            .expect("pending syncs poisoned")
+            .insert(email.clone());
+        if newly_pending {
+            let _ = self.sync_tx.send(email);
+        }
+        newly_pending
+    }
+
+    /// Drains the targeted sync queue one user at a time. Runs until the
+    /// service is dropped; only the first caller gets the queue.
+    pub async fn run_sync_queue(&self) {
+        let Some(mut rx) = self.sync_rx.lock().await.take() else {
+            return;
+        };
+        while let Some(email) = rx.recv().await {
+            // allow new notifications to queue another run while this one is in flight
+            self.pending_syncs
+                .lock()
+                .expect("pending syncs poisoned")
+                .remove(&email);
+            // the scheduled sync may have listed the mailbox before this mail
+            // arrived, so wait for it rather than skipping
+            let _claim = self.claim_when_free(&email).await;
+            let synced = async {
+                let user = self
+                    .user_service
+                    .find_by_email(&email)
+                    .await
+                    .context("Retrieving user for sync")?;
+                match user {
+                    Some(user) => self.sync_users(vec![user]).await,
+                    None => Ok(()),
+                }
+            };
+            if let Err(e) = synced.await {
+                tracing::error!(error = %e, user = %email, "queued sync failed");
+            }
+        }
+    }
+
+    /// Renews the Gmail `users.watch` registration of every active user.
+    pub async fn renew_watches(&self) -> Result<()> {
+        let Some(topic) = self.push.topic.as_deref() else {
+            return Ok(());
+        };
+        let users = self
+            .user_service
+            .get_users_by_status(true)
+            .await
+            .context("Retrieving users for watch renewal")?;
+        for user in users {
+            match self
+                .email_service
+                .watch_mailbox(&user.email, user.mail_source.as_deref(), topic)
+                .await
+            {
+                Ok(Some(expiration)) => {
+                    println!("Watch for {} renewed until {}", user.email, expiration)
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!(error = %e, user = %user.email, "watch renewal failed"),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn push_enabled(&self) -> bool {
+        self.push.topic.is_some()
     }
 
     /// Main Orchestrator of syncing the receipts into the DB.
@@ -68,6 +268,26 @@ impl IngestorService {
                 .context("Retrieving users for syncing")?,
         };
 
+        // users already being synced are left to that run
+        let mut claims = Vec::with_capacity(users.len());
+        let users: Vec<User> = users
+            .into_iter()
+            .filter(|user| match self.claim(&user.email) {
+                Some(claim) => {
+                    claims.push(claim);
+                    true
+                }
+                None => {
+                    println!("Sync for {} already running, skipping", user.email);
+                    false
+                }
+            })
+            .collect();
+        self.sync_users(users).await
+    }
+
+    /// Syncs users the caller has claimed.
+    async fn sync_users(&self, users: Vec<User>) -> Result<()> {
         if users.is_empty() {
             return Ok(());
         }
@@ -89,12 +309,13 @@ impl IngestorService {
             .as_millis() as i64;
 
         for (idx, user) in users.iter().enumerate() {
-            let queries = self.build_query(now_ms, user.last_synced);
+            let query = self.build_query(now_ms, user.last_synced);
             let email_service = email_service.clone();
             let user_task_email = user.email.clone();
+            let mail_source = user.mail_source.clone();
             let handle = tokio::spawn(async move {
                 email_service
-                    .query_and_process_untracked(&user_task_email, queries, 4)
+                    .query_and_process_untracked(&user_task_email, mail_source.as_deref(), query, 4)
                     .await
             });
             handles.push((idx, handle));
@@ -133,28 +354,15 @@ impl IngestorService {
         Ok(())
     }
 
-    pub fn get_time_query(current_time: i64, last_synced: i64) -> String {
-        let day_ms: i64 = 1000 * 60 * 60 * 24;
-        let diff_ms = (current_time - last_synced).max(0);
-        // round up
-        let diff_days = ((diff_ms + day_ms - 1) / day_ms).max(1);
-        diff_days.to_string()
-    }
-
-    pub fn build_query(&self, current_time: i64, last_synced: Option<i64>) -> Vec<String> {
-        let category = "primary";
-        let days: String = match last_synced {
-            Some(last_synced) => IngestorService::get_time_query(current_time, last_synced),
-            None => {
-                // default 1 week
-                "7".to_string()
-            }
-        };
-
-        vec![
-            format!("category:{}", category),
-            format!("from:({})", self.issuers_email.join(" OR ")),
-            format!("newer_than:{}d", days),
-        ]
+    pub fn build_query(&self, current_time: i64, last_synced: Option<i64>) -> MailQuery {
+        let week_ms: i64 = 1000 * 60 * 60 * 24 * 7;
+        MailQuery {
+            issuers: self.issuers_email.clone(),
+            category: Some("primary".to_string()),
+            // default 1 week
+            after: Some(last_synced.unwrap_or(current_time - week_ms)),
+            before: None,
+            cursor: None,
+        }
     }
 }
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    common::{api_response::ApiResponse, app_state::AppState},
+    domain::auth::models::Claims,
+};
+
+#[derive(Deserialize)]
+pub struct UpdateInstrument {
+    pub nickname: Option<String>,
+}
+
+pub async fn list_instruments(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state.instrument_service.list(&claims.sub).await {
+        Ok(instruments) => Ok(Json(ApiResponse::success(instruments))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!(
+                "Failed to list instruments: {}",
+                e
+            ))),
+        )),
+    }
+}
+
+pub async fn update_instrument(
+    Extension(claims): Extension<Claims>,
+    Path(instrument_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<UpdateInstrument>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state
+        .instrument_service
+        .rename(&claims.sub, &instrument_id, request.nickname.as_deref())
+        .await
+    {
+        Ok(true) => Ok(Json(ApiResponse::success(()))),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error("Instrument not found".to_string())),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!(
+                "Failed to update instrument: {}",
+                e
+            ))),
+        )),
+    }
+}
+
+/// Receipts paid with one of the caller's instruments.
+pub async fn instrument_receipts(
+    Extension(claims): Extension<Claims>,
+    Path(instrument_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state
+        .receipt_service
+        .get_by_instrument(&claims.sub, &instrument_id)
+        .await
+    {
+        Ok(receipts) => Ok(Json(ApiResponse::success(receipts))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!("Failed to get receipts: {}", e))),
+        )),
+    }
+}
+
+/// Net spend per instrument and currency.
+pub async fn instrument_spend(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state.receipt_service.spend_by_instrument(&claims.sub).await {
+        Ok(spend) => Ok(Json(ApiResponse::success(spend))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!(
+                "Failed to aggregate spend: {}",
+                e
+            ))),
+        )),
+    }
+}
@@ -0,0 +1,37 @@
+use crate::common::money::Money;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstrumentType {
+    Card,
+    Account,
+    Wallet,
+}
+
+/// A card, bank account or wallet the user pays with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentInstrument {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub user_email: String,
+    #[serde(rename = "type")]
+    pub instrument_type: Option<InstrumentType>,
+    pub last4: Option<String>,
+    /// Bank or wallet provider, e.g. `DBS` or `GrabPay`.
+    pub issuer: Option<String>,
+    /// Taken from the first email that mentioned it; users can rename it.
+    pub nickname: Option<String>,
+    pub created_at: i64,
+}
+
+/// Net spend of one instrument in one currency. `instrument_id` is `None`
+/// for receipts not linked to any instrument.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstrumentSpend {
+    pub instrument_id: Option<String>,
+    pub currency: Option<String>,
+    /// Purchases minus refunds and reversals; credits and transfers are left out.
+    pub total: Money,
+    pub receipts: u64,
+}
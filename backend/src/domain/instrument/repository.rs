@@ -0,0 +1,129 @@
+use crate::domain::{
+    instrument::models::{InstrumentType, PaymentInstrument},
+    receipt::models::PaymentDetails,
+};
+use anyhow::{Context, Result};
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, oid::ObjectId, to_bson, DateTime},
+    error::{Error, ErrorKind, WriteFailure},
+    options::{IndexOptions, ReturnDocument},
+    Client, Collection, IndexModel,
+};
+
+/// Mongo's error code for a unique index violation.
+const DUPLICATE_KEY: i32 = 11000;
+
+#[derive(Clone)]
+pub struct InstrumentRepo {
+    collection: Collection<PaymentInstrument>,
+}
+
+impl InstrumentRepo {
+    pub fn new(client: &Client, database: &str) -> Self {
+        InstrumentRepo {
+            collection: client.database(database).collection("payment_instruments"),
+        }
+    }
+
+    /// Creates the unique index that keeps concurrent `resolve` calls from
+    /// inserting the same instrument twice.
+    pub async fn ensure_indexes(&self) -> Result<()> {
+        let index = IndexModel::builder()
+            .keys(doc! { "user_email": 1, "type": 1, "issuer": 1, "last4": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("user_instrument".to_string())
+                    .unique(true)
+                    .build(),
+            )
+            .build();
+        self.collection
+            .create_index(index)
+            .await
+            .context("Failed to create payment instrument index")?;
+        Ok(())
+    }
+
+    pub async fn by_user(&self, user_email: &str) -> Result<Vec<PaymentInstrument>> {
+        let mut result = Vec::new();
+        let mut cursor = self
+            .collection
+            .find(doc! { "user_email": user_email })
+            .sort(doc! { "created_at": 1 })
+            .await
+            .with_context(|| format!("Failed to list instruments for {}", user_email))?;
+        while let Some(instrument) = cursor.try_next().await? {
+            result.push(instrument);
+        }
+        Ok(result)
+    }
+
+    /// Finds the user's instrument for the payment details, creating it when
+    /// a new card number or wallet shows up. Instruments are keyed by type,
+    /// issuer and last4, so two banks' cards ending alike stay apart; wallets
+    /// have no last4. Anything else returns `None`.
+    pub async fn resolve(
+        &self,
+        user_email: &str,
+        payment: &PaymentDetails,
+    ) -> Result<Option<String>> {
+        let keyed = payment.last4.is_some()
+            || (payment.issuer.is_some()
+                && payment.instrument_type == Some(InstrumentType::Wallet));
+        if !keyed {
+            return Ok(None);
+        }
+        let issuer = payment.issuer.as_deref().map(str::trim);
+        let filter = doc! {
+            "user_email": user_email,
+            "type": to_bson(&payment.instrument_type)?,
+            "issuer": issuer,
+            "last4": &payment.last4,
+        };
+        let update = doc! { "$setOnInsert": {
+            "_id": ObjectId::new().to_hex(),
+            "nickname": &payment.nickname,
+            "created_at": DateTime::now().timestamp_millis(),
+        }};
+        let upsert = || {
+            self.collection
+                .find_one_and_update(filter.clone(), update.clone())
+                .upsert(true)
+                .return_document(ReturnDocument::After)
+        };
+        let instrument = match upsert().await {
+            // a concurrent resolve inserted it first; this time it matches
+            Err(e) if is_duplicate_key(&e) => upsert().await,
+            other => other,
+        }
+        .with_context(|| format!("Failed to resolve payment instrument for {}", user_email))?;
+        Ok(instrument.map(|i| i.id))
+    }
+
+    /// Returns whether the user owns an instrument with that id.
+    pub async fn set_nickname(
+        &self,
+        user_email: &str,
+        id: &str,
+        nickname: Option<&str>,
+    ) -> Result<bool> {
+        let result = self
+            .collection
+            .update_one(
+                doc! { "_id": id, "user_email": user_email },
+                doc! { "$set": { "nickname": nickname } },
+            )
+            .await
+            .with_context(|| format!("Failed to rename instrument {}", id))?;
+        Ok(result.matched_count > 0)
+    }
+}
+
+fn is_duplicate_key(e: &Error) -> bool {
+    match e.kind.as_ref() {
+        ErrorKind::Command(err) => err.code == DUPLICATE_KEY,
+        ErrorKind::Write(WriteFailure::WriteError(err)) => err.code == DUPLICATE_KEY,
+        _ => false,
+    }
+}
@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use axum::{
+    middleware,
+    routing::{get, put},
+    Router,
+};
+
+use crate::{
+    common::app_state::AppState,
+    domain::{
+        auth::handlers::authorization_middleware,
+        instrument::handlers::{
+            instrument_receipts, instrument_spend, list_instruments, update_instrument,
+        },
+    },
+};
+
+pub fn routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/instruments", get(list_instruments))
+        .route("/instruments/spend", get(instrument_spend))
+        .route("/instruments/{instrument_id}", put(update_instrument))
+        .route(
+            "/instruments/{instrument_id}/receipts",
+            get(instrument_receipts),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            authorization_middleware,
+        ))
+        .with_state(state)
+}
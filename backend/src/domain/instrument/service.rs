@@ -0,0 +1,23 @@
+use crate::domain::instrument::{models::PaymentInstrument, repository::InstrumentRepo};
+use anyhow::Result;
+
+#[derive(Clone)]
+pub struct InstrumentService {
+    db_client: InstrumentRepo,
+}
+
+/// InstrumentService manages the cards, accounts and wallets receipts are paid with.
+impl InstrumentService {
+    pub fn new(db_client: InstrumentRepo) -> Self {
+        InstrumentService { db_client }
+    }
+
+    pub async fn list(&self, user_email: &str) -> Result<Vec<PaymentInstrument>> {
+        self.db_client.by_user(user_email).await
+    }
+
+    pub async fn rename(&self, user_email: &str, id: &str, nickname: Option<&str>) -> Result<bool> {
+        println!("Renaming instrument {} for {}", id, user_email);
+        self.db_client.set_nickname(user_email, id, nickname).await
+    }
+}
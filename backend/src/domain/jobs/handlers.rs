@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    common::{api_response::ApiResponse, app_state::AppState},
+    domain::{
+        auth::models::Claims,
+        jobs::models::{JobKind, ReextractScope},
+    },
+};
+
+#[derive(Deserialize)]
+pub struct ReextractRequest {
+    /// Unix millis; used when `archived` is not set.
+    pub after: Option<i64>,
+    pub before: Option<i64>,
+    /// Re-extract every archived message instead of a mailbox date range.
+    #[serde(default)]
+    pub archived: bool,
+    /// Defaults to a dry run.
+    pub dry_run: Option<bool>,
+    pub model: Option<String>,
+}
+
+/// Starts a re-extraction job for the caller's mail.
+pub async fn start_reextract(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ReextractRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    let scope = if request.archived {
+        ReextractScope::Archived
+    } else {
+        ReextractScope::DateRange {
+            after: request.after,
+            before: request.before,
+        }
+    };
+    let job = state
+        .job_service
+        .start(
+            &claims.sub,
+            JobKind::Reextract {
+                scope,
+                dry_run: request.dry_run.unwrap_or(true),
+                model: request.model,
+            },
+        )
+        .await
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(format!("Failed to start job: {err}"))),
+            )
+        })?;
+
+    Ok((StatusCode::ACCEPTED, Json(ApiResponse::success(job))))
+}
+
+#[derive(Deserialize)]
+pub struct ImportRequest {
+    /// Unix millis.
+    pub after: Option<i64>,
+    pub before: Option<i64>,
+}
+
+/// Starts a historical import of the caller's mailbox for a date range.
+pub async fn start_import(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ImportRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    if let (Some(after), Some(before)) = (request.after, request.before) {
+        if after >= before {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::error(
+                    "`after` must be before `before`".to_string(),
+                )),
+            ));
+        }
+    }
+    let job = state
+        .job_service
+        .start(
+            &claims.sub,
+            JobKind::Import {
+                after: request.after,
+                before: request.before,
+            },
+        )
+        .await
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(format!("Failed to start job: {err}"))),
+            )
+        })?;
+
+    Ok((StatusCode::ACCEPTED, Json(ApiResponse::success(job))))
+}
+
+pub async fn list_jobs(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state.job_service.list(&claims.sub).await {
+        Ok(jobs) => Ok(Json(ApiResponse::success(jobs))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!("Failed to list jobs: {}", e))),
+        )),
+    }
+}
+
+/// Returns one of the caller's jobs with its progress and report.
+pub async fn get_job(
+    Extension(claims): Extension<Claims>,
+    Path(job_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state.job_service.get(&claims.sub, &job_id).await {
+        Ok(Some(job)) => Ok(Json(ApiResponse::success(job))),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error(format!("Job {} not found", job_id))),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!("Failed to get job: {}", e))),
+        )),
+    }
+}
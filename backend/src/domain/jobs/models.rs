@@ -0,0 +1,148 @@
+use serde::{de::IgnoredAny, Deserialize, Deserializer, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// Which messages a re-extraction covers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "by", rename_all = "snake_case")]
+pub enum ReextractScope {
+    /// Messages from the user's mailbox received in the range (unix millis).
+    DateRange {
+        after: Option<i64>,
+        before: Option<i64>,
+    },
+    /// Every message in the user's raw archive.
+    Archived,
+}
+
+/// What a job does, with its parameters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobKind {
+    /// Re-runs extraction over already processed mail and diffs the result
+    /// against the stored receipts.
+    Reextract {
+        scope: ReextractScope,
+        /// Only report the differences, without changing receipts.
+        dry_run: bool,
+        model: Option<String>,
+    },
+    /// Backfills receipts from the user's mailbox for a date range, page by page.
+    Import {
+        after: Option<i64>,
+        before: Option<i64>,
+    },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobProgress {
+    /// Messages to go through (for imports, those listed so far).
+    pub total: u64,
+    pub done: u64,
+    /// Result pages listed from the mailbox.
+    #[serde(default)]
+    pub pages: u64,
+    /// Receipts extracted so far.
+    #[serde(default)]
+    pub receipts: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiptChange {
+    Changed,
+    Added,
+    Removed,
+    /// Extraction errored; the stored receipts are left alone.
+    Failed,
+}
+
+/// A message whose stored and re-extracted receipts differ. Only the
+/// names of the differing fields are kept, so large runs stay well within
+/// the size of one job document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageDiff {
+    pub source: String,
+    pub msg_id: String,
+    pub change: ReceiptChange,
+    /// How many receipts were stored and extracted for the message.
+    #[serde(deserialize_with = "receipt_count")]
+    pub before: usize,
+    #[serde(deserialize_with = "receipt_count")]
+    pub after: usize,
+    /// Fields that differ between the paired receipts, e.g. `amount`.
+    #[serde(default)]
+    pub fields: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Reads a count, or the length of the receipt lists older jobs stored.
+fn receipt_count<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Count {
+        Count(usize),
+        Receipts(Vec<IgnoredAny>),
+    }
+    Ok(match Count::deserialize(deserializer)? {
+        Count::Count(n) => n,
+        Count::Receipts(receipts) => receipts.len(),
+    })
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReextractReport {
+    pub messages: u64,
+    pub unchanged: u64,
+    pub changed: u64,
+    pub added: u64,
+    pub removed: u64,
+    pub failed: u64,
+    /// Whether the differences were written to the receipts.
+    pub applied: bool,
+    pub diffs: Vec<MessageDiff>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub pages: u64,
+    pub listed: u64,
+    /// Listed messages that were new to the ledger.
+    pub processed: u64,
+    pub receipts: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobReport {
+    Reextract(ReextractReport),
+    Import(ImportReport),
+}
+
+/// A background job started by a user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub user_email: String,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    #[serde(default)]
+    pub progress: JobProgress,
+    /// Where an interrupted job resumes, e.g. the mailbox page token of an import.
+    #[serde(default)]
+    pub checkpoint: Option<String>,
+    /// Set on completion; imports also save the report so far with each
+    /// checkpoint.
+    pub report: Option<JobReport>,
+    pub error: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
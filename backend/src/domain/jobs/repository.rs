@@ -0,0 +1,120 @@
+use crate::domain::jobs::models::{Job, JobProgress, JobReport, JobStatus};
+use anyhow::{Context, Result};
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, to_bson, DateTime, Document},
+    Client, Collection,
+};
+
+#[derive(Debug, Clone)]
+pub struct JobRepo {
+    collection: Collection<Job>,
+}
+
+impl JobRepo {
+    pub fn new(client: &Client, database: &str) -> Self {
+        JobRepo {
+            collection: client.database(database).collection("jobs"),
+        }
+    }
+
+    pub async fn insert(&self, job: &Job) -> Result<()> {
+        self.collection
+            .insert_one(job)
+            .await
+            .with_context(|| format!("Failed to create job {}", job.id))?;
+        Ok(())
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<Job>> {
+        self.collection
+            .find_one(doc! { "_id": id })
+            .await
+            .with_context(|| format!("Failed to get job {}", id))
+    }
+
+    async fn find_by(&self, filter: Document) -> Result<Vec<Job>> {
+        let mut result = Vec::new();
+        let mut cursor = self
+            .collection
+            .find(filter)
+            .sort(doc! { "created_at": -1 })
+            .await?;
+        while let Some(job) = cursor.try_next().await? {
+            result.push(job);
+        }
+        Ok(result)
+    }
+
+    pub async fn by_user(&self, user_email: &str) -> Result<Vec<Job>> {
+        self.find_by(doc! { "user_email": user_email })
+            .await
+            .with_context(|| format!("Failed to list jobs for {}", user_email))
+    }
+
+    /// Jobs that were queued or running when the process stopped.
+    pub async fn unfinished(&self) -> Result<Vec<Job>> {
+        self.find_by(doc! { "status": { "$in": ["queued", "running"] } })
+            .await
+            .context("Failed to list unfinished jobs")
+    }
+
+    async fn set(&self, id: &str, mut fields: Document) -> Result<()> {
+        fields.insert("updated_at", DateTime::now().timestamp_millis());
+        self.collection
+            .update_one(doc! { "_id": id }, doc! { "$set": fields })
+            .await
+            .with_context(|| format!("Failed to update job {}", id))?;
+        Ok(())
+    }
+
+    pub async fn set_status(&self, id: &str, status: JobStatus) -> Result<()> {
+        self.set(id, doc! { "status": to_bson(&status)? }).await
+    }
+
+    pub async fn set_progress(&self, id: &str, progress: &JobProgress) -> Result<()> {
+        self.set(id, doc! { "progress": to_bson(progress)? }).await
+    }
+
+    /// Saves where the job resumes, together with the progress and report
+    /// up to that point.
+    pub async fn set_checkpoint(
+        &self,
+        id: &str,
+        checkpoint: Option<&str>,
+        progress: &JobProgress,
+        report: &JobReport,
+    ) -> Result<()> {
+        self.set(
+            id,
+            doc! {
+                "checkpoint": checkpoint,
+                "progress": to_bson(progress)?,
+                "report": to_bson(report)?,
+            },
+        )
+        .await
+    }
+
+    pub async fn complete(&self, id: &str, report: &JobReport) -> Result<()> {
+        self.set(
+            id,
+            doc! {
+                "status": to_bson(&JobStatus::Completed)?,
+                "report": to_bson(report)?,
+            },
+        )
+        .await
+    }
+
+    pub async fn fail(&self, id: &str, error: &str) -> Result<()> {
+        self.set(
+            id,
+            doc! {
+                "status": to_bson(&JobStatus::Failed)?,
+                "error": error,
+            },
+        )
+        .await
+    }
+}
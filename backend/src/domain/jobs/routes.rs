@@ -0,0 +1,28 @@
+use std::sync::Arc;
+
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+
+use crate::{
+    common::app_state::AppState,
+    domain::{
+        auth::handlers::authorization_middleware,
+        jobs::handlers::{get_job, list_jobs, start_import, start_reextract},
+    },
+};
+
+pub fn routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/jobs", get(list_jobs))
+        .route("/jobs/import", post(start_import))
+        .route("/jobs/reextract", post(start_reextract))
+        .route("/jobs/{job_id}", get(get_job))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            authorization_middleware,
+        ))
+        .with_state(state)
+}
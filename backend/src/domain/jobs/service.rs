@@ -0,0 +1,409 @@
+use crate::domain::{
+    category::service::is_manual,
+    email::{models::MailQuery, service::EmailService},
+    jobs::{
+        models::{
+            ImportReport, Job, JobKind, JobProgress, JobReport, JobStatus, MessageDiff,
+            ReceiptChange, ReextractReport, ReextractScope,
+        },
+        repository::JobRepo,
+    },
+    receipt::{models::Receipt, service::ReceiptService},
+    user::service::UserService,
+};
+use anyhow::{Context, Result};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use std::{collections::BTreeSet, sync::Arc};
+
+/// Progress is written after this many messages.
+const PROGRESS_EVERY: u64 = 10;
+/// Messages of one import page processed concurrently.
+const IMPORT_WORKERS: usize = 4;
+
+#[derive(Clone)]
+pub struct JobService {
+    db_client: JobRepo,
+    email_service: Arc<EmailService>,
+    receipt_service: Arc<ReceiptService>,
+    user_service: Arc<UserService>,
+    issuers_email: Vec<String>,
+}
+
+impl JobService {
+    pub fn new(
+        db_client: JobRepo,
+        email_service: Arc<EmailService>,
+        receipt_service: Arc<ReceiptService>,
+        user_service: Arc<UserService>,
+        issuers_email: Vec<String>,
+    ) -> Self {
+        JobService {
+            db_client,
+            email_service,
+            receipt_service,
+            user_service,
+            issuers_email,
+        }
+    }
+
+    /// Records a new job and runs it in the background.
+    pub async fn start(&self, user_email: &str, kind: JobKind) -> Result<Job> {
+        let now = DateTime::now().timestamp_millis();
+        let job = Job {
+            id: ObjectId::new().to_hex(),
+            user_email: user_email.to_string(),
+            kind,
+            status: JobStatus::Queued,
+            progress: JobProgress::default(),
+            checkpoint: None,
+            report: None,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        };
+        self.db_client.insert(&job).await?;
+        self.spawn(job.clone());
+        Ok(job)
+    }
+
+    pub async fn get(&self, user_email: &str, id: &str) -> Result<Option<Job>> {
+        Ok(self
+            .db_client
+            .get(id)
+            .await?
+            .filter(|job| job.user_email == user_email))
+    }
+
+    pub async fn list(&self, user_email: &str) -> Result<Vec<Job>> {
+        self.db_client.by_user(user_email).await
+    }
+
+    /// Restarts jobs interrupted by a shutdown.
+    pub async fn resume_unfinished(&self) -> Result<()> {
+        for job in self.db_client.unfinished().await? {
+            println!("Resuming job {}", job.id);
+            self.spawn(job);
+        }
+        Ok(())
+    }
+
+    fn spawn(&self, job: Job) {
+        let svc = self.clone();
+        tokio::spawn(async move {
+            let id = job.id.clone();
+            if let Err(e) = svc.run(job).await {
+                tracing::error!(error = %e, job = %id, "job failed");
+                if let Err(e) = svc.db_client.fail(&id, &format!("{:#}", e)).await {
+                    tracing::error!(error = %e, job = %id, "failed to record job failure");
+                }
+            }
+        });
+    }
+
+    async fn run(&self, job: Job) -> Result<()> {
+        self.db_client
+            .set_status(&job.id, JobStatus::Running)
+            .await?;
+        let report = match &job.kind {
+            JobKind::Reextract {
+                scope,
+                dry_run,
+                model,
+            } => JobReport::Reextract(
+                self.reextract(&job, scope, *dry_run, model.as_deref())
+                    .await?,
+            ),
+            JobKind::Import { after, before } => {
+                JobReport::Import(self.import(&job, *after, *before).await?)
+            }
+        };
+        self.db_client.complete(&job.id, &report).await
+    }
+
+    /// Re-runs extraction for every message in scope and diffs the result
+    /// against the stored receipts. Unless `dry_run`, differing messages get
+    /// their receipts replaced; categories set on the stored receipts carry
+    /// over to their replacements.
+    async fn reextract(
+        &self,
+        job: &Job,
+        scope: &ReextractScope,
+        dry_run: bool,
+        model: Option<&str>,
+    ) -> Result<ReextractReport> {
+        let user = self
+            .user_service
+            .find_by_email(&job.user_email)
+            .await?
+            .with_context(|| format!("User {} not found", job.user_email))?;
+        let query = match scope {
+            ReextractScope::DateRange { after, before } => Some(MailQuery {
+                issuers: self.issuers_email.clone(),
+                category: None,
+                after: *after,
+                before: *before,
+                cursor: None,
+            }),
+            ReextractScope::Archived => None,
+        };
+        let messages = self
+            .email_service
+            .list_for_reextract(&job.user_email, user.mail_source.as_deref(), query)
+            .await?;
+
+        let mut progress = JobProgress {
+            total: messages.len() as u64,
+            ..Default::default()
+        };
+        self.db_client.set_progress(&job.id, &progress).await?;
+        let mut report = ReextractReport {
+            applied: !dry_run,
+            ..Default::default()
+        };
+
+        for (source, msg_id) in messages {
+            let before = self
+                .receipt_service
+                .get_for_message(&job.user_email, &msg_id)
+                .await?;
+            let extracted = self
+                .email_service
+                .reextract_message(&job.user_email, &source, &msg_id, model, !dry_run)
+                .await;
+            report.messages += 1;
+
+            match extracted {
+                Err(e) => {
+                    report.failed += 1;
+                    report.diffs.push(MessageDiff {
+                        source,
+                        msg_id,
+                        change: ReceiptChange::Failed,
+                        before: before.len(),
+                        after: 0,
+                        fields: vec![],
+                        error: Some(format!("{:#}", e)),
+                    });
+                }
+                Ok(mut after) => {
+                    carry_categories(&before, &mut after);
+                    if let Some((change, fields)) = diff(&before, &after) {
+                        match change {
+                            ReceiptChange::Changed => report.changed += 1,
+                            ReceiptChange::Added => report.added += 1,
+                            ReceiptChange::Removed => report.removed += 1,
+                            ReceiptChange::Failed => report.failed += 1,
+                        }
+                        let counts = (before.len(), after.len());
+                        if !dry_run {
+                            self.receipt_service
+                                .replace_for_message(&job.user_email, &msg_id, after)
+                                .await?;
+                        }
+                        report.diffs.push(MessageDiff {
+                            source,
+                            msg_id,
+                            change,
+                            before: counts.0,
+                            after: counts.1,
+                            fields,
+                            error: None,
+                        });
+                    } else {
+                        report.unchanged += 1;
+                    }
+                }
+            }
+
+            progress.done += 1;
+            if progress.done.is_multiple_of(PROGRESS_EVERY) || progress.done == progress.total {
+                self.db_client.set_progress(&job.id, &progress).await?;
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+impl JobService {
+    /// Imports the mailbox page by page, storing receipts after each page.
+    /// The next page token is checkpointed with the report so far, so a
+    /// restarted job continues from the page it was on with its counts
+    /// intact; the ledger skips messages already handled.
+    async fn import(
+        &self,
+        job: &Job,
+        after: Option<i64>,
+        before: Option<i64>,
+    ) -> Result<ImportReport> {
+        let user = self
+            .user_service
+            .find_by_email(&job.user_email)
+            .await?
+            .with_context(|| format!("User {} not found", job.user_email))?;
+        // same filters as the regular sync, over an explicit range
+        let query = MailQuery {
+            issuers: self.issuers_email.clone(),
+            category: Some("primary".to_string()),
+            after,
+            before,
+            cursor: None,
+        };
+        let mut progress = job.progress.clone();
+        let mut page_token = job.checkpoint.clone();
+        // a resumed job carries on from the report saved with its checkpoint
+        let mut report = match &job.report {
+            Some(JobReport::Import(report)) => report.clone(),
+            _ => ImportReport::default(),
+        };
+
+        loop {
+            let page = self
+                .email_service
+                .import_page(
+                    &job.user_email,
+                    user.mail_source.as_deref(),
+                    &query,
+                    page_token.as_deref(),
+                    IMPORT_WORKERS,
+                )
+                .await?;
+            let receipts = page.receipts.transactions.len() as u64;
+            println!(
+                "Import {}: page {} listed {} messages, {} receipts",
+                job.id,
+                progress.pages + 1,
+                page.listed,
+                receipts
+            );
+            if receipts > 0 {
+                self.receipt_service.store(page.receipts).await?;
+            }
+
+            progress.pages += 1;
+            progress.total += page.listed as u64;
+            progress.done += page.listed as u64;
+            progress.receipts += receipts;
+            report.pages += 1;
+            report.listed += page.listed as u64;
+            report.processed += page.processed as u64;
+            report.receipts += receipts;
+
+            page_token = page.next_page_token;
+            self.db_client
+                .set_checkpoint(
+                    &job.id,
+                    page_token.as_deref(),
+                    &progress,
+                    &JobReport::Import(report.clone()),
+                )
+                .await?;
+            if page_token.is_none() {
+                break;
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// Pairs each new receipt with the stored one it replaces: same amount and
+/// currency first, then the remaining ones in order.
+fn pair(before: &[Receipt], after: &[Receipt]) -> Vec<Option<usize>> {
+    let mut used = vec![false; before.len()];
+    let mut pairs: Vec<Option<usize>> = after
+        .iter()
+        .map(|a| {
+            let idx = before
+                .iter()
+                .enumerate()
+                .position(|(i, b)| !used[i] && b.amount == a.amount && b.currency == a.currency)?;
+            used[idx] = true;
+            Some(idx)
+        })
+        .collect();
+    for pair in pairs.iter_mut().filter(|p| p.is_none()) {
+        if let Some(idx) = used.iter().position(|u| !u) {
+            used[idx] = true;
+            *pair = Some(idx);
+        }
+    }
+    pairs
+}
+
+/// Keeps the user's own categories on the replacements of their receipts;
+/// the rest are left to the categorizer, which sees the new extraction.
+fn carry_categories(before: &[Receipt], after: &mut [Receipt]) {
+    for (i, pair) in pair(before, after).into_iter().enumerate() {
+        let Some(stored) = pair.map(|j| &before[j]) else {
+            continue;
+        };
+        if !is_manual(stored) {
+            continue;
+        }
+        if let Some(categories) = stored.categories.clone() {
+            after[i].categories = Some(categories);
+            after[i].category_source = stored.category_source;
+            after[i].category_rule = stored.category_rule.clone();
+        }
+    }
+}
+
+/// Extracted fields that differ between a stored receipt and its replacement.
+fn changed_fields(a: &Receipt, b: &Receipt) -> Vec<&'static str> {
+    [
+        ("merchant", extracted_merchant(a) == extracted_merchant(b)),
+        ("amount", a.amount == b.amount),
+        ("currency", a.currency == b.currency),
+        ("timestamp", a.timestamp == b.timestamp),
+        (
+            "source_attachment",
+            a.source_attachment == b.source_attachment,
+        ),
+        ("line_items", a.line_items == b.line_items),
+        ("subtotal", a.subtotal == b.subtotal),
+        ("tax", a.tax == b.tax),
+        ("tip", a.tip == b.tip),
+        ("shipping", a.shipping == b.shipping),
+        ("discount", a.discount == b.discount),
+        ("kind", a.kind == b.kind),
+        ("payment.last4", last4(a) == last4(b)),
+    ]
+    .into_iter()
+    .filter_map(|(field, same)| (!same).then_some(field))
+    .collect()
+}
+
+/// The name as extracted; stored receipts carry the canonical one in `merchant`.
+fn extracted_merchant(receipt: &Receipt) -> Option<&str> {
+    receipt
+        .merchant_raw
+        .as_deref()
+        .or(receipt.merchant.as_deref())
+}
+
+fn last4(receipt: &Receipt) -> Option<&str> {
+    receipt.payment.as_ref()?.last4.as_deref()
+}
+
+/// The kind of change, with the fields that differ between paired receipts.
+fn diff(before: &[Receipt], after: &[Receipt]) -> Option<(ReceiptChange, Vec<String>)> {
+    match (before.is_empty(), after.is_empty()) {
+        (true, true) => None,
+        (true, false) => Some((ReceiptChange::Added, vec![])),
+        (false, true) => Some((ReceiptChange::Removed, vec![])),
+        (false, false) => {
+            let mut fields = BTreeSet::new();
+            for (i, p) in pair(before, after).into_iter().enumerate() {
+                if let Some(j) = p {
+                    fields.extend(changed_fields(&before[j], &after[i]));
+                }
+            }
+            let unchanged = before.len() == after.len() && fields.is_empty();
+            (!unchanged).then(|| {
+                let fields = fields.into_iter().map(str::to_string).collect();
+                (ReceiptChange::Changed, fields)
+            })
+        }
+    }
+}
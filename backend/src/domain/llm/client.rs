@@ -0,0 +1,141 @@
+use crate::domain::llm::{mock::MockLlm, ollama::OllamaLlm, openai::OpenAiCompatibleLlm};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::sync::Arc;
+
+fn default_timeout_secs() -> u64 {
+    120
+}
+
+/// Connection and sampling settings for one LLM backend.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LlmConfig {
+    /// `ollama`, `openai`, `llamacpp` or `mock`.
+    pub provider: String,
+    pub model: String,
+    /// Base URL; defaults per provider when absent.
+    pub host: Option<String>,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    pub temperature: Option<f32>,
+    pub api_key: Option<String>,
+    /// Canned responses returned in turn by the `mock` provider.
+    #[serde(default)]
+    pub mock_responses: Vec<String>,
+}
+
+impl LlmConfig {
+    pub fn host_or_default(&self) -> String {
+        self.host.clone().unwrap_or_else(|| {
+            match self.provider.as_str() {
+                "openai" => "https://api.openai.com",
+                "llamacpp" => "http://localhost:8080",
+                _ => "http://localhost:11434",
+            }
+            .to_string()
+        })
+    }
+
+    /// Same backend, different model (e.g. the vision model or a reprocess override).
+    pub fn with_model(&self, model: &str) -> Self {
+        LlmConfig {
+            model: model.to_string(),
+            ..self.clone()
+        }
+    }
+}
+
+pub struct LlmRequest {
+    pub prompt: String,
+    /// Raw image bytes for multimodal models.
+    pub images: Vec<Vec<u8>>,
+    /// Ask the backend to constrain output to JSON.
+    pub json: bool,
+}
+
+impl LlmRequest {
+    pub fn json(prompt: String) -> Self {
+        LlmRequest {
+            prompt,
+            images: vec![],
+            json: true,
+        }
+    }
+}
+
+pub struct LlmResponse {
+    pub text: String,
+    /// `<provider>:<model>` of the client that answered.
+    pub model: String,
+}
+
+/// Text generation backend used for receipt extraction.
+#[async_trait]
+pub trait LlmClient: Send + Sync {
+    /// `<provider>:<model>`, recorded on extracted receipts.
+    fn name(&self) -> String;
+
+    async fn generate(&self, request: &LlmRequest) -> Result<LlmResponse>;
+}
+
+/// Tries each client in order and returns the first successful response.
+pub struct FallbackLlm {
+    clients: Vec<Arc<dyn LlmClient>>,
+}
+
+impl FallbackLlm {
+    pub fn new(clients: Vec<Arc<dyn LlmClient>>) -> Self {
+        FallbackLlm { clients }
+    }
+}
+
+#[async_trait]
+impl LlmClient for FallbackLlm {
+    fn name(&self) -> String {
+        self.clients
+            .iter()
+            .map(|c| c.name())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    async fn generate(&self, request: &LlmRequest) -> Result<LlmResponse> {
+        let mut last_err = None;
+        for client in &self.clients {
+            match client.generate(request).await {
+                Ok(response) => return Ok(response),
+                Err(e) => {
+                    tracing::warn!(error = %e, model = %client.name(), "llm failed, trying next");
+                    last_err = Some(e);
+                }
+            }
+        }
+        match last_err {
+            Some(e) => Err(e.context("All LLM backends failed")),
+            None => bail!("No LLM backends configured"),
+        }
+    }
+}
+
+pub fn build_llm_client(config: &LlmConfig) -> Result<Arc<dyn LlmClient>> {
+    Ok(match config.provider.as_str() {
+        "ollama" => Arc::new(OllamaLlm::new(config)?),
+        "openai" | "llamacpp" => Arc::new(OpenAiCompatibleLlm::new(config)?),
+        "mock" => Arc::new(MockLlm::new(&config.model, config.mock_responses.clone())),
+        other => bail!("Unknown LLM provider: {}", other),
+    })
+}
+
+/// Builds the primary client, wrapped with the fallbacks in order when any are given.
+pub fn build_llm_chain(primary: &LlmConfig, fallbacks: &[LlmConfig]) -> Result<Arc<dyn LlmClient>> {
+    let primary = build_llm_client(primary)?;
+    if fallbacks.is_empty() {
+        return Ok(primary);
+    }
+    let mut clients = vec![primary];
+    for config in fallbacks {
+        clients.push(build_llm_client(config)?);
+    }
+    Ok(Arc::new(FallbackLlm::new(clients)))
+}
@@ -0,0 +1,43 @@
+use crate::domain::llm::client::{LlmClient, LlmRequest, LlmResponse};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Deterministic stub that replays canned responses in order (wrapping
+/// around), for offline runs and tests. With no responses it reports no
+/// transactions.
+pub struct MockLlm {
+    model: String,
+    responses: Vec<String>,
+    next: AtomicUsize,
+}
+
+impl MockLlm {
+    pub fn new(model: &str, responses: Vec<String>) -> Self {
+        MockLlm {
+            model: model.to_string(),
+            responses,
+            next: AtomicUsize::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl LlmClient for MockLlm {
+    fn name(&self) -> String {
+        format!("mock:{}", self.model)
+    }
+
+    async fn generate(&self, _request: &LlmRequest) -> Result<LlmResponse> {
+        let text = if self.responses.is_empty() {
+            r#"{"transactions": []}"#.to_string()
+        } else {
+            let idx = self.next.fetch_add(1, Ordering::Relaxed) % self.responses.len();
+            self.responses[idx].clone()
+        };
+        Ok(LlmResponse {
+            text,
+            model: self.name(),
+        })
+    }
+}
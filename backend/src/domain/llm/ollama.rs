@@ -0,0 +1,60 @@
+use crate::domain::llm::client::{LlmClient, LlmConfig, LlmRequest, LlmResponse};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use ollama_rs::{
+    generation::{completion::request::GenerationRequest, images::Image, parameters::FormatType},
+    models::ModelOptions,
+    Ollama,
+};
+use std::time::Duration;
+
+/// Ollama `/api/generate` backend.
+pub struct OllamaLlm {
+    ollama: Ollama,
+    model: String,
+    timeout: Duration,
+    temperature: Option<f32>,
+}
+
+impl OllamaLlm {
+    pub fn new(config: &LlmConfig) -> Result<Self> {
+        let host = config.host_or_default();
+        Ok(OllamaLlm {
+            ollama: Ollama::try_new(host.as_str())
+                .with_context(|| format!("Invalid Ollama host {}", host))?,
+            model: config.model.clone(),
+            timeout: Duration::from_secs(config.timeout_secs),
+            temperature: config.temperature,
+        })
+    }
+}
+
+#[async_trait]
+impl LlmClient for OllamaLlm {
+    fn name(&self) -> String {
+        format!("ollama:{}", self.model)
+    }
+
+    async fn generate(&self, request: &LlmRequest) -> Result<LlmResponse> {
+        let mut req = GenerationRequest::new(self.model.clone(), request.prompt.as_str());
+        for image in &request.images {
+            req = req.add_image(Image::from_base64(
+                base64::engine::general_purpose::STANDARD.encode(image),
+            ));
+        }
+        if request.json {
+            req = req.format(FormatType::Json);
+        }
+        if let Some(temperature) = self.temperature {
+            req = req.options(ModelOptions::default().temperature(temperature));
+        }
+        let res = tokio::time::timeout(self.timeout, self.ollama.generate(req))
+            .await
+            .with_context(|| format!("{} timed out", self.name()))??;
+        Ok(LlmResponse {
+            text: res.response,
+            model: self.name(),
+        })
+    }
+}
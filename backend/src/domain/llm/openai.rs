@@ -0,0 +1,109 @@
+use crate::domain::llm::client::{LlmClient, LlmConfig, LlmRequest, LlmResponse};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+#[derive(Deserialize)]
+struct ChatCompletionResponse {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    content: Option<String>,
+}
+
+/// `/v1/chat/completions` backend for OpenAI-compatible servers
+/// (OpenAI, vLLM, llama.cpp `llama-server`, ...).
+pub struct OpenAiCompatibleLlm {
+    client: Client,
+    provider: String,
+    host: String,
+    model: String,
+    temperature: Option<f32>,
+    api_key: Option<String>,
+}
+
+impl OpenAiCompatibleLlm {
+    pub fn new(config: &LlmConfig) -> Result<Self> {
+        Ok(OpenAiCompatibleLlm {
+            client: Client::builder()
+                .timeout(Duration::from_secs(config.timeout_secs))
+                .build()?,
+            provider: config.provider.clone(),
+            host: config.host_or_default().trim_end_matches('/').to_string(),
+            model: config.model.clone(),
+            temperature: config.temperature,
+            api_key: config.api_key.clone(),
+        })
+    }
+
+    fn content(request: &LlmRequest) -> Value {
+        if request.images.is_empty() {
+            return Value::String(request.prompt.clone());
+        }
+        let mut parts = vec![json!({ "type": "text", "text": request.prompt })];
+        for image in &request.images {
+            let data = base64::engine::general_purpose::STANDARD.encode(image);
+            parts.push(json!({
+                "type": "image_url",
+                "image_url": { "url": format!("data:image/png;base64,{}", data) }
+            }));
+        }
+        Value::Array(parts)
+    }
+}
+
+#[async_trait]
+impl LlmClient for OpenAiCompatibleLlm {
+    fn name(&self) -> String {
+        format!("{}:{}", self.provider, self.model)
+    }
+
+    async fn generate(&self, request: &LlmRequest) -> Result<LlmResponse> {
+        let mut body = json!({
+            "model": self.model,
+            "messages": [{ "role": "user", "content": OpenAiCompatibleLlm::content(request) }],
+        });
+        if let Some(temperature) = self.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if request.json {
+            body["response_format"] = json!({ "type": "json_object" });
+        }
+
+        let mut req = self
+            .client
+            .post(format!("{}/v1/chat/completions", self.host))
+            .json(&body);
+        if let Some(key) = &self.api_key {
+            req = req.bearer_auth(key);
+        }
+        let resp: ChatCompletionResponse = req
+            .send()
+            .await
+            .with_context(|| format!("Calling {}", self.name()))?
+            .error_for_status()?
+            .json()
+            .await?;
+        let text = resp
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|c| c.message.content)
+            .context("LLM returned no choices")?;
+        Ok(LlmResponse {
+            text,
+            model: self.name(),
+        })
+    }
+}
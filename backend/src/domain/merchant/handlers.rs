@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    common::{api_response::ApiResponse, app_state::AppState},
+    domain::merchant::models::{MerchantError, MerchantSpec},
+};
+
+#[derive(Deserialize)]
+pub struct MergeRequest {
+    /// Merchant that is kept.
+    pub target: String,
+    /// Merchants folded into the target and deleted.
+    pub sources: Vec<String>,
+}
+
+pub async fn list_merchants(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state.merchant_service.list().await {
+        Ok(merchants) => Ok(Json(ApiResponse::success(merchants))),
+        Err(e) => Err(error_response("Failed to list merchants", e)),
+    }
+}
+
+pub async fn create_merchant(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<MerchantSpec>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state.merchant_service.create(request).await {
+        Ok(change) => Ok((StatusCode::CREATED, Json(ApiResponse::success(change)))),
+        Err(e) => Err(error_response("Failed to create merchant", e)),
+    }
+}
+
+pub async fn update_merchant(
+    Path(merchant_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<MerchantSpec>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state.merchant_service.update(&merchant_id, request).await {
+        Ok(change) => Ok(Json(ApiResponse::success(change))),
+        Err(e) => Err(error_response("Failed to update merchant", e)),
+    }
+}
+
+/// Folds duplicate merchants into one and relinks their receipts.
+pub async fn merge_merchants(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<MergeRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state
+        .merchant_service
+        .merge(&request.target, &request.sources)
+        .await
+    {
+        Ok(change) => Ok(Json(ApiResponse::success(change))),
+        Err(e) => Err(error_response("Failed to merge merchants", e)),
+    }
+}
+
+/// Moves some aliases and patterns, with their receipts, to a new merchant.
+pub async fn split_merchant(
+    Path(merchant_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<MerchantSpec>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state.merchant_service.split(&merchant_id, request).await {
+        Ok(change) => Ok((StatusCode::CREATED, Json(ApiResponse::success(change)))),
+        Err(e) => Err(error_response("Failed to split merchant", e)),
+    }
+}
+
+fn error_response(context: &str, e: anyhow::Error) -> (StatusCode, Json<ApiResponse<()>>) {
+    let status = match e.downcast_ref::<MerchantError>() {
+        Some(MerchantError::NotFound) => StatusCode::NOT_FOUND,
+        Some(MerchantError::Invalid(_)) => StatusCode::BAD_REQUEST,
+        None => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(ApiResponse::error(format!("{}: {}", context, e))),
+    )
+}
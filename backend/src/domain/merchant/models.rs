@@ -0,0 +1,193 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A canonical merchant that the names found in emails are normalized to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Merchant {
+    #[serde(rename = "_id")]
+    pub id: String,
+    /// Name shown for every receipt of the merchant.
+    pub name: String,
+    /// Other spellings, compared ignoring case and punctuation.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Case-insensitive regexes over the extracted name, e.g. `^AMZN Mktp`.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    /// Category given to new receipts that have none.
+    pub default_category: Option<String>,
+    /// Key of the logo asset the frontend shows for the merchant.
+    pub logo_key: Option<String>,
+    pub created_at: i64,
+}
+
+/// Fields of a merchant as sent by the client.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MerchantSpec {
+    pub name: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    pub default_category: Option<String>,
+    pub logo_key: Option<String>,
+}
+
+/// A registry change and how many receipts were rewritten because of it.
+#[derive(Debug, Serialize)]
+pub struct MerchantChange {
+    pub merchant: Merchant,
+    pub receipts: u64,
+}
+
+/// Receipts sharing a merchant link, display name and extracted name.
+#[derive(Debug, Deserialize)]
+pub struct MerchantGroup {
+    pub merchant_id: Option<String>,
+    pub merchant: Option<String>,
+    /// Name as extracted, before normalization.
+    pub raw: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum MerchantError {
+    NotFound,
+    Invalid(String),
+}
+
+impl fmt::Display for MerchantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MerchantError::NotFound => write!(f, "Merchant not found"),
+            MerchantError::Invalid(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for MerchantError {}
+
+/// Lower-cased letters and digits only, so `Amazon.com` and `AMAZON COM` agree.
+pub fn merchant_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+pub fn compile_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
+
+/// Resolves extracted names against the registry: an exact name or alias
+/// first, then the patterns, in registry order.
+pub struct MerchantMatcher {
+    entries: Vec<(Merchant, Vec<String>, Vec<Regex>)>,
+}
+
+impl MerchantMatcher {
+    /// Patterns that no longer compile are skipped.
+    pub fn new(merchants: Vec<Merchant>) -> Self {
+        let entries = merchants
+            .into_iter()
+            .map(|merchant| {
+                let keys = std::iter::once(&merchant.name)
+                    .chain(&merchant.aliases)
+                    .map(|n| merchant_key(n))
+                    .filter(|k| !k.is_empty())
+                    .collect();
+                let patterns = merchant
+                    .patterns
+                    .iter()
+                    .filter_map(|p| match compile_pattern(p) {
+                        Ok(regex) => Some(regex),
+                        Err(e) => {
+                            tracing::warn!(merchant = %merchant.name, pattern = %p, "skipping merchant pattern: {}", e);
+                            None
+                        }
+                    })
+                    .collect();
+                (merchant, keys, patterns)
+            })
+            .collect();
+        MerchantMatcher { entries }
+    }
+
+    pub fn find(&self, raw: &str) -> Option<&Merchant> {
+        let key = merchant_key(raw);
+        if key.is_empty() {
+            return None;
+        }
+        self.entries
+            .iter()
+            .find(|(_, keys, _)| keys.contains(&key))
+            .or_else(|| {
+                self.entries
+                    .iter()
+                    .find(|(_, _, patterns)| patterns.iter().any(|p| p.is_match(raw)))
+            })
+            .map(|(merchant, _, _)| merchant)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merchant(id: &str, aliases: &[&str], patterns: &[&str]) -> Merchant {
+        Merchant {
+            id: id.to_string(),
+            name: id.to_string(),
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            default_category: None,
+            logo_key: None,
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn names_and_aliases_win_over_patterns() {
+        let matcher = MerchantMatcher::new(vec![
+            merchant("Amazon", &["Amazon.com", "AMZN"], &["^amzn mktp"]),
+            // its pattern also matches `Amazon Fresh`, but the alias comes first
+            merchant("Catch-all", &[], &["amazon"]),
+            merchant("Amazon Fresh", &["Fresh by Amazon"], &[]),
+            merchant("Broken", &[], &["(unclosed"]),
+            merchant("Grab", &[], &["^grab\\b", "grabpay"]),
+        ]);
+        let cases = [
+            ("Amazon", Some("Amazon")),
+            ("AMAZON.COM", Some("Amazon")),
+            ("amzn", Some("Amazon")),
+            ("AMZN Mktp US*2K4", Some("Amazon")),
+            ("Amazon Fresh", Some("Amazon Fresh")),
+            ("fresh by amazon", Some("Amazon Fresh")),
+            ("Amazon Prime", Some("Catch-all")),
+            ("Grab Food", Some("Grab")),
+            ("Paid via GrabPay", Some("Grab")),
+            ("Grabbit", None),
+            ("(unclosed", None),
+            ("...", None),
+            ("", None),
+        ];
+        for (raw, expected) in cases {
+            let found = matcher.find(raw).map(|m| m.id.as_str());
+            assert_eq!(found, expected, "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn keys_ignore_case_and_punctuation() {
+        let cases = [
+            ("Amazon.com", "amazoncom"),
+            ("AMAZON COM", "amazoncom"),
+            ("7-Eleven #1234", "7eleven1234"),
+            ("Café Ñandú", "caféñandú"),
+            (" - ", ""),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(merchant_key(name), expected, "{:?}", name);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use crate::domain::merchant::models::Merchant;
+use anyhow::{Context, Result};
+use futures::TryStreamExt;
+use mongodb::{bson::doc, Client, Collection};
+
+#[derive(Clone)]
+pub struct MerchantRepo {
+    collection: Collection<Merchant>,
+}
+
+impl MerchantRepo {
+    pub fn new(client: &Client, database: &str) -> Self {
+        MerchantRepo {
+            collection: client.database(database).collection("merchants"),
+        }
+    }
+
+    /// Every merchant, oldest first; that order breaks ties when matching.
+    pub async fn all(&self) -> Result<Vec<Merchant>> {
+        let mut result = Vec::new();
+        let mut cursor = self
+            .collection
+            .find(doc! {})
+            .sort(doc! { "created_at": 1 })
+            .await
+            .context("Failed to list merchants")?;
+        while let Some(merchant) = cursor.try_next().await? {
+            result.push(merchant);
+        }
+        Ok(result)
+    }
+
+    pub async fn insert(&self, merchant: &Merchant) -> Result<()> {
+        self.collection
+            .insert_one(merchant)
+            .await
+            .with_context(|| format!("Failed to insert merchant {}", merchant.name))?;
+        Ok(())
+    }
+
+    pub async fn replace(&self, merchant: &Merchant) -> Result<()> {
+        self.collection
+            .replace_one(doc! { "_id": &merchant.id }, merchant)
+            .await
+            .with_context(|| format!("Failed to update merchant {}", merchant.name))?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, ids: &[String]) -> Result<()> {
+        self.collection
+            .delete_many(doc! { "_id": { "$in": ids } })
+            .await
+            .context("Failed to delete merchants")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use axum::{
+    middleware,
+    routing::{get, post, put},
+    Router,
+};
+
+use crate::{
+    common::app_state::AppState,
+    domain::{
+        auth::handlers::{admin_middleware, authorization_middleware},
+        merchant::handlers::{
+            create_merchant, list_merchants, merge_merchants, split_merchant, update_merchant,
+        },
+    },
+};
+
+pub fn routes(state: Arc<AppState>) -> Router {
+    // the registry is shared by all users, so only admins may change it
+    let admin = Router::new()
+        .route("/merchants", post(create_merchant))
+        .route("/merchants/merge", post(merge_merchants))
+        .route("/merchants/{merchant_id}", put(update_merchant))
+        .route("/merchants/{merchant_id}/split", post(split_merchant))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            admin_middleware,
+        ));
+
+    Router::new()
+        .route("/merchants", get(list_merchants))
+        .merge(admin)
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            authorization_middleware,
+        ))
+        .with_state(state)
+}
@@ -0,0 +1,220 @@
+use crate::domain::{
+    merchant::{
+        models::{
+            compile_pattern, merchant_key, Merchant, MerchantChange, MerchantError,
+            MerchantMatcher, MerchantSpec,
+        },
+        repository::MerchantRepo,
+    },
+    receipt::repository::ReceiptRepo,
+};
+use anyhow::Result;
+use mongodb::bson::{oid::ObjectId, DateTime};
+
+#[derive(Clone)]
+pub struct MerchantService {
+    db_client: MerchantRepo,
+    receipts: ReceiptRepo,
+}
+
+/// MerchantService maintains the merchant registry and keeps stored receipts
+/// in line with it. The registry is shared by all users.
+impl MerchantService {
+    pub fn new(db_client: MerchantRepo, receipts: ReceiptRepo) -> Self {
+        MerchantService {
+            db_client,
+            receipts,
+        }
+    }
+
+    pub async fn list(&self) -> Result<Vec<Merchant>> {
+        self.db_client.all().await
+    }
+
+    /// Adds a merchant and links the stored receipts it matches.
+    pub async fn create(&self, spec: MerchantSpec) -> Result<MerchantChange> {
+        let mut merchants = self.db_client.all().await?;
+        let merchant = from_spec(ObjectId::new().to_hex(), spec);
+        validate(&merchant, &merchants)?;
+        println!("Creating merchant {}", merchant.name);
+        self.db_client.insert(&merchant).await?;
+        merchants.push(merchant.clone());
+        let receipts = self.relink(merchants, &[]).await?;
+        Ok(MerchantChange { merchant, receipts })
+    }
+
+    /// Replaces the merchant's fields; its receipts are renamed or unlinked
+    /// to match.
+    pub async fn update(&self, id: &str, spec: MerchantSpec) -> Result<MerchantChange> {
+        let mut merchants = self.db_client.all().await?;
+        let idx = position(&merchants, id)?;
+        let merchant = Merchant {
+            created_at: merchants[idx].created_at,
+            ..from_spec(id.to_string(), spec)
+        };
+        validate(&merchant, &merchants)?;
+        self.db_client.replace(&merchant).await?;
+        merchants[idx] = merchant.clone();
+        let receipts = self
+            .relink(merchants, std::slice::from_ref(&merchant.id))
+            .await?;
+        Ok(MerchantChange { merchant, receipts })
+    }
+
+    /// Folds `source_ids` into `target_id`: their names become aliases of the
+    /// target, their patterns move over and their receipts are relinked.
+    pub async fn merge(&self, target_id: &str, source_ids: &[String]) -> Result<MerchantChange> {
+        if source_ids.is_empty() || source_ids.iter().any(|id| id == target_id) {
+            return Err(MerchantError::Invalid(
+                "Merge needs at least one source other than the target".to_string(),
+            )
+            .into());
+        }
+        let mut merchants = self.db_client.all().await?;
+        let mut target = merchants[position(&merchants, target_id)?].clone();
+        for source_id in source_ids {
+            let source = &merchants[position(&merchants, source_id)?];
+            for name in std::iter::once(&source.name).chain(&source.aliases) {
+                let key = merchant_key(name);
+                let known = std::iter::once(&target.name)
+                    .chain(&target.aliases)
+                    .any(|n| merchant_key(n) == key);
+                if !known {
+                    target.aliases.push(name.clone());
+                }
+            }
+            for pattern in &source.patterns {
+                if !target.patterns.contains(pattern) {
+                    target.patterns.push(pattern.clone());
+                }
+            }
+            target.default_category = target
+                .default_category
+                .or_else(|| source.default_category.clone());
+            target.logo_key = target.logo_key.or_else(|| source.logo_key.clone());
+        }
+        println!(
+            "Merging {} merchants into {}",
+            source_ids.len(),
+            target.name
+        );
+        self.db_client.replace(&target).await?;
+        self.db_client.delete(source_ids).await?;
+        merchants.retain(|m| !source_ids.contains(&m.id));
+        let idx = position(&merchants, target_id)?;
+        merchants[idx] = target.clone();
+
+        let mut relinked = source_ids.to_vec();
+        relinked.push(target.id.clone());
+        let receipts = self.relink(merchants, &relinked).await?;
+        Ok(MerchantChange {
+            merchant: target,
+            receipts,
+        })
+    }
+
+    /// Moves the aliases and patterns in `spec` from merchant `id` to a new
+    /// merchant, along with the receipts they match.
+    pub async fn split(&self, id: &str, spec: MerchantSpec) -> Result<MerchantChange> {
+        let mut merchants = self.db_client.all().await?;
+        let idx = position(&merchants, id)?;
+        let merchant = from_spec(ObjectId::new().to_hex(), spec);
+        let source = &mut merchants[idx];
+        if merchant_key(&merchant.name) == merchant_key(&source.name) {
+            return Err(MerchantError::Invalid(
+                "The new merchant needs a name of its own".to_string(),
+            )
+            .into());
+        }
+        let moved: Vec<String> = std::iter::once(&merchant.name)
+            .chain(&merchant.aliases)
+            .map(|n| merchant_key(n))
+            .collect();
+        source.aliases.retain(|a| !moved.contains(&merchant_key(a)));
+        source.patterns.retain(|p| !merchant.patterns.contains(p));
+        let source = source.clone();
+        validate(&merchant, &merchants)?;
+
+        println!("Splitting {} off {}", merchant.name, source.name);
+        self.db_client.replace(&source).await?;
+        self.db_client.insert(&merchant).await?;
+        merchants.push(merchant.clone());
+        let receipts = self.relink(merchants, &[source.id]).await?;
+        Ok(MerchantChange { merchant, receipts })
+    }
+
+    /// Re-resolves receipts linked to `merchant_ids`, and all unlinked ones,
+    /// against `merchants`. Returns how many receipts changed.
+    ///
+    /// Spans every user's receipts, as the registry is shared; the routes
+    /// that reach this are admin-only.
+    async fn relink(&self, merchants: Vec<Merchant>, merchant_ids: &[String]) -> Result<u64> {
+        let matcher = MerchantMatcher::new(merchants);
+        let mut changed = 0;
+        for group in self.receipts.merchant_groups(merchant_ids).await? {
+            let Some(raw) = group.raw.as_deref() else {
+                continue;
+            };
+            let target = matcher.find(raw);
+            let name = target.map_or(raw, |m| m.name.as_str());
+            if group.merchant_id.as_deref() == target.map(|m| m.id.as_str())
+                && group.merchant.as_deref() == Some(name)
+            {
+                continue;
+            }
+            changed += self.receipts.relink_merchant(&group, target).await?;
+        }
+        Ok(changed)
+    }
+}
+
+fn from_spec(id: String, spec: MerchantSpec) -> Merchant {
+    Merchant {
+        id,
+        name: spec.name.trim().to_string(),
+        aliases: spec.aliases,
+        patterns: spec.patterns,
+        default_category: spec.default_category,
+        logo_key: spec.logo_key,
+        created_at: DateTime::now().timestamp_millis(),
+    }
+}
+
+fn position(merchants: &[Merchant], id: &str) -> Result<usize> {
+    merchants
+        .iter()
+        .position(|m| m.id == id)
+        .ok_or_else(|| MerchantError::NotFound.into())
+}
+
+/// Rejects empty names, broken patterns and names or aliases that already
+/// belong to another merchant.
+fn validate(merchant: &Merchant, merchants: &[Merchant]) -> Result<()> {
+    if merchant_key(&merchant.name).is_empty() {
+        return Err(MerchantError::Invalid("Merchant name is empty".to_string()).into());
+    }
+    for pattern in &merchant.patterns {
+        if let Err(e) = compile_pattern(pattern) {
+            return Err(
+                MerchantError::Invalid(format!("Invalid pattern `{}`: {}", pattern, e)).into(),
+            );
+        }
+    }
+    for name in std::iter::once(&merchant.name).chain(&merchant.aliases) {
+        let key = merchant_key(name);
+        let owner = merchants.iter().find(|m| {
+            m.id != merchant.id
+                && std::iter::once(&m.name)
+                    .chain(&m.aliases)
+                    .any(|n| merchant_key(n) == key)
+        });
+        if let Some(owner) = owner {
+            return Err(MerchantError::Invalid(format!(
+                "`{}` already belongs to {}",
+                name, owner.name
+            ))
+            .into());
+        }
+    }
+    Ok(())
+}
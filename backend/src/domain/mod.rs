@@ -1,4 +1,31 @@
+pub mod category {
+    pub mod handlers;
+    pub mod models;
+    pub mod repository;
+    pub mod routes;
+    pub mod service;
+}
+
 pub mod email {
+    pub mod archive;
+    pub mod cache;
+    pub mod extractor;
+    pub mod gmail;
+    pub mod handlers;
+    pub mod imap;
+    pub mod maildir;
+    pub mod models;
+    pub mod prompts;
+    pub mod repository;
+    pub mod routes;
+    pub mod sanitize;
+    pub mod service;
+    pub mod source;
+    pub mod templates;
+    pub mod validation;
+}
+
+pub mod fx {
     pub mod handlers;
     pub mod models;
     pub mod repository;
@@ -13,6 +40,37 @@ pub mod ingestor {
     pub mod service;
 }
 
+pub mod instrument {
+    pub mod handlers;
+    pub mod models;
+    pub mod repository;
+    pub mod routes;
+    pub mod service;
+}
+
+pub mod jobs {
+    pub mod handlers;
+    pub mod models;
+    pub mod repository;
+    pub mod routes;
+    pub mod service;
+}
+
+pub mod llm {
+    pub mod client;
+    pub mod mock;
+    pub mod ollama;
+    pub mod openai;
+}
+
+pub mod merchant {
+    pub mod handlers;
+    pub mod models;
+    pub mod repository;
+    pub mod routes;
+    pub mod service;
+}
+
 pub mod receipt {
     pub mod handlers;
     pub mod models;
@@ -5,11 +5,14 @@ use axum::{
     extract::{Path, State},
     http::StatusCode,
     response::IntoResponse,
-    Json,
+    Extension, Json,
 };
 use serde::Deserialize;
 
-use crate::common::{api_response::ApiResponse, app_state::AppState};
+use crate::{
+    common::{api_response::ApiResponse, app_state::AppState},
+    domain::auth::models::Claims,
+};
 
 #[derive(Deserialize)]
 pub struct UpdateCategories {
@@ -30,13 +33,14 @@ pub async fn get_receipts_by_email(
 }
 
 pub async fn update_receipt_categories(
+    Extension(claims): Extension<Claims>,
     Path(receipt_id): Path<String>,
     State(state): State<Arc<AppState>>,
     Json(request): Json<UpdateCategories>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
     match state
         .receipt_service
-        .update_categories(&receipt_id, request.categories)
+        .update_categories(&claims.sub, &receipt_id, request.categories)
         .await
     {
         Ok(_) => Ok(Json(ApiResponse::success(()))),
@@ -49,3 +53,17 @@ pub async fn update_receipt_categories(
         )),
     }
 }
+
+/// Re-runs the refund matcher over the caller's receipts.
+pub async fn link_refunds(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    match state.receipt_service.link_refunds(&claims.sub).await {
+        Ok(linked) => Ok(Json(ApiResponse::success(linked))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(format!("Failed to link refunds: {}", e))),
+        )),
+    }
+}
@@ -1,18 +1,360 @@
+use crate::{
+    common::money::{currency_exponent, Money},
+    domain::{fx::models::FxConversion, instrument::models::InstrumentType},
+};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReceiptList {
     pub transactions: Vec<Receipt>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct Receipt {
     pub msg_id: Option<String>, // Gmail message ID
     pub owner: Option<String>,
     pub issuer: Option<String>,
+    /// Canonical name when the merchant is in the registry.
     pub merchant: Option<String>,
-    pub amount: Option<f64>,
+    /// Merchant name as extracted, kept once it has been normalized.
+    pub merchant_raw: Option<String>,
+    /// Registry `Merchant` the name resolved to.
+    pub merchant_id: Option<String>,
+    pub amount: Option<Money>,
     pub currency: Option<String>,
     pub categories: Option<Vec<String>>,
+    /// What set `categories`; `None` on receipts categorized before this was recorded.
+    pub category_source: Option<CategorySource>,
+    /// Rule that set `categories`, when `category_source` is `rule`.
+    pub category_rule: Option<String>,
     pub timestamp: Option<i64>,
+    /// File name of the attachment the receipt was extracted from, if not the body.
+    pub source_attachment: Option<String>,
+    /// What produced the receipt, e.g. `template:<name>` or `ollama:<model>`.
+    pub extractor: Option<String>,
+    /// Prompt file the LLM was given, as `<name>@v<version>`.
+    pub prompt_version: Option<String>,
+    /// Schema check outcome for LLM output; `None` for template receipts.
+    pub validation: Option<ValidationStatus>,
+    /// SHA-256 of the archived raw message the receipt came from.
+    pub raw_sha256: Option<String>,
+    /// What was bought, when the receipt lists it.
+    pub line_items: Option<Vec<LineItem>>,
+    pub subtotal: Option<Money>,
+    pub tax: Option<Money>,
+    pub tip: Option<Money>,
+    pub shipping: Option<Money>,
+    /// Amount taken off the total, as a positive number.
+    pub discount: Option<Money>,
+    /// Whether the breakdown adds up to `amount`; `None` when there is none.
+    pub totals_check: Option<TotalsCheck>,
+    /// Direction of the money; `None` on older receipts, read as a purchase.
+    pub kind: Option<TransactionKind>,
+    /// `msg_id` of the purchase a refund or reversal was matched to.
+    pub refund_of: Option<String>,
+    /// Card, account or wallet as shown in the email.
+    pub payment: Option<PaymentDetails>,
+    /// The user's `PaymentInstrument` the payment details resolved to.
+    pub instrument_id: Option<String>,
+    /// `amount` in the owner's base currency; `None` when no rate was found.
+    pub converted: Option<FxConversion>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CategorySource {
+    /// Set by the user; never overwritten automatically.
+    Manual,
+    Rule,
+    /// The registry merchant's default category.
+    Merchant,
+    /// Learned from the user's corrections on earlier receipts.
+    Learned,
+    /// Guessed by the LLM from the user's categories.
+    Llm,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct PaymentDetails {
+    #[serde(rename = "type")]
+    pub instrument_type: Option<InstrumentType>,
+    pub last4: Option<String>,
+    pub issuer: Option<String>,
+    pub nickname: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionKind {
+    #[default]
+    Purchase,
+    Refund,
+    /// Chargeback or cancelled authorization.
+    Reversal,
+    /// Money in that is not tied to a purchase, e.g. cashback or interest.
+    Credit,
+    /// Money moved between accounts.
+    Transfer,
+}
+
+impl TransactionKind {
+    /// Gives back money of an earlier purchase.
+    pub fn is_return(self) -> bool {
+        matches!(self, TransactionKind::Refund | TransactionKind::Reversal)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct LineItem {
+    pub description: Option<String>,
+    pub quantity: Option<f64>,
+    pub unit_price: Option<Money>,
+    pub total: Option<Money>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TotalsCheck {
+    /// Line items, subtotal and the other parts add up to the total.
+    Balanced,
+    /// They don't; the receipt needs a look.
+    Mismatch,
+}
+
+impl PaymentDetails {
+    /// Last four digits of a masked number such as `**** 1234` or `x1234`.
+    pub fn last4_of(raw: &str) -> Option<String> {
+        let digits: Vec<char> = raw.chars().filter(char::is_ascii_digit).collect();
+        (digits.len() >= 4).then(|| digits[digits.len() - 4..].iter().collect())
+    }
+}
+
+impl LineItem {
+    /// `total`, or `quantity × unit_price` when only those were given.
+    pub fn line_total(&self) -> Option<Money> {
+        self.total.or_else(|| {
+            let quantity = Money::from_f64(self.quantity.unwrap_or(1.0))?;
+            self.unit_price?.checked_mul(quantity)
+        })
+    }
+}
+
+impl Receipt {
+    /// Decimals of the receipt's currency; amounts are stored with this many.
+    pub fn exponent(&self) -> u32 {
+        currency_exponent(self.currency.as_deref())
+    }
+
+    /// Rounds the amounts to the currency's minor unit. Unit prices keep
+    /// extra decimals (fuel is priced per litre to the tenth of a cent).
+    /// Amounts too large to store are dropped.
+    pub fn normalize_amounts(&mut self) {
+        let exponent = self.exponent();
+        for value in [
+            &mut self.amount,
+            &mut self.subtotal,
+            &mut self.tax,
+            &mut self.tip,
+            &mut self.shipping,
+            &mut self.discount,
+        ] {
+            *value = value.and_then(|v| v.rescale(exponent));
+        }
+        for item in self.line_items.iter_mut().flatten() {
+            item.total = item.total.and_then(|v| v.rescale(exponent));
+            item.unit_price = item
+                .unit_price
+                .and_then(|v| v.rescale(v.exponent().max(exponent)));
+        }
+    }
+
+    /// Checks that the line items add up to the subtotal and that subtotal
+    /// (or the items) plus tax, tip and shipping minus discount is the total,
+    /// allowing one minor unit of rounding per summed value.
+    /// Returns `None` when there is nothing to check against.
+    pub fn check_totals(&self) -> Option<TotalsCheck> {
+        let exponent = self.exponent();
+        let minor = |value: Money| value.rescale(exponent).map(|v| v.minor() as i128);
+        let amount = minor(self.amount?)?;
+        let items = self.line_items.as_deref().unwrap_or_default();
+        let items_sum = if items.is_empty() {
+            None
+        } else {
+            items
+                .iter()
+                .map(|item| minor(item.line_total()?))
+                .sum::<Option<i128>>()
+        };
+        let tolerance = (items.len() + 1) as i128;
+        let part = |value: Option<Money>| value.map_or(Some(0), minor);
+
+        let mut balanced = true;
+        let subtotal = match self.subtotal {
+            Some(subtotal) => Some(minor(subtotal)?),
+            None => None,
+        };
+        if let (Some(sum), Some(subtotal)) = (items_sum, subtotal) {
+            balanced &= (sum - subtotal).abs() <= tolerance;
+        }
+        let expected =
+            subtotal.or(items_sum)? + part(self.tax)? + part(self.tip)? + part(self.shipping)?
+                - part(self.discount)?.abs();
+        // refunds may be reported with a negative total
+        balanced &= (expected.abs() - amount.abs()).abs() <= tolerance;
+
+        Some(if balanced {
+            TotalsCheck::Balanced
+        } else {
+            TotalsCheck::Mismatch
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationStatus {
+    /// The model's first answer matched the schema as-is.
+    Valid,
+    /// Matched after coercion or a re-prompt.
+    Repaired,
+    /// Still invalid after all repair attempts; only the usable fields are kept.
+    Rejected,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(raw: &str) -> Option<Money> {
+        Some(Money::parse(raw).unwrap())
+    }
+
+    fn item(quantity: Option<f64>, unit_price: &str, total: Option<&str>) -> LineItem {
+        LineItem {
+            description: None,
+            quantity,
+            unit_price: money(unit_price),
+            total: total.and_then(money),
+        }
+    }
+
+    #[test]
+    fn line_total_falls_back_to_quantity_times_price() {
+        let cases = [
+            (item(Some(3.0), "2.50", Some("7.00")), money("7.00")),
+            (item(Some(3.0), "2.50", None), money("7.50")),
+            (item(None, "2.50", None), money("2.50")),
+            (item(Some(1.5), "4.00", None), money("6.00")),
+            (item(Some(12.345), "1.899", None), money("23.443155")),
+        ];
+        for (item, expected) in cases {
+            assert_eq!(item.line_total(), expected, "{:?}", item);
+        }
+        let unpriced = LineItem {
+            unit_price: None,
+            ..item(Some(2.0), "0", None)
+        };
+        assert_eq!(unpriced.line_total(), None);
+    }
+
+    #[test]
+    fn totals_are_checked_against_the_parts() {
+        let base = Receipt {
+            currency: Some("USD".to_string()),
+            amount: money("23.00"),
+            subtotal: money("20.00"),
+            tax: money("2.00"),
+            tip: money("1.00"),
+            line_items: Some(vec![
+                item(Some(2.0), "5.00", None),
+                item(None, "10.00", Some("10.00")),
+            ]),
+            ..Default::default()
+        };
+        let cases = [
+            ("balanced", base.clone(), Some(TotalsCheck::Balanced)),
+            (
+                "total off by a dollar",
+                Receipt {
+                    amount: money("24.00"),
+                    ..base.clone()
+                },
+                Some(TotalsCheck::Mismatch),
+            ),
+            (
+                "items do not add up to the subtotal",
+                Receipt {
+                    subtotal: money("21.00"),
+                    amount: money("24.00"),
+                    ..base.clone()
+                },
+                Some(TotalsCheck::Mismatch),
+            ),
+            (
+                "discount taken off, whatever its sign",
+                Receipt {
+                    discount: money("-3.00"),
+                    amount: money("20.00"),
+                    ..base.clone()
+                },
+                Some(TotalsCheck::Balanced),
+            ),
+            (
+                "negative refund total",
+                Receipt {
+                    amount: money("-23.00"),
+                    ..base.clone()
+                },
+                Some(TotalsCheck::Balanced),
+            ),
+            (
+                "missing subtotal uses the items",
+                Receipt {
+                    subtotal: None,
+                    ..base.clone()
+                },
+                Some(TotalsCheck::Balanced),
+            ),
+            (
+                "rounding within a cent per value",
+                Receipt {
+                    amount: money("23.02"),
+                    ..base.clone()
+                },
+                Some(TotalsCheck::Balanced),
+            ),
+            (
+                "fractional quantity",
+                Receipt {
+                    amount: money("9.00"),
+                    subtotal: money("9.00"),
+                    tax: None,
+                    tip: None,
+                    line_items: Some(vec![item(Some(1.5), "6.00", None)]),
+                    ..base.clone()
+                },
+                Some(TotalsCheck::Balanced),
+            ),
+            (
+                "nothing to check against",
+                Receipt {
+                    subtotal: None,
+                    line_items: None,
+                    ..base.clone()
+                },
+                None,
+            ),
+            (
+                "no total",
+                Receipt {
+                    amount: None,
+                    ..base.clone()
+                },
+                None,
+            ),
+        ];
+        for (name, receipt, expected) in cases {
+            assert_eq!(receipt.check_totals(), expected, "{}", name);
+        }
+    }
 }
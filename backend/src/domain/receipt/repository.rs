@@ -1,9 +1,17 @@
-use crate::domain::receipt::models::{Receipt, ReceiptList};
+use crate::{
+    common::money::{Money, MAX_EXPONENT},
+    domain::{
+        fx::models::FxConversion,
+        instrument::models::InstrumentSpend,
+        merchant::models::{Merchant, MerchantGroup},
+        receipt::models::{CategorySource, Receipt, ReceiptList, TransactionKind},
+    },
+};
 use anyhow::{Context, Result};
 use chrono::{TimeZone, Utc};
 use futures::TryStreamExt;
 use mongodb::{
-    bson::{doc, Document},
+    bson::{doc, from_document, oid::ObjectId, to_bson, to_raw_document_buf, Bson, Document},
     Client, Collection,
 };
 
@@ -24,7 +32,7 @@ impl ReceiptRepo {
             .collection
             .insert_many(receipts.transactions)
             .await
-            .context(format!("Failed to insert receipts"))?;
+            .context("Failed to insert receipts".to_string())?;
         Ok(())
     }
 
@@ -41,6 +49,203 @@ impl ReceiptRepo {
         self.find_by(doc! {"owner": email}).await
     }
 
+    /// The user's receipts with their document ids, for updates of single receipts.
+    pub async fn by_email_with_ids(&self, email: &str) -> Result<Vec<(ObjectId, Receipt)>> {
+        let mut result = Vec::new();
+        let mut cursor = self
+            .collection
+            .clone_with_type::<Document>()
+            .find(doc! {"owner": email})
+            .await
+            .with_context(|| format!("failed to list receipts for {}", email))?;
+        while let Some(doc) = cursor.try_next().await? {
+            let id = doc.get_object_id("_id")?;
+            result.push((id, from_document(doc)?));
+        }
+        Ok(result)
+    }
+
+    /// Distinct categories on the user's receipts.
+    pub async fn categories_of(&self, email: &str) -> Result<Vec<String>> {
+        let values = self
+            .collection
+            .distinct("categories", doc! {"owner": email})
+            .await
+            .with_context(|| format!("failed to list categories for {}", email))?;
+        Ok(values
+            .into_iter()
+            .filter_map(|v| v.as_str().map(str::to_string))
+            .collect())
+    }
+
+    /// Distinct owners of stored receipts; with `unconverted_only`, just
+    /// those with receipts never run through FX conversion.
+    pub async fn owners(&self, unconverted_only: bool) -> Result<Vec<String>> {
+        let filter = if unconverted_only {
+            doc! {"converted": {"$exists": false}, "amount": {"$ne": null}}
+        } else {
+            doc! {}
+        };
+        let values = self
+            .collection
+            .distinct("owner", filter)
+            .await
+            .context("failed to list receipt owners")?;
+        Ok(values
+            .into_iter()
+            .filter_map(|v| v.as_str().map(str::to_string))
+            .collect())
+    }
+
+    pub async fn set_categories(
+        &self,
+        id: ObjectId,
+        categories: Option<&[String]>,
+        source: Option<CategorySource>,
+        rule: Option<&str>,
+    ) -> Result<()> {
+        self.collection
+            .update_one(
+                doc! {"_id": id},
+                doc! {"$set": {
+                    "categories": categories,
+                    "category_source": to_bson(&source)?,
+                    "category_rule": rule,
+                }},
+            )
+            .await
+            .with_context(|| format!("failed to set categories of receipt {}", id))?;
+        Ok(())
+    }
+
+    /// Rewrites receipts stored with float amounts into `{minor, exponent}`,
+    /// rounded to their currency. Returns how many were rewritten; once all
+    /// are, this is a single empty query.
+    pub async fn migrate_amounts(&self) -> Result<u64> {
+        let fields = [
+            "amount",
+            "subtotal",
+            "tax",
+            "tip",
+            "shipping",
+            "discount",
+            "line_items.unit_price",
+            "line_items.total",
+        ];
+        let filter = doc! {"$or": fields
+        .iter()
+        .map(|field| doc! {*field: {"$type": "number"}})
+        .collect::<Vec<_>>()};
+        let mut cursor = self
+            .collection
+            .clone_with_type::<Document>()
+            .find(filter)
+            .await
+            .context("failed to find receipts with float amounts")?;
+        let mut migrated = 0;
+        while let Some(doc) = cursor.try_next().await? {
+            let id = doc.get_object_id("_id")?;
+            let mut receipt: Receipt = from_document(doc)?;
+            receipt.normalize_amounts();
+            self.collection
+                .replace_one(doc! {"_id": id}, &receipt)
+                .await
+                .with_context(|| format!("failed to migrate amounts of receipt {}", id))?;
+            migrated += 1;
+        }
+        Ok(migrated)
+    }
+
+    pub async fn set_conversion(
+        &self,
+        id: ObjectId,
+        conversion: Option<&FxConversion>,
+    ) -> Result<()> {
+        // the raw serializer writes amounts as `{minor, exponent}`, like inserts
+        let converted = match conversion {
+            Some(c) => Bson::Document(to_raw_document_buf(c)?.to_document()?),
+            None => Bson::Null,
+        };
+        self.collection
+            .update_one(doc! {"_id": id}, doc! {"$set": {"converted": converted}})
+            .await
+            .with_context(|| format!("failed to set conversion of receipt {}", id))?;
+        Ok(())
+    }
+
+    /// Marks the user's receipts that got no conversion as checked, so the
+    /// startup backfill does not pick them up again.
+    pub async fn mark_unconverted(&self, email: &str) -> Result<()> {
+        self.collection
+            .update_many(
+                doc! {"owner": email, "converted": {"$exists": false}},
+                doc! {"$set": {"converted": Bson::Null}},
+            )
+            .await
+            .with_context(|| format!("failed to mark unconverted receipts of {}", email))?;
+        Ok(())
+    }
+
+    pub async fn by_email_and_instrument(
+        &self,
+        email: &str,
+        instrument_id: &str,
+    ) -> Result<Vec<Receipt>> {
+        self.find_by(doc! {"owner": email, "instrument_id": instrument_id})
+            .await
+    }
+
+    /// Sums the user's spend per instrument and currency, netting refunds and
+    /// reversals against purchases.
+    pub async fn spend_by_instrument(&self, email: &str) -> Result<Vec<InstrumentSpend>> {
+        let pipeline = vec![
+            doc! {"$match": {"owner": email}},
+            doc! {"$group": {
+                "_id": {"instrument_id": "$instrument_id", "currency": "$currency"},
+                "total": {"$sum": {"$switch": {
+                    "branches": [
+                        {
+                            "case": {"$in": ["$kind", ["refund", "reversal"]]},
+                            "then": {"$multiply": [{"$abs": "$amount.minor"}, -1]}
+                        },
+                        {"case": {"$in": ["$kind", ["credit", "transfer"]]}, "then": 0},
+                    ],
+                    "default": {"$abs": "$amount.minor"}
+                }}},
+                // amounts are stored with their currency's exponent
+                "exponent": {"$max": "$amount.exponent"},
+                "receipts": {"$sum": 1},
+            }},
+            doc! {"$project": {
+                "_id": 0,
+                "instrument_id": "$_id.instrument_id",
+                "currency": "$_id.currency",
+                "total": {"minor": "$total", "exponent": {"$ifNull": ["$exponent", 0]}},
+                "receipts": 1,
+            }},
+            doc! {"$sort": {"instrument_id": 1, "currency": 1}},
+        ];
+        let mut result = Vec::new();
+        let mut cursor = self
+            .collection
+            .aggregate(pipeline)
+            .await
+            .with_context(|| format!("failed to aggregate spend for {}", email))?;
+        while let Some(doc) = cursor.try_next().await? {
+            result.push(from_document(doc)?);
+        }
+        Ok(result)
+    }
+
+    /// The user's receipts with `from <= timestamp <= to` (epoch seconds).
+    pub async fn by_email_between(&self, email: &str, from: i64, to: i64) -> Result<Vec<Receipt>> {
+        self.find_by(doc! {
+            "owner": email,
+            "timestamp": {"$gte": from, "$lte": to}
+        })
+        .await
+    }
+
     pub async fn by_email_and_month(
         &self,
         email: &str,
@@ -65,20 +270,30 @@ impl ReceiptRepo {
         .await
     }
 
+    /// Receipts with `min_amount <= amount <= max_amount`, compared exactly
+    /// whatever the stored exponent.
     pub async fn by_email_and_amount(
         &self,
         email: &str,
-        min_amount: f64,
-        max_amount: f64,
+        min_amount: Money,
+        max_amount: Money,
     ) -> Result<Vec<Receipt>> {
-        self.find_by(doc! {
-            "owner": email,
-            "amount": {
-                "$gte": min_amount,
-                "$lte": max_amount
-            }
-        })
-        .await
+        let mut ranges = Vec::new();
+        for exponent in 0..=MAX_EXPONENT {
+            let (Some(min), Some(max)) =
+                (min_amount.ceil_to(exponent), max_amount.floor_to(exponent))
+            else {
+                continue;
+            };
+            ranges.push(doc! {
+                "amount.exponent": exponent,
+                "amount.minor": {"$gte": min.minor(), "$lte": max.minor()},
+            });
+        }
+        if ranges.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.find_by(doc! {"owner": email, "$or": ranges}).await
     }
 
     pub async fn by_email_and_merchant(&self, email: &str, merchant: &str) -> Result<Vec<Receipt>> {
@@ -89,6 +304,65 @@ impl ReceiptRepo {
         .await
     }
 
+    /// Distinct `(merchant_id, merchant, extracted name)` combinations of the
+    /// receipts linked to `merchant_ids` or to no merchant, across all users.
+    pub async fn merchant_groups(&self, merchant_ids: &[String]) -> Result<Vec<MerchantGroup>> {
+        let mut ids: Vec<Bson> = merchant_ids
+            .iter()
+            .map(|id| Bson::from(id.as_str()))
+            .collect();
+        ids.push(Bson::Null);
+        let pipeline = vec![
+            doc! {"$match": {"merchant_id": {"$in": ids}}},
+            doc! {"$group": {"_id": {
+                "merchant_id": "$merchant_id",
+                "merchant": "$merchant",
+                "raw": {"$ifNull": ["$merchant_raw", "$merchant"]},
+            }}},
+            doc! {"$replaceRoot": {"newRoot": "$_id"}},
+        ];
+        let mut result = Vec::new();
+        let mut cursor = self
+            .collection
+            .aggregate(pipeline)
+            .await
+            .context("failed to group receipts by merchant")?;
+        while let Some(doc) = cursor.try_next().await? {
+            result.push(from_document(doc)?);
+        }
+        Ok(result)
+    }
+
+    /// Points the receipts of `group` at `merchant`, or back to their
+    /// extracted name when there is none. Returns how many were updated.
+    pub async fn relink_merchant(
+        &self,
+        group: &MerchantGroup,
+        merchant: Option<&Merchant>,
+    ) -> Result<u64> {
+        let raw = group.raw.as_deref();
+        let update = match merchant {
+            Some(m) => doc! {"merchant": &m.name, "merchant_id": &m.id, "merchant_raw": raw},
+            None => doc! {"merchant": raw, "merchant_id": null, "merchant_raw": null},
+        };
+        let result = self
+            .collection
+            .update_many(
+                doc! {
+                    "merchant_id": &group.merchant_id,
+                    "merchant": &group.merchant,
+                    "$or": [
+                        {"merchant_raw": raw},
+                        {"merchant_raw": null, "merchant": raw},
+                    ],
+                },
+                doc! {"$set": update},
+            )
+            .await
+            .with_context(|| format!("failed to relink receipts of {}", raw.unwrap_or_default()))?;
+        Ok(result.modified_count)
+    }
+
     pub async fn get_receipts_by_month(&self, year: i32, month: u32) -> Result<Vec<Receipt>> {
         let start_date = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).unwrap();
         let end_date = if month == 12 {
@@ -106,15 +380,69 @@ impl ReceiptRepo {
         .await
     }
 
-    pub async fn upsert_categories(&self, msg_id: &str, categories: Vec<String>) -> Result<()> {
+    pub async fn upsert_categories(
+        &self,
+        email: &str,
+        msg_id: &str,
+        categories: Vec<String>,
+    ) -> Result<()> {
         let _ = self
             .collection
             .update_one(
-                doc! {"msg_id": msg_id},
-                doc! {"$set": doc! {"categories": categories}},
+                doc! {"owner": email, "msg_id": msg_id},
+                doc! {"$set": doc! {
+                    "categories": categories,
+                    "category_source": to_bson(&CategorySource::Manual)?,
+                    "category_rule": null,
+                }},
             )
             .await
-            .context(format!("failed to update categories"))?;
+            .context("failed to update categories".to_string())?;
+        Ok(())
+    }
+
+    /// Links the refunds and reversals of `msg_id` to the purchase in `purchase_msg_id`.
+    pub async fn set_refund_of(
+        &self,
+        email: &str,
+        msg_id: &str,
+        purchase_msg_id: &str,
+    ) -> Result<()> {
+        let returns = [TransactionKind::Refund, TransactionKind::Reversal]
+            .iter()
+            .map(mongodb::bson::to_bson)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.collection
+            .update_many(
+                doc! {"owner": email, "msg_id": msg_id, "kind": {"$in": returns}},
+                doc! {"$set": {"refund_of": purchase_msg_id}},
+            )
+            .await
+            .with_context(|| format!("failed to link refund {}", msg_id))?;
+        Ok(())
+    }
+
+    pub async fn by_message(&self, email: &str, msg_id: &str) -> Result<Vec<Receipt>> {
+        self.find_by(doc! {"owner": email, "msg_id": msg_id}).await
+    }
+
+    /// Swaps all receipts of one message for `receipts`.
+    pub async fn replace_for_message(
+        &self,
+        email: &str,
+        msg_id: &str,
+        receipts: Vec<Receipt>,
+    ) -> Result<()> {
+        self.collection
+            .delete_many(doc! {"owner": email, "msg_id": msg_id})
+            .await
+            .with_context(|| format!("failed to delete receipts of {}", msg_id))?;
+        if !receipts.is_empty() {
+            self.collection
+                .insert_many(receipts)
+                .await
+                .with_context(|| format!("failed to insert receipts of {}", msg_id))?;
+        }
         Ok(())
     }
 }
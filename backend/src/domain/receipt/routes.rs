@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use axum::{
     middleware,
-    routing::{get, put},
+    routing::{get, post, put},
     Router,
 };
 
@@ -10,13 +10,14 @@ use crate::{
     common::app_state::AppState,
     domain::{
         auth::handlers::authorization_middleware,
-        receipt::handlers::{get_receipts_by_email, update_receipt_categories},
+        receipt::handlers::{get_receipts_by_email, link_refunds, update_receipt_categories},
     },
 };
 
 pub fn routes(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/receipts/{email}", get(get_receipts_by_email))
+        .route("/receipts/refunds/link", post(link_refunds))
         .route(
             "/receipts/{receipt_id}/categories",
             put(update_receipt_categories),
@@ -1,25 +1,168 @@
-use crate::domain::receipt::{models::ReceiptList, repository::ReceiptRepo};
+use crate::domain::{
+    category::service::CategoryService,
+    fx::service::FxService,
+    instrument::{models::InstrumentSpend, repository::InstrumentRepo},
+    merchant::{
+        models::{merchant_key, MerchantMatcher},
+        repository::MerchantRepo,
+    },
+    receipt::{
+        models::{Receipt, ReceiptList, TransactionKind},
+        repository::ReceiptRepo,
+    },
+};
 use anyhow::Result;
+use std::{
+    collections::{BTreeSet, HashSet},
+    sync::Arc,
+};
+
+/// How long after a purchase a refund or reversal may still be matched to it.
+const REFUND_WINDOW_SECS: i64 = 120 * 24 * 60 * 60;
 
 #[derive(Clone)]
 pub struct ReceiptService {
     db_client: ReceiptRepo,
+    instruments: InstrumentRepo,
+    merchants: MerchantRepo,
+    categorizer: Arc<CategoryService>,
+    fx: Arc<FxService>,
 }
 
 /// ReceiptService handles business logic for transactions relating to email receipts.
 impl ReceiptService {
-    pub fn new(db_client: ReceiptRepo) -> Self {
+    pub fn new(
+        db_client: ReceiptRepo,
+        instruments: InstrumentRepo,
+        merchants: MerchantRepo,
+        categorizer: Arc<CategoryService>,
+        fx: Arc<FxService>,
+    ) -> Self {
         ReceiptService {
-            db_client: db_client,
+            db_client,
+            instruments,
+            merchants,
+            categorizer,
+            fx,
         }
     }
 
-    pub async fn store(&self, receipts: ReceiptList) -> Result<()> {
+    pub async fn store(&self, mut receipts: ReceiptList) -> Result<()> {
         println!("Storing receipts");
+        receipts
+            .transactions
+            .iter_mut()
+            .for_each(Receipt::normalize_amounts);
+        self.normalize_merchants(&mut receipts.transactions).await?;
+        self.categorizer
+            .categorize(&mut receipts.transactions)
+            .await?;
+        self.attach_instruments(&mut receipts.transactions).await?;
+        self.fx.convert(&mut receipts.transactions).await?;
+        let returns = unlinked_returns(&receipts.transactions);
+        let owners: BTreeSet<String> = returns.iter().filter_map(|r| r.owner.clone()).collect();
         self.db_client.insert(receipts).await?;
+        for owner in owners {
+            self.link_new_refunds(&owner, &returns).await?;
+        }
+        Ok(())
+    }
+
+    /// Swaps extracted merchant names for their canonical merchant, keeping
+    /// the original in `merchant_raw`.
+    async fn normalize_merchants(&self, receipts: &mut [Receipt]) -> Result<()> {
+        if receipts.iter().all(|r| r.merchant.is_none()) {
+            return Ok(());
+        }
+        let matcher = MerchantMatcher::new(self.merchants.all().await?);
+        for receipt in receipts.iter_mut() {
+            let Some(raw) = receipt.merchant_raw.clone().or(receipt.merchant.clone()) else {
+                continue;
+            };
+            let Some(merchant) = matcher.find(&raw) else {
+                continue;
+            };
+            receipt.merchant = Some(merchant.name.clone());
+            receipt.merchant_id = Some(merchant.id.clone());
+            receipt.merchant_raw = Some(raw);
+        }
+        Ok(())
+    }
+
+    /// Links receipts to the owner's instrument for their payment details,
+    /// creating it the first time a card or wallet shows up. The sender's
+    /// name stands in for a missing payment issuer.
+    async fn attach_instruments(&self, receipts: &mut [Receipt]) -> Result<()> {
+        for receipt in receipts.iter_mut() {
+            let (Some(owner), Some(payment)) = (&receipt.owner, &mut receipt.payment) else {
+                continue;
+            };
+            if payment.issuer.is_none() {
+                payment.issuer = receipt.issuer.clone();
+            }
+            receipt.instrument_id = self.instruments.resolve(owner, payment).await?;
+        }
         Ok(())
     }
 
+    pub async fn get_by_instrument(&self, email: &str, instrument_id: &str) -> Result<ReceiptList> {
+        let receipts = self
+            .db_client
+            .by_email_and_instrument(email, instrument_id)
+            .await?;
+        Ok(ReceiptList {
+            transactions: receipts,
+        })
+    }
+
+    pub async fn spend_by_instrument(&self, email: &str) -> Result<Vec<InstrumentSpend>> {
+        self.db_client.spend_by_instrument(email).await
+    }
+
+    /// Matches the user's unlinked refunds and reversals to their purchases
+    /// and records the link in `refund_of`. Returns how many were linked.
+    pub async fn link_refunds(&self, email: &str) -> Result<usize> {
+        let receipts = self.db_client.by_email(email).await?;
+        let links = match_refunds(&receipts, |_| true);
+        self.save_links(email, links).await
+    }
+
+    /// Links just the given new refunds of the user, loading only receipts
+    /// from a refund window around them.
+    async fn link_new_refunds(&self, email: &str, returns: &[Receipt]) -> Result<usize> {
+        let returns: Vec<&Receipt> = returns
+            .iter()
+            .filter(|r| r.owner.as_deref().is_none_or(|o| o == email))
+            .collect();
+        let times = returns.iter().filter_map(|r| r.timestamp);
+        let (Some(first), Some(last)) = (times.clone().min(), times.max()) else {
+            return Ok(0);
+        };
+        let ids: HashSet<&str> = returns.iter().filter_map(|r| r.msg_id.as_deref()).collect();
+        // purchases come up to a window before a refund, and refunds already
+        // linked to those purchases up to a window after them
+        let receipts = self
+            .db_client
+            .by_email_between(email, first - REFUND_WINDOW_SECS, last + REFUND_WINDOW_SECS)
+            .await?;
+        let links = match_refunds(&receipts, |r| {
+            r.msg_id.as_deref().is_some_and(|id| ids.contains(id))
+        });
+        self.save_links(email, links).await
+    }
+
+    async fn save_links(&self, email: &str, links: Vec<(String, String)>) -> Result<usize> {
+        for (refund, purchase) in &links {
+            self.db_client
+                .set_refund_of(email, refund, purchase)
+                .await?;
+        }
+        if !links.is_empty() {
+            println!("Linked {} refunds for {}", links.len(), email);
+        }
+        Ok(links.len())
+    }
+
     pub async fn get_by(&self, email: &str) -> Result<ReceiptList> {
         println!("Getting receipts for {}", email);
         let receipts = self.db_client.by_email(email).await?;
@@ -28,9 +171,248 @@ impl ReceiptService {
         })
     }
 
-    pub async fn update_categories(&self, msg_id: &str, categories: Vec<String>) -> Result<()> {
+    /// Sets the categories by hand and remembers the correction for later
+    /// receipts of the same merchant.
+    pub async fn update_categories(
+        &self,
+        email: &str,
+        msg_id: &str,
+        categories: Vec<String>,
+    ) -> Result<()> {
         println!("Updating categories for {}", msg_id);
-        self.db_client.upsert_categories(msg_id, categories).await?;
+        let before = self.db_client.by_message(email, msg_id).await?;
+        self.db_client
+            .upsert_categories(email, msg_id, categories.clone())
+            .await?;
+        if let Some(receipt) = before.first() {
+            self.categorizer
+                .record_correction(email, receipt, &categories)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn get_for_message(&self, email: &str, msg_id: &str) -> Result<Vec<Receipt>> {
+        self.db_client.by_message(email, msg_id).await
+    }
+
+    pub async fn replace_for_message(
+        &self,
+        email: &str,
+        msg_id: &str,
+        mut receipts: Vec<Receipt>,
+    ) -> Result<()> {
+        receipts.iter_mut().for_each(Receipt::normalize_amounts);
+        self.normalize_merchants(&mut receipts).await?;
+        self.categorizer.categorize(&mut receipts).await?;
+        self.attach_instruments(&mut receipts).await?;
+        self.fx.convert(&mut receipts).await?;
+        let returns = unlinked_returns(&receipts);
+        self.db_client
+            .replace_for_message(email, msg_id, receipts)
+            .await?;
+        self.link_new_refunds(email, &returns).await?;
         Ok(())
     }
 }
+
+fn unlinked_returns(receipts: &[Receipt]) -> Vec<Receipt> {
+    receipts
+        .iter()
+        .filter(|r| r.refund_of.is_none() && r.kind.unwrap_or_default().is_return())
+        .cloned()
+        .collect()
+}
+
+/// Pairs unlinked refunds and reversals picked by `wanted`, as
+/// `(refund msg_id, purchase msg_id)`, with a purchase of the same merchant,
+/// currency and amount made at most `REFUND_WINDOW_SECS` before. The latest
+/// such purchase wins and each purchase is matched once. Partial refunds are
+/// left unlinked.
+fn match_refunds(receipts: &[Receipt], wanted: impl Fn(&Receipt) -> bool) -> Vec<(String, String)> {
+    let mut taken: HashSet<&str> = receipts
+        .iter()
+        .filter_map(|r| r.refund_of.as_deref())
+        .collect();
+    let mut refunds: Vec<&Receipt> = receipts
+        .iter()
+        .filter(|r| r.refund_of.is_none() && r.kind.unwrap_or_default().is_return() && wanted(r))
+        .collect();
+    refunds.sort_by_key(|r| r.timestamp);
+
+    let mut links = Vec::new();
+    for refund in refunds {
+        let (Some(refund_id), Some(refunded_at), Some(amount)) =
+            (refund.msg_id.as_deref(), refund.timestamp, refund.amount)
+        else {
+            continue;
+        };
+        let merchant = normalize_merchant(refund.merchant.as_deref());
+        if merchant.is_empty() {
+            continue;
+        }
+        let purchase = receipts
+            .iter()
+            .filter(|p| {
+                p.kind.unwrap_or_default() == TransactionKind::Purchase
+                    && p.msg_id
+                        .as_deref()
+                        .is_some_and(|id| id != refund_id && !taken.contains(id))
+                    && p.currency == refund.currency
+                    && p.amount.is_some_and(|a| a.abs() == amount.abs())
+                    && p.timestamp
+                        .is_some_and(|t| t <= refunded_at && refunded_at - t <= REFUND_WINDOW_SECS)
+                    && same_merchant(&normalize_merchant(p.merchant.as_deref()), &merchant)
+            })
+            .max_by_key(|p| p.timestamp);
+        if let Some(purchase_id) = purchase.and_then(|p| p.msg_id.as_deref()) {
+            taken.insert(purchase_id);
+            links.push((refund_id.to_string(), purchase_id.to_string()));
+        }
+    }
+    links
+}
+
+fn normalize_merchant(merchant: Option<&str>) -> String {
+    merchant_key(merchant.unwrap_or_default())
+}
+
+/// Refund emails often shorten the name, e.g. `Amazon` for `Amazon.com`.
+fn same_merchant(a: &str, b: &str) -> bool {
+    !a.is_empty() && !b.is_empty() && (a.contains(b) || b.contains(a))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::money::Money;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn receipt(id: &str, kind: TransactionKind, merchant: &str, amount: &str, day: i64) -> Receipt {
+        Receipt {
+            msg_id: Some(id.to_string()),
+            kind: Some(kind),
+            merchant: Some(merchant.to_string()),
+            amount: Money::parse(amount),
+            currency: Some("USD".to_string()),
+            timestamp: Some(day * DAY),
+            ..Default::default()
+        }
+    }
+
+    fn purchase(id: &str, merchant: &str, amount: &str, day: i64) -> Receipt {
+        receipt(id, TransactionKind::Purchase, merchant, amount, day)
+    }
+
+    fn refund(id: &str, merchant: &str, amount: &str, day: i64) -> Receipt {
+        receipt(id, TransactionKind::Refund, merchant, amount, day)
+    }
+
+    fn links(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(r, p)| (r.to_string(), p.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn refunds_match_purchases_in_the_window() {
+        let taken = Receipt {
+            refund_of: Some("p1".to_string()),
+            ..refund("r0", "Shop", "20.00", 101)
+        };
+        let cases = [
+            (
+                "same amount and merchant inside the window",
+                vec![
+                    purchase("p1", "Shop", "20.00", 100),
+                    refund("r1", "SHOP", "20.00", 110),
+                ],
+                links(&[("r1", "p1")]),
+            ),
+            (
+                "shortened merchant name",
+                vec![
+                    purchase("p1", "Shop.com", "20.00", 100),
+                    refund("r1", "Shop", "20.00", 110),
+                ],
+                links(&[("r1", "p1")]),
+            ),
+            (
+                "purchase older than the window",
+                vec![
+                    purchase("p1", "Shop", "20.00", 100),
+                    refund("r1", "Shop", "20.00", 221),
+                ],
+                vec![],
+            ),
+            (
+                "purchase after the refund",
+                vec![
+                    purchase("p1", "Shop", "20.00", 111),
+                    refund("r1", "Shop", "20.00", 110),
+                ],
+                vec![],
+            ),
+            (
+                "purchase already taken",
+                vec![
+                    purchase("p1", "Shop", "20.00", 100),
+                    taken,
+                    refund("r1", "Shop", "20.00", 110),
+                ],
+                vec![],
+            ),
+            (
+                "partial refund",
+                vec![
+                    purchase("p1", "Shop", "20.00", 100),
+                    refund("r1", "Shop", "5.00", 110),
+                ],
+                vec![],
+            ),
+            (
+                "two refunds for one purchase",
+                vec![
+                    purchase("p1", "Shop", "20.00", 100),
+                    refund("r1", "Shop", "20.00", 110),
+                    refund("r2", "Shop", "20.00", 120),
+                ],
+                links(&[("r1", "p1")]),
+            ),
+            (
+                "the latest purchase wins",
+                vec![
+                    purchase("p1", "Shop", "20.00", 100),
+                    purchase("p2", "Shop", "20.00", 105),
+                    refund("r1", "Shop", "20.00", 110),
+                ],
+                links(&[("r1", "p2")]),
+            ),
+            (
+                "other merchant",
+                vec![
+                    purchase("p1", "Cafe", "20.00", 100),
+                    refund("r1", "Shop", "20.00", 110),
+                ],
+                vec![],
+            ),
+        ];
+        for (name, receipts, expected) in cases {
+            assert_eq!(match_refunds(&receipts, |_| true), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn only_wanted_refunds_are_matched() {
+        let receipts = [
+            purchase("p1", "Shop", "20.00", 100),
+            purchase("p2", "Cafe", "8.00", 100),
+            refund("r1", "Shop", "20.00", 110),
+            refund("r2", "Cafe", "8.00", 110),
+        ];
+        let matched = match_refunds(&receipts, |r| r.msg_id.as_deref() == Some("r2"));
+        assert_eq!(matched, links(&[("r2", "p2")]));
+    }
+}
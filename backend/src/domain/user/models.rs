@@ -9,6 +9,12 @@ pub struct User {
     pub last_synced: Option<i64>,
     pub secret: Option<Secret>,
     pub gmail_token: Option<String>,
+    /// Mail source kind to sync from (`gmail`, `imap`, `maildir`); `None` uses the default.
+    #[serde(default)]
+    pub mail_source: Option<String>,
+    /// ISO 4217 code receipts are converted to; `None` uses `FX_BASE_CURRENCY`.
+    #[serde(default)]
+    pub base_currency: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -23,6 +29,8 @@ pub struct PublicUser {
     pub active: bool,
     pub last_synced: Option<i64>,
     pub google_sub: Option<String>,
+    pub mail_source: Option<String>,
+    pub base_currency: Option<String>,
 }
 
 impl From<User> for PublicUser {
@@ -33,6 +41,8 @@ impl From<User> for PublicUser {
             active: value.active,
             last_synced: value.last_synced,
             google_sub: value.google_sub,
+            mail_source: value.mail_source,
+            base_currency: value.base_currency,
         }
     }
 }
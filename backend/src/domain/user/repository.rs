@@ -56,6 +56,28 @@ impl UserRepo {
         Ok(())
     }
 
+    pub async fn update_mail_source(&self, email: &str, mail_source: Option<&str>) -> Result<()> {
+        self.collection
+            .update_one(
+                doc! { "email": email },
+                doc! { "$set": { "mail_source": mail_source } },
+            )
+            .await
+            .context("Updating mail source")?;
+        Ok(())
+    }
+
+    pub async fn update_base_currency(&self, email: &str, currency: &str) -> Result<()> {
+        self.collection
+            .update_one(
+                doc! { "email": email },
+                doc! { "$set": { "base_currency": currency } },
+            )
+            .await
+            .context("Updating base currency")?;
+        Ok(())
+    }
+
     pub async fn find_users_by_status(&self, status: bool) -> Result<Vec<User>> {
         let mut users: Vec<User> = Vec::new();
         let mut cursor = self.collection.find(doc! {"active": status}).await?;
@@ -9,22 +9,18 @@ pub struct UserService {
 
 impl UserService {
     pub fn new(db_client: UserRepo) -> Self {
-        UserService {
-            db_client: db_client,
-        }
+        UserService { db_client }
     }
 
     pub async fn get_users_by_status(&self, status: bool) -> Result<Vec<User>> {
-        Ok(self
-            .db_client
+        self.db_client
             .find_users_by_status(status)
             .await
-            .context("Retrieving users by status")?)
+            .context("Retrieving users by status")
     }
 
     pub async fn register_new_user(&self, user: User) -> Result<()> {
-        let _ = self
-            .db_client
+        self.db_client
             .insert_user(user)
             .await
             .context("Registering new user")?;
@@ -68,15 +64,23 @@ impl UserService {
             last_synced: None,
             secret: None,
             gmail_token: None,
+            mail_source: None,
+            base_currency: None,
         };
 
         self.register_new_user(new_user.clone()).await?;
         Ok(new_user)
     }
 
+    pub async fn set_mail_source(&self, email: &str, mail_source: Option<&str>) -> Result<()> {
+        self.db_client
+            .update_mail_source(email, mail_source)
+            .await
+            .context("Updating mail source")
+    }
+
     pub async fn update_last_synced(&self, users: Vec<User>) -> Result<()> {
-        let _ = self
-            .db_client
+        self.db_client
             .bulk_update_users(users)
             .await
             .context("Bulk updating last_synced")?;
@@ -1,11 +1,11 @@
-use crate::app::{build_app, mount_routes, start_sync_job};
 use anyhow::{Context, Result};
+use backend::app::{
+    build_app, mount_routes, start_archive_retention, start_job_runner, start_sync_job,
+    start_sync_queue, start_watch_renewal,
+};
 use backend::config::AppConfig;
 use dotenvy::dotenv;
 use std::sync::Arc;
-mod app;
-mod common;
-mod domain;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -21,7 +21,11 @@ async fn main() -> Result<()> {
 
     // API Components
     let app = mount_routes(app_state.clone());
-    let _ = start_sync_job(60 * 60 * 24, app_state.clone()); // 1 day!
+    start_sync_job(60 * 60 * 24, app_state.clone()); // 1 day!
+    start_sync_queue(app_state.clone());
+    start_job_runner(app_state.clone());
+    start_watch_renewal(60 * 60 * 24, app_state.clone());
+    start_archive_retention(60 * 60 * 24, app_state.clone());
 
     // Start the server
     let listener = tokio::net::TcpListener::bind("0.0.0.0:4000").await.unwrap();
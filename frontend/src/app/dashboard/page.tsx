@@ -25,8 +25,8 @@ import {
     buildSummary,
     buildTimeSeriesWithProjector,
     detectAnomaliesWithProjector,
+    spendSign,
 } from "@/lib/analytics";
-import type { CurrencyCode } from "@/lib/config";
 import type { Receipt, ReceiptFilters as ReceiptFiltersType } from "@/types";
 
 type InlineNotice = {
@@ -76,7 +76,7 @@ function buildActivityEntries(
 
 export default function DashboardPage() {
     const session = useSessionContext();
-    const { convert, supported } = useCurrency();
+    const { baseAmount, currency } = useCurrency();
     const [filters, setFilters] = useState<ReceiptFiltersType>(() => ({
         email: session.email ?? "",
         range: DEFAULT_RANGE,
@@ -110,38 +110,43 @@ export default function DashboardPage() {
         refresh,
     });
 
-    const supportedSet = useMemo(
-        () => new Set<CurrencyCode>(supported),
-        [supported],
-    );
+    // receipts the backend had no rate for are left out of the totals and
+    // counted in a notice instead of being summed as zero
+    const { convertible, unconverted } = useMemo(() => {
+        const convertible: Receipt[] = [];
+        let unconverted = 0;
+        filtered.forEach((receipt) => {
+            if (baseAmount(receipt) === null) {
+                unconverted += 1;
+            } else {
+                convertible.push(receipt);
+            }
+        });
+        return { convertible, unconverted };
+    }, [filtered, baseAmount]);
 
+    // refunds net against spend; credits and transfers are left out
     const amountProjector = useCallback(
-        (receipt: Receipt) => {
-            const baseCurrency = supportedSet.has(
-                receipt.currency as CurrencyCode,
-            )
-                ? (receipt.currency as CurrencyCode)
-                : "USD";
-            return convert(receipt.amount, baseCurrency);
-        },
-        [convert, supportedSet],
+        (receipt: Receipt) =>
+            spendSign(receipt) * Math.abs(baseAmount(receipt) ?? 0),
+        [baseAmount],
     );
 
     const summary = useMemo(
-        () => buildSummary(filtered, amountProjector),
-        [filtered, amountProjector],
+        () => buildSummary(convertible, amountProjector),
+        [convertible, amountProjector],
     );
     const series = useMemo(
-        () => buildTimeSeriesWithProjector(filtered, amountProjector),
-        [filtered, amountProjector],
+        () => buildTimeSeriesWithProjector(convertible, amountProjector),
+        [convertible, amountProjector],
     );
     const categories = useMemo(
-        () => buildCategorySlices(filtered, amountProjector),
-        [filtered, amountProjector],
+        () => buildCategorySlices(convertible, amountProjector),
+        [convertible, amountProjector],
     );
     const anomalies = useMemo(
-        () => detectAnomaliesWithProjector(filtered, amountProjector),
-        [filtered, amountProjector],
+        () => detectAnomaliesWithProjector(convertible, amountProjector),
+        [convertible, amountProjector],
     );
 
     const availableCategories = useMemo(() => {
@@ -276,6 +281,16 @@ export default function DashboardPage() {
                                 }}
                             />
                         )}
+                        {unconverted > 0 && (
+                            <SystemFeedback
+                                notice={{
+                                    id: "fx-missing",
+                                    title: `${unconverted} receipt${unconverted === 1 ? "" : "s"} left out of the totals`,
+                                    detail: `No ${currency} rate was found for their date. They are listed below in their own currency, marked "no rate".`,
+                                    tone: "info",
+                                }}
+                            />
+                        )}
                         <InsightsGrid
                             summary={summary}
                             series={series}
@@ -3,7 +3,6 @@
 import { useEffect, useId, useMemo, useState } from "react";
 
 import { useCurrency } from "@/context/currency-context";
-import type { CurrencyCode } from "@/lib/config";
 import type { Receipt } from "@/types";
 import { formatDateTime } from "@/lib/dates";
 import {
@@ -40,7 +39,7 @@ export function ReceiptDrawer({
     onClose,
     onUpdateCategories,
 }: ReceiptDrawerProps) {
-    const { convert, format, supported } = useCurrency();
+    const { baseAmount, format } = useCurrency();
     const [localCategories, setLocalCategories] = useState<string[]>([]);
     const [newCategory, setNewCategory] = useState("");
     const [suggestions] = useState<string[]>([
@@ -76,11 +75,6 @@ export function ReceiptDrawer({
         return () => window.removeEventListener("keydown", handleKey);
     }, [receipt, onClose]);
 
-    const supportedSet = useMemo(
-        () => new Set<CurrencyCode>(supported),
-        [supported],
-    );
-
     const canSave = useMemo(() => {
         if (!receipt) return false;
         const original = receipt.categories ?? [];
@@ -92,10 +86,11 @@ export function ReceiptDrawer({
 
     if (!receipt) return null;
 
-    const baseCurrency = supportedSet.has(receipt.currency as CurrencyCode)
-        ? (receipt.currency as CurrencyCode)
-        : "USD";
-    const convertedAmount = format(convert(receipt.amount, baseCurrency));
+    const amountInBase = baseAmount(receipt);
+    const convertedAmount =
+        amountInBase === null
+            ? format(receipt.amount, receipt.currency)
+            : format(amountInBase);
     const originalAmount = new Intl.NumberFormat(undefined, {
         style: "currency",
         currency: receipt.currency ?? "USD",
@@ -3,7 +3,6 @@
 import { useCallback, useMemo, useState } from "react";
 
 import { useCurrency } from "@/context/currency-context";
-import type { CurrencyCode } from "@/lib/config";
 import type { Receipt } from "@/types";
 import {
     Badge,
@@ -42,22 +41,17 @@ export function ReceiptsTable({
         key: null,
         dir: "asc",
     });
-    const { convert, format, supported } = useCurrency();
-    const supportedSet = useMemo(
-        () => new Set<CurrencyCode>(supported),
-        [supported],
-    );
+    const { baseAmount, format } = useCurrency();
 
+    // falls back to the original currency, marked, when the backend had no rate
     const convertAmount = useCallback(
         (receipt: Receipt) => {
-            const baseCurrency = supportedSet.has(
-                receipt.currency as CurrencyCode,
-            )
-                ? (receipt.currency as CurrencyCode)
-                : "USD";
-            return format(convert(receipt.amount, baseCurrency));
+            const amount = baseAmount(receipt);
+            return amount === null
+                ? `${format(receipt.amount, receipt.currency)} (no rate)`
+                : format(amount);
         },
-        [convert, format, supportedSet],
+        [baseAmount, format],
     );
 
     const toTitleCase = (value: string) =>
@@ -90,7 +84,11 @@ export function ReceiptsTable({
                         ) * dirFactor
                     );
                 case "amount":
-                    return (a.amount - b.amount) * dirFactor;
+                    return (
+                        ((baseAmount(a) ?? a.amount) -
+                            (baseAmount(b) ?? b.amount)) *
+                        dirFactor
+                    );
                 case "timestamp":
                 default:
                     return (
@@ -101,7 +99,7 @@ export function ReceiptsTable({
             }
         });
         return next;
-    }, [receipts, sort]);
+    }, [baseAmount, receipts, sort]);
 
     const toggleSort = (key: SortKey) => {
         setSort((prev) => {
@@ -9,47 +9,79 @@ import {
     useState,
 } from "react";
 
+import { apiFetch } from "@/lib/api";
 import {
     CurrencyCode,
     currencyLabels,
-    currencyRates,
     currencySymbols,
+    supportedCurrencies,
 } from "@/lib/config";
+import type { ApiResponse, Receipt } from "@/types";
 
 type CurrencyContextValue = {
+    // the signed-in user's base currency, which receipts are converted to
     currency: CurrencyCode;
-    setCurrency: (code: CurrencyCode) => void;
-    convert: (amount: number, from: CurrencyCode, to?: CurrencyCode) => number;
+    setCurrency: (code: CurrencyCode) => Promise<void>;
+    // the receipt's amount in the base currency, or null when the backend
+    // had no rate for it
+    baseAmount: (receipt: Receipt) => number | null;
     format: (amount: number, code?: CurrencyCode, options?: Intl.NumberFormatOptions) => string;
     supported: CurrencyCode[];
 };
 
-const STORAGE_KEY = "finos.currency";
+type BaseCurrency = {
+    currency: CurrencyCode;
+};
+
+const DEFAULT_CURRENCY: CurrencyCode = "USD";
 const CurrencyContext = createContext<CurrencyContextValue | null>(null);
 
 export function CurrencyProvider({ children }: { children: React.ReactNode }) {
-    const [currency, setCurrencyState] = useState<CurrencyCode>("USD");
+    const [currency, setCurrencyState] =
+        useState<CurrencyCode>(DEFAULT_CURRENCY);
 
     useEffect(() => {
-        const stored = window.localStorage.getItem(STORAGE_KEY) as
-            | CurrencyCode
-            | null;
-        if (stored && currencyRates[stored]) {
-            setCurrencyState(stored);
-        }
+        const controller = new AbortController();
+        apiFetch<ApiResponse<BaseCurrency>>("/fx/base-currency", {
+            signal: controller.signal,
+        })
+            .then((payload) => {
+                if (payload.success && payload.data?.currency) {
+                    setCurrencyState(payload.data.currency);
+                }
+            })
+            // signed out or offline: keep the default until the next load
+            .catch(() => undefined);
+        return () => controller.abort();
     }, []);
 
-    const setCurrency = useCallback((code: CurrencyCode) => {
-        setCurrencyState(code);
-        window.localStorage.setItem(STORAGE_KEY, code);
+    // The backend re-converts the stored receipts before answering, so
+    // consumers keyed on `currency` can refetch straight away.
+    const setCurrency = useCallback(async (code: CurrencyCode) => {
+        try {
+            const payload = await apiFetch<ApiResponse<unknown>>(
+                "/fx/base-currency",
+                {
+                    method: "PUT",
+                    body: JSON.stringify({ currency: code }),
+                },
+            );
+            if (!payload.success) {
+                throw new Error(payload.error ?? "Backend declined the change.");
+            }
+            setCurrencyState(code);
+        } catch (err) {
+            console.warn("Failed to set base currency", err);
+        }
     }, []);
 
-    const convert = useCallback(
-        (amount: number, from: CurrencyCode, to: CurrencyCode = currency) => {
-            const fromRate = currencyRates[from] ?? 1;
-            const toRate = currencyRates[to] ?? 1;
-            const usdValue = amount * fromRate;
-            return usdValue / toRate;
+    const baseAmount = useCallback(
+        (receipt: Receipt) => {
+            if (receipt.converted?.currency === currency) {
+                return receipt.converted.amount;
+            }
+            if (receipt.currency === currency) return receipt.amount;
+            return null;
         },
         [currency],
     );
@@ -68,15 +100,23 @@ export function CurrencyProvider({ children }: { children: React.ReactNode }) {
         [currency],
     );
 
+    const supported = useMemo(
+        () =>
+            supportedCurrencies.includes(currency)
+                ? supportedCurrencies
+                : [currency, ...supportedCurrencies],
+        [currency],
+    );
+
     const value = useMemo<CurrencyContextValue>(
         () => ({
             currency,
             setCurrency,
-            convert,
+            baseAmount,
             format,
-            supported: Object.keys(currencyRates) as CurrencyCode[],
+            supported,
         }),
-        [convert, currency, format, setCurrency],
+        [baseAmount, currency, format, setCurrency, supported],
     );
 
     return (
@@ -95,5 +135,5 @@ export function useCurrency() {
 }
 
 export function describeCurrency(code: CurrencyCode) {
-    return `${currencySymbols[code]} ${currencyLabels[code]}`;
+    return `${currencySymbols[code] ?? code} ${currencyLabels[code] ?? code}`;
 }
@@ -2,6 +2,7 @@
 
 import { useCallback, useEffect, useMemo, useState } from "react";
 
+import { useCurrency } from "@/context/currency-context";
 import { apiFetch, ApiError } from "@/lib/api";
 import { applyFilters } from "@/lib/analytics";
 import { mapBackendReceipts } from "@/lib/receipts";
@@ -16,6 +17,8 @@ export function useReceipts(filters: ReceiptFilters) {
     const [receipts, setReceipts] = useState<Receipt[]>([]);
     const [loading, setLoading] = useState(false);
     const [error, setError] = useState<string | null>(null);
+    // conversions change with the base currency, so reload when it does
+    const { currency } = useCurrency();
 
     const fetchReceipts = useCallback(
         async (signal?: AbortSignal) => {
@@ -63,7 +66,8 @@ export function useReceipts(filters: ReceiptFilters) {
                 setLoading(false);
             }
         },
-        [filters.email],
+        // eslint-disable-next-line react-hooks/exhaustive-deps
+        [filters.email, currency],
     );
 
     useEffect(() => {
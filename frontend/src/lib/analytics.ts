@@ -9,7 +9,24 @@ import type {
 
 type AmountProjector = (receipt: Receipt) => number;
 
-const defaultProjector: AmountProjector = (receipt) => receipt.amount;
+// How a receipt counts towards spend, as in the backend's per-instrument
+// totals: refunds and reversals give money back, credits and transfers are
+// not spend. Amounts are stored positive whatever the kind.
+export function spendSign(receipt: Receipt): number {
+    switch (receipt.kind) {
+        case "refund":
+        case "reversal":
+            return -1;
+        case "credit":
+        case "transfer":
+            return 0;
+        default:
+            return 1;
+    }
+}
+
+const defaultProjector: AmountProjector = (receipt) =>
+    spendSign(receipt) * Math.abs(receipt.amount);
 
 const rangeToDays: Record<ReceiptFilters["range"], number> = {
     "7d": 7,
@@ -2,21 +2,23 @@ const FALLBACK_API_BASE = "http://localhost:4000";
 
 export type CurrencyCode = string;
 
-export const currencyRates: Record<string, number> = {
-    USD: 1, // base
-    EUR: 1.08,
-    SGD: 0.74,
-    GBP: 1.27,
-    JPY: 0.0066,
-    AUD: 0.68,
-    CAD: 0.74,
-    INR: 0.012,
-    CHF: 1.13,
-    HKD: 0.13,
-};
+// Offered as base currencies; the backend converts receipts with its own
+// rate table.
+export const supportedCurrencies: CurrencyCode[] = [
+    "USD",
+    "EUR",
+    "SGD",
+    "GBP",
+    "JPY",
+    "AUD",
+    "CAD",
+    "INR",
+    "CHF",
+    "HKD",
+];
 
 export const currencyLabels: Record<string, string> = Object.fromEntries(
-    Object.keys(currencyRates).map((code) => [code, code]),
+    supportedCurrencies.map((code) => [code, code]),
 );
 
 export const currencySymbols: Record<string, string> = {
@@ -1,10 +1,11 @@
 import { apiFetch } from "@/lib/api";
-import { currencyRates } from "@/lib/config";
 import type {
     ApiResponse,
+    BackendFxConversion,
     BackendReceipt,
     BackendReceiptList,
     Receipt,
+    TransactionKind,
 } from "@/types";
 
 const DEFAULT_OWNER = "unknown@finos.app";
@@ -18,6 +19,12 @@ function normalizeTimestamp(value?: number | null): number {
     return value > 10_000_000_000 ? value : value * 1000;
 }
 
+function parseAmount(value?: string | number | null): number {
+    if (value === null || value === undefined) return 0;
+    const amount = typeof value === "number" ? value : Number.parseFloat(value);
+    return Number.isFinite(amount) ? amount : 0;
+}
+
 function normalizeCategories(categories?: (string | null)[] | null): string[] {
     if (!categories) return [];
     return categories
@@ -30,10 +37,30 @@ function sanitizeCurrency(code?: string | null): string {
     if (!normalized) return DEFAULT_CURRENCY;
     if (normalized.length !== 3) return DEFAULT_CURRENCY;
     if (!/^[A-Z]{3}$/.test(normalized)) return DEFAULT_CURRENCY;
-    if (!currencyRates[normalized]) return DEFAULT_CURRENCY;
     return normalized;
 }
 
+const TRANSACTION_KINDS: TransactionKind[] = [
+    "purchase",
+    "refund",
+    "reversal",
+    "credit",
+    "transfer",
+];
+
+function normalizeKind(kind?: string | null): TransactionKind {
+    const normalized = kind?.trim().toLowerCase() as TransactionKind;
+    return TRANSACTION_KINDS.includes(normalized) ? normalized : "purchase";
+}
+
+function mapConversion(converted?: BackendFxConversion | null) {
+    if (!converted?.currency || converted.amount == null) return undefined;
+    return {
+        currency: sanitizeCurrency(converted.currency),
+        amount: parseAmount(converted.amount),
+    };
+}
+
 export function mapBackendReceipts(
     receipts: BackendReceipt[] = [],
     fallbackOwner?: string,
@@ -58,8 +85,11 @@ export function mapBackendReceipts(
             owner,
             issuer,
             merchant,
-            amount: receipt.amount ?? 0,
+            amount: parseAmount(receipt.amount),
             currency,
+            converted: mapConversion(receipt.converted),
+            kind: normalizeKind(receipt.kind),
+            refundOf: receipt.refund_of ?? undefined,
             categories: normalizeCategories(receipt.categories),
             timestamp: new Date(timestampMs).toISOString(),
         };
@@ -17,10 +17,30 @@ export type BackendReceipt = {
     owner?: string | null;
     issuer?: string | null;
     merchant?: string | null;
-    amount?: number | null;
+    // exact decimal string, e.g. "12.50"
+    amount?: string | number | null;
     currency?: string | null;
     categories?: (string | null)[] | null;
     timestamp?: number | null;
+    converted?: BackendFxConversion | null;
+    kind?: TransactionKind | null;
+    // msg_id of the purchase a refund gives money back for
+    refund_of?: string | null;
+};
+
+export type TransactionKind =
+    | "purchase"
+    | "refund"
+    | "reversal"
+    | "credit"
+    | "transfer";
+
+// The amount in the owner's base currency, as converted by the backend.
+export type BackendFxConversion = {
+    currency?: string | null;
+    amount?: string | number | null;
+    rate?: string | number | null;
+    rate_date?: string | null;
 };
 
 export type BackendReceiptList = {
@@ -35,6 +55,12 @@ export type Receipt = {
     merchant: string;
     amount: number;
     currency: string;
+    converted?: {
+        currency: string;
+        amount: number;
+    };
+    kind: TransactionKind;
+    refundOf?: string;
     categories: string[];
     timestamp: string; // ISO string for easier charting
     notes?: string;